@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use pgn_reader::SanPlus;
+use serde::Deserialize;
+use specta::Type;
+use vampirc_uci::uci::ScoreValue;
+
+use super::{MoveAnalysis, MoveClassification, Novelty};
+use crate::{
+    db::{annotate_game, format_eval, read_game_tree, MoveAnnotation},
+    error::Error,
+    AppState,
+};
+
+/// Plies of the engine's line written as a variation.
+const VARIATION_PLIES: usize = 8;
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotateOptions {
+    /// Move-quality NAGs from the classifications.
+    pub nags: bool,
+    /// `[%eval]` comments with the score after every move.
+    pub evals: bool,
+    /// The engine's line after inaccuracies and worse moves.
+    pub variations: bool,
+    /// A comment on the novelty saying what was played before it.
+    pub novelties: bool,
+}
+
+fn eval_comment(analysis: &MoveAnalysis) -> Option<String> {
+    match analysis.best.first()?.score.value {
+        ScoreValue::Cp(cp) => format_eval(Some(cp), None),
+        ScoreValue::Mate(mate) => format_eval(None, Some(mate as i32)),
+    }
+}
+
+/// Annotations for every main line move, where `analysis[i]` is the
+/// position before move `i`.
+fn annotations(analysis: &[MoveAnalysis], options: &AnnotateOptions) -> Vec<MoveAnnotation> {
+    analysis
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let nag = entry
+                .classification
+                .filter(|_| options.nags)
+                .and_then(MoveClassification::nag);
+            let eval = analysis
+                .get(i + 1)
+                .filter(|_| options.evals)
+                .and_then(eval_comment);
+            let note = analysis
+                .get(i + 1)
+                .filter(|_| options.novelties)
+                .and_then(|next| next.novelty_info.as_ref())
+                .map(Novelty::comment);
+            let mistake = matches!(
+                entry.classification,
+                Some(
+                    MoveClassification::Inaccuracy
+                        | MoveClassification::Mistake
+                        | MoveClassification::Blunder
+                        | MoveClassification::MissedWin
+                )
+            );
+            let variation = match entry.best.first() {
+                Some(line) if options.variations && mistake => line
+                    .san_moves
+                    .iter()
+                    .take(VARIATION_PLIES)
+                    .map_while(|san| SanPlus::from_ascii(san.as_bytes()).ok())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            MoveAnnotation {
+                nag,
+                eval,
+                note,
+                variation,
+            }
+        })
+        .collect()
+}
+
+/// Writes a game analysis into the first game of a PGN, returning the
+/// annotated PGN. Headers are kept as they are.
+#[tauri::command]
+#[specta::specta]
+pub async fn annotate_game_pgn(
+    pgn: String,
+    analysis: Vec<MoveAnalysis>,
+    options: AnnotateOptions,
+) -> Result<String, Error> {
+    let mut game = read_game_tree(&pgn)?;
+    game.tree.annotate(&annotations(&analysis, &options));
+
+    let headers: Vec<&str> = pgn
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| line.starts_with('['))
+        .collect();
+    let result = game.result.as_deref().unwrap_or("*");
+    let movetext = format!("{} {}", game.tree.to_string().trim(), result);
+
+    if headers.is_empty() {
+        Ok(format!("{}\n", movetext.trim()))
+    } else {
+        Ok(format!("{}\n\n{}\n", headers.join("\n"), movetext.trim()))
+    }
+}
+
+/// Writes a game analysis into a database game's moves.
+#[tauri::command]
+#[specta::specta]
+pub async fn annotate_db_game(
+    file: PathBuf,
+    game_id: i32,
+    analysis: Vec<MoveAnalysis>,
+    options: AnnotateOptions,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    annotate_game(&state, &file, game_id, &annotations(&analysis, &options))?;
+    Ok(())
+}
@@ -0,0 +1,467 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use dashmap::mapref::entry::Entry;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, uci::UciMove, CastlingMode, Chess, Position};
+use specta::Type;
+use tauri::{path::BaseDirectory, Manager};
+use tauri_specta::Event;
+use vampirc_uci::uci::ScoreValue;
+
+use super::{
+    analyze_with_retries, emit_engine_status, ensure_multipv_option, transport::EngineReader,
+    BestMoves, EngineLaunch, EngineOption, EngineOptions, EngineProcess, EngineStatus, GoMode,
+    ReportProgress,
+};
+use crate::{
+    db::{game_main_line, get_games, store_evaluations, GameQueryJs, NewEvaluation},
+    error::Error,
+    AppState,
+};
+
+/// One file per job, so a job survives a crash of the app.
+const JOBS_DIR: &str = "engines/batch-jobs";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchStatus {
+    Running,
+    /// Stopped by the user or by the app exiting; can be resumed.
+    Paused,
+    Cancelled,
+    Finished,
+}
+
+/// A database analysis job, saved after every game.
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchJob {
+    pub id: String,
+    pub file: PathBuf,
+    pub engine: String,
+    pub go_mode: GoMode,
+    pub uci_options: Vec<EngineOption>,
+    #[serde(default)]
+    #[specta(optional)]
+    pub launch: Option<EngineLaunch>,
+    /// The games to analyse, in order, fixed when the job starts.
+    pub game_ids: Vec<i32>,
+    /// Games done; the job resumes with `game_ids[completed]`.
+    pub completed: u32,
+    /// Games that could not be read, skipped.
+    pub failed: Vec<i32>,
+    pub status: BatchStatus,
+}
+
+/// Asks a running job to stop between two positions. The game in progress
+/// is analysed again on resume.
+#[derive(Debug, Default)]
+pub struct BatchControl {
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl BatchControl {
+    fn should_stop(&self) -> bool {
+        self.paused.load(Ordering::Relaxed) || self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Job files are named after the id, so it may only hold characters that
+/// keep it a plain file name.
+fn check_job_id(id: &str) -> Result<(), Error> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidBatchJobId(id.to_string()))
+    }
+}
+
+fn job_path(app: &tauri::AppHandle, id: &str) -> Result<PathBuf, Error> {
+    check_job_id(id)?;
+    Ok(app
+        .path()
+        .resolve(format!("{}/{}.json", JOBS_DIR, id), BaseDirectory::AppData)?)
+}
+
+fn save_job(app: &tauri::AppHandle, job: &BatchJob) -> Result<(), Error> {
+    let path = job_path(app, &job.id)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(job)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+fn load_job(app: &tauri::AppHandle, id: &str) -> Result<BatchJob, Error> {
+    let path = job_path(app, id)?;
+    if !path.exists() {
+        return Err(Error::BatchJobNotFound(id.to_string()));
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn evaluation(game_id: i32, ply: usize, engine: &str, best: &BestMoves) -> NewEvaluation {
+    let (cp, mate) = match best.score.value {
+        ScoreValue::Cp(cp) => (Some(cp), None),
+        ScoreValue::Mate(mate) => (None, Some(mate as i32)),
+    };
+    let wdl = best.score.wdl;
+    NewEvaluation {
+        game_id,
+        ply: ply as i32,
+        engine: engine.to_string(),
+        depth: best.depth as i32,
+        cp,
+        mate,
+        wdl_win: wdl.map(|(win, _, _)| win as i32),
+        wdl_draw: wdl.map(|(_, draw, _)| draw as i32),
+        wdl_loss: wdl.map(|(_, _, loss)| loss as i32),
+        best_move: best.uci_moves.first().cloned(),
+    }
+}
+
+struct BatchEngine {
+    proc: EngineProcess,
+    reader: EngineReader,
+    uci_options: Vec<EngineOption>,
+    restarts: u32,
+}
+
+/// A stored game's start position, main line and the plies of it that
+/// have a position to analyse.
+struct StoredLine {
+    fen: String,
+    moves: Vec<String>,
+    plies: Vec<usize>,
+}
+
+fn stored_line(
+    job: &BatchJob,
+    game_id: i32,
+    state: &tauri::State<'_, AppState>,
+) -> Result<StoredLine, Error> {
+    let (fen, moves) = game_main_line(state, &job.file, game_id)?;
+    let mut pos: Chess = Fen::from_ascii(fen.as_bytes())?.into_position(CastlingMode::Chess960)?;
+
+    let mut plies = Vec::with_capacity(moves.len() + 1);
+    for ply in 0..=moves.len() {
+        if ply > 0 {
+            let mv = UciMove::from_ascii(moves[ply - 1].as_bytes())?.to_move(&pos)?;
+            pos.play_unchecked(&mv);
+        }
+        if !pos.is_game_over() {
+            plies.push(ply);
+        }
+    }
+    Ok(StoredLine { fen, moves, plies })
+}
+
+/// Evaluates every position of a stored game's main line, or `None` when
+/// the job was stopped first. Fails if the engine does on any position.
+async fn analyze_stored_game(
+    job: &BatchJob,
+    game_id: i32,
+    line: StoredLine,
+    engine: &mut BatchEngine,
+    control: &BatchControl,
+    app: &tauri::AppHandle,
+) -> Result<Option<Vec<NewEvaluation>>, Error> {
+    let total = job.game_ids.len() as f64;
+
+    let mut evals = Vec::with_capacity(line.plies.len());
+    for &ply in &line.plies {
+        if control.should_stop() {
+            return Ok(None);
+        }
+
+        let game_progress = ply as f64 / (line.moves.len() + 1) as f64;
+        ReportProgress {
+            progress: (job.completed as f64 + game_progress) / total * 100.0,
+            id: job.id.clone(),
+            finished: false,
+        }
+        .emit(app)?;
+
+        let options = EngineOptions {
+            fen: line.fen.clone(),
+            moves: line.moves[..ply].to_vec(),
+            extra_options: engine.uci_options.clone(),
+            ..Default::default()
+        };
+        let best = analyze_with_retries(
+            &mut engine.proc,
+            &mut engine.reader,
+            options,
+            &job.go_mode,
+            &mut engine.restarts,
+            (app, &job.engine, &job.id),
+        )
+        .await
+        .map_err(|e| {
+            warn!("Failed to analyze game {} at ply {}: {}", game_id, ply, e);
+            e
+        })?;
+        evals.extend(best.first().map(|best| evaluation(game_id, ply, &job.engine, best)));
+    }
+    Ok(Some(evals))
+}
+
+async fn analyze_games(
+    job: &mut BatchJob,
+    control: &BatchControl,
+    app: &tauri::AppHandle,
+    state: &tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    let (proc, reader) =
+        EngineProcess::new(PathBuf::from(&job.engine), &job.launch.clone().unwrap_or_default())
+            .await?;
+    emit_engine_status(app, &job.engine, &job.id, EngineStatus::Started);
+    let mut uci_options = job.uci_options.clone();
+    ensure_multipv_option(&mut uci_options, &proc.config);
+    let mut engine = BatchEngine {
+        proc,
+        reader,
+        uci_options,
+        restarts: 0,
+    };
+
+    let result: Result<(), Error> = async {
+        while let Some(&game_id) = job.game_ids.get(job.completed as usize) {
+            // Engine failures end the job as paused, and the game is analysed
+            // again on resume; games that can't be read are skipped
+            match stored_line(job, game_id, state) {
+                Ok(line) => {
+                    let analysed =
+                        analyze_stored_game(job, game_id, line, &mut engine, control, app).await?;
+                    match analysed {
+                        Some(evals) => store_evaluations(state, &job.file, &evals)?,
+                        None => break,
+                    }
+                }
+                Err(e) => {
+                    warn!("Skipping game {} in batch analysis {}: {}", game_id, job.id, e);
+                    job.failed.push(game_id);
+                }
+            }
+            job.completed += 1;
+            save_job(app, job)?;
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = engine.proc.kill().await {
+        warn!("Failed to quit engine after batch analysis: {}", e);
+    }
+    result
+}
+
+/// Runs a job until it is done, paused or cancelled. A cancelled job is
+/// forgotten; the evaluations it stored are kept.
+async fn run_job(
+    mut job: BatchJob,
+    app: &tauri::AppHandle,
+    state: &tauri::State<'_, AppState>,
+) -> Result<BatchJob, Error> {
+    let control = Arc::new(BatchControl::default());
+    match state.batch_jobs.entry(job.id.clone()) {
+        Entry::Occupied(_) => return Err(Error::BatchJobRunning(job.id)),
+        Entry::Vacant(entry) => {
+            entry.insert(control.clone());
+        }
+    }
+
+    job.status = BatchStatus::Running;
+    let result = match save_job(app, &job) {
+        Ok(()) => analyze_games(&mut job, &control, app, state).await,
+        Err(e) => Err(e),
+    };
+    state.batch_jobs.remove(&job.id);
+
+    job.status = if control.cancelled.load(Ordering::Relaxed) {
+        BatchStatus::Cancelled
+    } else if result.is_err() || job.completed < job.game_ids.len() as u32 {
+        BatchStatus::Paused
+    } else {
+        BatchStatus::Finished
+    };
+    if job.status == BatchStatus::Cancelled {
+        fs::remove_file(job_path(app, &job.id)?)?;
+    } else {
+        save_job(app, &job)?;
+    }
+    result?;
+
+    info!(
+        "Batch analysis {} {:?}: {}/{} games",
+        job.id,
+        job.status,
+        job.completed,
+        job.game_ids.len()
+    );
+    ReportProgress {
+        progress: job.completed as f64 / job.game_ids.len().max(1) as f64 * 100.0,
+        id: job.id.clone(),
+        finished: true,
+    }
+    .emit(app)?;
+    Ok(job)
+}
+
+/// Analyses every game of a database matching `query`, storing one
+/// evaluation per main line position. Resolves when the job finishes or is
+/// paused or cancelled.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_batch_analysis(
+    id: String,
+    file: PathBuf,
+    mut query: GameQueryJs,
+    engine: String,
+    go_mode: GoMode,
+    uci_options: Vec<EngineOption>,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<BatchJob, Error> {
+    check_job_id(&id)?;
+    // Every matching game, not one page of them
+    if let Some(options) = query.options.as_mut() {
+        options.page = None;
+        options.page_size = None;
+        options.skip_count = true;
+    }
+    let games = get_games(file.clone(), query, state.clone()).await?;
+    info!("Starting batch analysis {}: {} games", id, games.data.len());
+
+    let job = BatchJob {
+        id,
+        file,
+        engine,
+        go_mode,
+        uci_options,
+        launch,
+        game_ids: games.data.iter().map(|game| game.id).collect(),
+        completed: 0,
+        failed: Vec::new(),
+        status: BatchStatus::Running,
+    };
+    run_job(job, &app, &state).await
+}
+
+/// Continues a paused job, or one interrupted by the app closing.
+#[tauri::command]
+#[specta::specta]
+pub async fn resume_batch_analysis(
+    id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<BatchJob, Error> {
+    let job = load_job(&app, &id)?;
+    info!(
+        "Resuming batch analysis {} at game {}/{}",
+        id,
+        job.completed,
+        job.game_ids.len()
+    );
+    run_job(job, &app, &state).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pause_batch_analysis(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    if let Some(control) = state.batch_jobs.get(&id) {
+        info!("Pausing batch analysis {}", id);
+        control.paused.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_batch_analysis(
+    id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    if let Some(control) = state.batch_jobs.get(&id) {
+        info!("Cancelling batch analysis {}", id);
+        control.cancelled.store(true, Ordering::Relaxed);
+        return Ok(());
+    }
+    let path = job_path(&app, &id)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Saved jobs, including ones a crash left marked as running.
+#[tauri::command]
+#[specta::specta]
+pub async fn list_batch_analyses(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<BatchJob>, Error> {
+    let dir = app.path().resolve(JOBS_DIR, BaseDirectory::AppData)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut jobs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let mut job: BatchJob = match fs::read_to_string(&path)
+            .map_err(Error::from)
+            .and_then(|text| Ok(serde_json::from_str(&text)?))
+        {
+            Ok(job) => job,
+            Err(e) => {
+                warn!("Ignoring batch job {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if job.status == BatchStatus::Running && !state.batch_jobs.contains_key(&job.id) {
+            job.status = BatchStatus::Paused;
+        }
+        jobs.push(job);
+    }
+    jobs.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_ids_are_plain_file_names() {
+        assert!(check_job_id("analysis-1_a").is_ok());
+        assert!(check_job_id("").is_err());
+        assert!(check_job_id("../settings").is_err());
+        assert!(check_job_id("jobs/1").is_err());
+        assert!(check_job_id("C:job").is_err());
+    }
+}
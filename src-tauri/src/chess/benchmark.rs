@@ -0,0 +1,339 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri_specta::Event;
+use tokio::time::{sleep, timeout};
+
+use super::{
+    registry, transport::EngineReader, EngineLaunch, EngineLog, EngineOption, EngineOptions,
+    EngineProcess, EngineProtocol, GoMode, ReportProgress,
+};
+use crate::error::Error;
+
+const DEFAULT_BENCH_DEPTH: u32 = 13;
+const BENCH_TIMEOUT: Duration = Duration::from_secs(300); // Whole built-in bench
+const POSITION_TIMEOUT: Duration = Duration::from_secs(120); // One fixed-depth search
+const STDERR_SETTLE: Duration = Duration::from_millis(200); // Stockfish prints the bench summary on stderr
+
+/// Positions searched when the engine has no `bench` command, taken from
+/// Stockfish's bench set so the two methods cover similar middlegames.
+const BENCH_POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum BenchmarkMethod {
+    /// The engine's own `bench` command.
+    Bench,
+    /// `BENCH_POSITIONS` searched to a fixed depth.
+    Positions,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionBenchmark {
+    pub fen: String,
+    pub depth: u32,
+    pub nodes: u64,
+    /// Time until the engine reached the target depth.
+    pub time_ms: u64,
+    pub nps: u64,
+}
+
+/// One benchmark run. Runs of the same method and depth can be compared
+/// across builds and machines: equal signatures mean the engines searched
+/// the same trees, so only the speed differs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineBenchmark {
+    pub engine: String,
+    pub method: BenchmarkMethod,
+    /// The fixed depth, for `Positions` runs.
+    #[specta(optional)]
+    pub depth: Option<u32>,
+    pub total_nodes: u64,
+    pub total_time_ms: u64,
+    pub nps: u64,
+    /// The node count, like Stockfish's bench signature. Only reproducible
+    /// with a single search thread.
+    pub signature: String,
+    /// Empty for `Bench` runs, which only report totals.
+    pub positions: Vec<PositionBenchmark>,
+    #[specta(optional)]
+    pub binary_hash: Option<String>,
+    /// RFC 3339 time the run finished.
+    pub date: String,
+}
+
+impl EngineBenchmark {
+    fn new(
+        engine: &str,
+        method: BenchmarkMethod,
+        depth: Option<u32>,
+        total_nodes: u64,
+        total_time_ms: u64,
+        nps: u64,
+        positions: Vec<PositionBenchmark>,
+    ) -> Self {
+        Self {
+            engine: engine.to_string(),
+            method,
+            depth,
+            total_nodes,
+            total_time_ms,
+            nps,
+            signature: total_nodes.to_string(),
+            positions,
+            binary_hash: registry::hash_binary(Path::new(engine)),
+            date: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+fn nps(nodes: u64, time_ms: u64) -> u64 {
+    nodes * 1000 / time_ms.max(1)
+}
+
+/// Totals from the summary `bench` prints, e.g. `Nodes searched  : 1234567`.
+fn parse_bench_summary(entries: &[EngineLog]) -> Option<(u64, u64, u64)> {
+    let (mut nodes, mut time_ms, mut reported_nps) = (None, None, None);
+    for entry in entries {
+        let (EngineLog::Engine(line) | EngineLog::Stderr(line)) = entry else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<u64>() else {
+            continue;
+        };
+        match key.trim() {
+            "Nodes searched" => nodes = Some(value),
+            "Total time (ms)" => time_ms = Some(value),
+            "Nodes/second" => reported_nps = Some(value),
+            _ => {}
+        }
+    }
+    let nodes = nodes?;
+    let time_ms = time_ms.unwrap_or(0);
+    Some((nodes, time_ms, reported_nps.unwrap_or_else(|| nps(nodes, time_ms))))
+}
+
+/// Runs `bench` with `options` set and waits for it through `isready`,
+/// which the engine only answers once the bench is done. `None` when the
+/// engine printed no summary, meaning it does not know the command.
+async fn run_bench(
+    proc: &mut EngineProcess,
+    reader: &mut EngineReader,
+    options: &[EngineOption],
+) -> Result<Option<(u64, u64, u64)>, Error> {
+    proc.set_options(EngineOptions {
+        fen: BENCH_POSITIONS[0].to_string(),
+        extra_options: options.to_vec(),
+        ..Default::default()
+    })
+    .await?;
+    EngineProcess::send_command_with_log(&mut proc.stdin, "bench\n", &proc.logs).await?;
+    EngineProcess::send_command_with_log(&mut proc.stdin, "isready\n", &proc.logs).await?;
+
+    let wait_ready = async {
+        while let Some(line) = reader.next_line().await? {
+            proc.logs.push(EngineLog::Engine(line.clone()));
+            if line == "readyok" {
+                return Ok(());
+            }
+        }
+        Err(Error::EngineExited)
+    };
+    timeout(BENCH_TIMEOUT, wait_ready)
+        .await
+        .map_err(|_| Error::EngineTimeout)??;
+
+    sleep(STDERR_SETTLE).await;
+    Ok(parse_bench_summary(&proc.logs.entries()))
+}
+
+async fn run_positions(
+    proc: &mut EngineProcess,
+    reader: &mut EngineReader,
+    depth: u32,
+    options: &[EngineOption],
+    id: &str,
+    app: &tauri::AppHandle,
+) -> Result<Vec<PositionBenchmark>, Error> {
+    let mut results = Vec::with_capacity(BENCH_POSITIONS.len());
+    for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+        ReportProgress {
+            progress: (i as f64 / BENCH_POSITIONS.len() as f64) * 100.0,
+            id: id.to_string(),
+            finished: false,
+        }
+        .emit(app)?;
+
+        // A fresh game clears the hash, so results don't depend on the order
+        proc.new_game().await?;
+        proc.set_options(EngineOptions {
+            fen: fen.to_string(),
+            extra_options: options.to_vec(),
+            ..Default::default()
+        })
+        .await?;
+
+        let started = Instant::now();
+        proc.go(&GoMode::Depth(depth)).await?;
+        let result = timeout(POSITION_TIMEOUT, proc.wait_for_best_move(reader))
+            .await
+            .map_err(|_| Error::EngineTimeout)??;
+        let elapsed = started.elapsed().as_millis() as u64;
+
+        let line = result.lines.first();
+        let nodes = line.map_or(0, |l| l.nodes);
+        let time_ms = line.map(|l| l.time).filter(|&t| t > 0).unwrap_or(elapsed);
+        debug!("Benchmark position {}: {} nodes in {}ms", i, nodes, time_ms);
+        results.push(PositionBenchmark {
+            fen: fen.to_string(),
+            depth: line.map_or(depth, |l| l.depth),
+            nodes,
+            time_ms,
+            nps: nps(nodes, time_ms),
+        });
+    }
+    Ok(results)
+}
+
+/// Benchmarks an engine with its built-in `bench` when it has one, or by
+/// searching a fixed set of positions to `depth` otherwise. The result is
+/// stored with the engine's registry entry when it is registered.
+#[tauri::command]
+#[specta::specta]
+pub async fn benchmark_engine(
+    id: String,
+    engine: String,
+    depth: Option<u32>,
+    options: Vec<EngineOption>,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+) -> Result<EngineBenchmark, Error> {
+    info!("Benchmarking engine {}", engine);
+    let (mut proc, mut reader) =
+        EngineProcess::new(PathBuf::from(&engine), &launch.unwrap_or_default()).await?;
+
+    // Only UCI engines are known to have `bench`; a custom depth asks for
+    // the positions, since `bench` picks its own.
+    let bench = match proc.protocol {
+        EngineProtocol::Uci if depth.is_none() => {
+            match run_bench(&mut proc, &mut reader, &options).await {
+                // The engine may still be busy with `bench`, so the positions
+                // are searched by a fresh one
+                Err(Error::EngineTimeout) => {
+                    warn!("Engine {} timed out running bench, searching positions instead", engine);
+                    if let Some(child) = proc.child.as_mut() {
+                        let _ = child.kill().await;
+                    }
+                    proc.respawn().await.map(|fresh| {
+                        reader = fresh;
+                        None
+                    })
+                }
+                bench => bench,
+            }
+        }
+        _ => Ok(None),
+    };
+
+    let result = match bench {
+        Ok(Some((total_nodes, total_time_ms, nps))) => Ok(EngineBenchmark::new(
+            &engine,
+            BenchmarkMethod::Bench,
+            None,
+            total_nodes,
+            total_time_ms,
+            nps,
+            Vec::new(),
+        )),
+        Ok(None) => {
+            let depth = depth.unwrap_or(DEFAULT_BENCH_DEPTH);
+            run_positions(&mut proc, &mut reader, depth, &options, &id, &app)
+                .await
+                .map(|positions| {
+                    let total_nodes = positions.iter().map(|p| p.nodes).sum();
+                    let total_time_ms = positions.iter().map(|p| p.time_ms).sum();
+                    EngineBenchmark::new(
+                        &engine,
+                        BenchmarkMethod::Positions,
+                        Some(depth),
+                        total_nodes,
+                        total_time_ms,
+                        nps(total_nodes, total_time_ms),
+                        positions,
+                    )
+                })
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = proc.kill().await {
+        warn!("Failed to quit engine after benchmark: {}", e);
+    }
+    let benchmark = result?;
+
+    ReportProgress {
+        progress: 100.0,
+        id,
+        finished: true,
+    }
+    .emit(&app)?;
+
+    info!(
+        "Benchmark of {}: {} nodes, {} nps",
+        engine, benchmark.total_nodes, benchmark.nps
+    );
+
+    if !registry::record_benchmark(&app, &engine, &benchmark).await? {
+        debug!("Engine {} is not registered, benchmark not stored", engine);
+    }
+    Ok(benchmark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stockfish_bench_summary() {
+        let entries = vec![
+            EngineLog::Gui("bench\n".to_string()),
+            EngineLog::Engine("Position: 1/50 (rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1)".to_string()),
+            EngineLog::Engine("bestmove e2e4 ponder c7c5".to_string()),
+            EngineLog::Stderr("===========================".to_string()),
+            EngineLog::Stderr("Total time (ms) : 2184".to_string()),
+            EngineLog::Stderr("Nodes searched  : 2030154".to_string()),
+            EngineLog::Stderr("Nodes/second    : 929558".to_string()),
+            EngineLog::Engine("readyok".to_string()),
+        ];
+        assert_eq!(parse_bench_summary(&entries), Some((2030154, 2184, 929558)));
+    }
+
+    #[test]
+    fn ignores_engines_without_bench() {
+        let entries = vec![
+            EngineLog::Gui("bench\n".to_string()),
+            EngineLog::Engine("info string Unknown command: bench".to_string()),
+            EngineLog::Engine("readyok".to_string()),
+        ];
+        assert_eq!(parse_bench_summary(&entries), None);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use diesel::{
+    connection::SimpleConnection,
+    prelude::*,
+    sql_query,
+    sql_types::{Integer, Text},
+};
+use log::{debug, info, warn};
+use tauri::{path::BaseDirectory, Manager};
+
+use super::{
+    engine_match::position_key, position_after, BestMoves, EngineOptions, EngineProcess,
+};
+use crate::{error::Error, AppState};
+
+const CACHE_FILE: &str = "engines/analysis-cache.db3";
+
+const CREATE_CACHE_SQL: &str = "CREATE TABLE IF NOT EXISTS Analysis (
+    Engine TEXT NOT NULL,
+    Fen TEXT NOT NULL,
+    MultiPv INTEGER NOT NULL,
+    Depth INTEGER NOT NULL,
+    Lines TEXT NOT NULL,
+    PRIMARY KEY (Engine, Fen, MultiPv)
+);";
+
+/// Identifies a cached analysis. The FEN is normalized (move counters are
+/// dropped) and already includes the moves played from the root position.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnalysisCacheKey {
+    pub fen: String,
+    pub engine: String,
+    pub multipv: u16,
+}
+
+impl AnalysisCacheKey {
+    pub fn new(engine: &str, options: &EngineOptions) -> Result<Self, Error> {
+        let pos = position_after(&options.fen, &options.moves)?;
+
+        Ok(Self {
+            fen: position_key(&pos),
+            engine: engine.to_string(),
+            multipv: EngineProcess::calculate_multipv(options, &pos),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedAnalysis {
+    pub depth: u32,
+    pub lines: Vec<BestMoves>,
+}
+
+#[derive(QueryableByName)]
+struct CacheRow {
+    #[diesel(sql_type = Integer, column_name = "Depth")]
+    depth: i32,
+    #[diesel(sql_type = Text, column_name = "Lines")]
+    lines: String,
+}
+
+/// Stores the deepest lines seen for each position, engine and MultiPV.
+pub struct AnalysisCache {
+    conn: Mutex<SqliteConnection>,
+}
+
+impl AnalysisCache {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        info!("Opening analysis cache at {}", path.display());
+        let mut conn = SqliteConnection::establish(&path.to_string_lossy())?;
+        conn.batch_execute(CREATE_CACHE_SQL)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, SqliteConnection>, Error> {
+        self.conn
+            .lock()
+            .map_err(|_| Error::MutexLockFailed("Failed to lock analysis cache".to_string()))
+    }
+
+    pub fn get(&self, key: &AnalysisCacheKey) -> Result<Option<CachedAnalysis>, Error> {
+        let row: Option<CacheRow> = sql_query(
+            "SELECT Depth, Lines FROM Analysis WHERE Engine = ? AND Fen = ? AND MultiPv = ?",
+        )
+        .bind::<Text, _>(&key.engine)
+        .bind::<Text, _>(&key.fen)
+        .bind::<Integer, _>(key.multipv as i32)
+        .get_result(&mut *self.conn()?)
+        .optional()?;
+
+        row.map(|row| {
+            Ok(CachedAnalysis {
+                depth: row.depth as u32,
+                lines: serde_json::from_str(&row.lines)?,
+            })
+        })
+        .transpose()
+    }
+
+    /// Saves the lines unless a deeper analysis is already stored.
+    pub fn put(&self, key: &AnalysisCacheKey, lines: &[BestMoves]) -> Result<(), Error> {
+        let Some(depth) = lines.iter().map(|line| line.depth).min() else {
+            return Ok(());
+        };
+        if lines.len() < key.multipv as usize {
+            return Ok(());
+        }
+
+        debug!("Caching analysis for {} at depth {}", key.fen, depth);
+        sql_query(
+            "INSERT INTO Analysis (Engine, Fen, MultiPv, Depth, Lines) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (Engine, Fen, MultiPv) DO UPDATE
+             SET Depth = excluded.Depth, Lines = excluded.Lines
+             WHERE excluded.Depth > Analysis.Depth",
+        )
+        .bind::<Text, _>(&key.engine)
+        .bind::<Text, _>(&key.fen)
+        .bind::<Integer, _>(key.multipv as i32)
+        .bind::<Integer, _>(depth as i32)
+        .bind::<Text, _>(serde_json::to_string(lines)?)
+        .execute(&mut *self.conn()?)?;
+        Ok(())
+    }
+
+    pub fn clear(&self, engine: Option<&str>) -> Result<(), Error> {
+        let mut conn = self.conn()?;
+        match engine {
+            Some(engine) => {
+                sql_query("DELETE FROM Analysis WHERE Engine = ?")
+                    .bind::<Text, _>(engine)
+                    .execute(&mut *conn)?;
+            }
+            None => {
+                sql_query("DELETE FROM Analysis").execute(&mut *conn)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the shared analysis cache, opening it on first use. The cache is
+/// only an optimization, so failing to open it is logged and ignored.
+pub(super) fn analysis_cache(app: &tauri::AppHandle) -> Option<Arc<AnalysisCache>> {
+    let state = app.state::<AppState>();
+    state
+        .analysis_cache
+        .get_or_try_init(|| {
+            let path = app.path().resolve(CACHE_FILE, BaseDirectory::AppData)?;
+            Ok::<_, Error>(Arc::new(AnalysisCache::open(&path)?))
+        })
+        .map_err(|e| warn!("Failed to open analysis cache: {}", e))
+        .ok()
+        .cloned()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clear_analysis_cache(
+    engine: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<(), Error> {
+    if let Some(cache) = analysis_cache(&app) {
+        cache.clear(engine.as_deref())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use vampirc_uci::uci::{Score, ScoreValue};
+
+    fn line(depth: u32, multipv: u16) -> BestMoves {
+        BestMoves {
+            depth,
+            multipv,
+            score: Score {
+                value: ScoreValue::Cp(20),
+                ..Default::default()
+            },
+            uci_moves: vec!["e2e4".to_string()],
+            san_moves: vec!["e4".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn key() -> AnalysisCacheKey {
+        AnalysisCacheKey::new(
+            "engine",
+            &EngineOptions {
+                fen: Fen::default().to_string(),
+                moves: vec![],
+                extra_options: vec![],
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_deepest_lines() {
+        let cache = AnalysisCache::open(Path::new(":memory:")).unwrap();
+        let key = key();
+
+        assert!(cache.get(&key).unwrap().is_none());
+
+        cache.put(&key, &[line(20, 1)]).unwrap();
+        cache.put(&key, &[line(12, 1)]).unwrap();
+        assert_eq!(cache.get(&key).unwrap().unwrap().depth, 20);
+
+        cache.put(&key, &[line(25, 1)]).unwrap();
+        assert_eq!(cache.get(&key).unwrap().unwrap().depth, 25);
+
+        cache.clear(Some("engine")).unwrap();
+        assert!(cache.get(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn key_ignores_move_counters() {
+        let after_moves = AnalysisCacheKey::new(
+            "engine",
+            &EngineOptions {
+                fen: Fen::default().to_string(),
+                moves: vec!["g1f3".into(), "g8f6".into(), "f3g1".into(), "f6g8".into()],
+                extra_options: vec![],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(after_moves, key());
+    }
+}
@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::{uci::UciMove, Chess, Color, Position};
+use specta::Type;
+use vampirc_uci::uci::{Score, ScoreValue};
+
+use super::{AnalysisOptions, BestMoves, MoveAnalysis};
+use crate::error::Error;
+
+/// Scores are capped here, and mates count as this much, like the frontend does.
+const CP_CEILING: i32 = 1000;
+/// Win chance lost by a move, in percentage points, for each class.
+const BLUNDER_LOSS: f64 = 20.0;
+const MISTAKE_LOSS: f64 = 10.0;
+const INACCURACY_LOSS: f64 = 5.0;
+const GOOD_LOSS: f64 = 2.0;
+/// The best move is the only good one when the second line is this much worse.
+const ONLY_MOVE_GAP: f64 = 10.0;
+/// A position is won above this win chance and no longer won below
+/// `NOT_WINNING`.
+pub(super) const WINNING: f64 = 80.0;
+const NOT_WINNING: f64 = 60.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum MoveClassification {
+    Best,
+    Excellent,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+    /// A won position let slip.
+    MissedWin,
+    /// The best move, when every other move is much worse.
+    OnlyMove,
+    /// An only move that sacrifices material.
+    Brilliant,
+}
+
+impl MoveClassification {
+    /// The NAG written for the class; good moves get none.
+    pub fn nag(self) -> Option<u8> {
+        match self {
+            Self::Brilliant => Some(3),
+            Self::OnlyMove => Some(7),
+            Self::Inaccuracy => Some(6),
+            Self::Mistake | Self::MissedWin => Some(2),
+            Self::Blunder => Some(4),
+            Self::Best | Self::Excellent | Self::Good => None,
+        }
+    }
+}
+
+/// A white-relative score in centipawns, capped at `CP_CEILING`.
+fn white_cp(score: &Score) -> i32 {
+    match score.value {
+        ScoreValue::Cp(cp) => cp.clamp(-CP_CEILING, CP_CEILING),
+        ScoreValue::Mate(mate) => CP_CEILING * (mate as i32).signum(),
+    }
+}
+
+/// White's chance to win in percent, from the WDL statistics when the
+/// engine reports them and from the centipawn score otherwise.
+pub(super) fn white_win_chance(score: &Score) -> f64 {
+    if let Some((win, draw, _)) = score.wdl {
+        return (win as f64 + draw as f64 / 2.0) / 10.0;
+    }
+    let cp = white_cp(score);
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+/// The win chance of `color`, given a white-relative score.
+pub(super) fn win_chance(score: &Score, color: Color) -> f64 {
+    match color {
+        Color::White => white_win_chance(score),
+        Color::Black => 100.0 - white_win_chance(score),
+    }
+}
+
+/// A move with analysed positions before and after it, scored from the
+/// mover's side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct PlayedMove {
+    /// Index of the move, and of the analysis of the position before it.
+    pub index: usize,
+    pub turn: Color,
+    pub cp_before: i32,
+    pub cp_after: i32,
+    pub win_before: f64,
+    pub win_after: f64,
+}
+
+/// The moves of a game analysis, where entry `i` is the position before
+/// `moves[i]`. Moves next to a position that failed to analyse are left
+/// out; finished positions are not analysed and get their result instead.
+pub(super) fn played_moves(
+    analysis: &[MoveAnalysis],
+    start: Chess,
+    moves: &[String],
+) -> Result<Vec<PlayedMove>, Error> {
+    let mut played_moves = Vec::with_capacity(moves.len());
+    let mut pos = start;
+    for (index, played) in moves.iter().enumerate() {
+        let turn = pos.turn();
+        let mv = UciMove::from_ascii(played.as_bytes())?.to_move(&pos)?;
+        pos.play_unchecked(&mv);
+
+        let sign = if turn.is_white() { 1 } else { -1 };
+        let Some(before) = analysis.get(index).and_then(|entry| entry.best.first()) else {
+            continue;
+        };
+        let (cp_after, win_after) = match analysis.get(index + 1).and_then(|next| next.best.first()) {
+            Some(line) => (sign * white_cp(&line.score), win_chance(&line.score, turn)),
+            None if pos.is_checkmate() => (CP_CEILING, 100.0),
+            None if pos.is_game_over() => (0, 50.0),
+            None => continue,
+        };
+        played_moves.push(PlayedMove {
+            index,
+            turn,
+            cp_before: sign * white_cp(&before.score),
+            cp_after,
+            win_before: win_chance(&before.score, turn),
+            win_after,
+        });
+    }
+    Ok(played_moves)
+}
+
+/// Classifies `played` from the lines of the position it was played in and
+/// the mover's win chance afterwards. `None` without lines to compare to.
+fn classify(
+    before: &[BestMoves],
+    after: f64,
+    played: &str,
+    turn: Color,
+    is_sacrifice: bool,
+) -> Option<MoveClassification> {
+    let best = before.first()?;
+    let expected = win_chance(&best.score, turn);
+
+    if best.uci_moves.first().map(String::as_str) == Some(played) {
+        let only_move = before
+            .get(1)
+            .is_some_and(|second| expected - win_chance(&second.score, turn) > ONLY_MOVE_GAP);
+        return Some(match (only_move, is_sacrifice) {
+            (true, true) => MoveClassification::Brilliant,
+            (true, false) => MoveClassification::OnlyMove,
+            (false, _) => MoveClassification::Best,
+        });
+    }
+
+    let loss = expected - after;
+    Some(if expected >= WINNING && after < NOT_WINNING {
+        MoveClassification::MissedWin
+    } else if loss > BLUNDER_LOSS {
+        MoveClassification::Blunder
+    } else if loss > MISTAKE_LOSS {
+        MoveClassification::Mistake
+    } else if loss > INACCURACY_LOSS {
+        MoveClassification::Inaccuracy
+    } else if loss > GOOD_LOSS {
+        MoveClassification::Good
+    } else {
+        MoveClassification::Excellent
+    })
+}
+
+/// Fills in win chances and move classes of a game analysis, where entry `i`
+/// is the position before `moves[i]`.
+pub(super) fn classify_game(
+    analysis: &mut [MoveAnalysis],
+    start: Chess,
+    moves: &[String],
+) -> Result<(), Error> {
+    for entry in analysis.iter_mut() {
+        entry.win_chance = entry.best.first().map(|line| white_win_chance(&line.score));
+    }
+
+    for played in played_moves(analysis, start, moves)? {
+        // A move's sacrifice flag is on the position it leads to. Material
+        // given up for nothing is a mistake, not a sacrifice
+        let is_sacrifice = match analysis.get_mut(played.index + 1) {
+            Some(next) => {
+                next.is_sacrifice &= played.win_before - played.win_after <= INACCURACY_LOSS;
+                next.is_sacrifice
+            }
+            None => false,
+        };
+        let entry = &mut analysis[played.index];
+        entry.classification = classify(
+            &entry.best,
+            played.win_after,
+            &moves[played.index],
+            played.turn,
+            is_sacrifice,
+        );
+    }
+    Ok(())
+}
+
+/// Classifies the moves of `options`. Nothing is classified when the played
+/// moves were left out of the search: the lines are then the best
+/// alternatives, not the best moves.
+pub(super) fn classify_analysis(
+    analysis: &mut [MoveAnalysis],
+    start: Chess,
+    options: &AnalysisOptions,
+) -> Result<(), Error> {
+    if options.exclude_played_moves {
+        return Ok(());
+    }
+    classify_game(analysis, start, &options.moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(uci_move: &str, cp: i32) -> BestMoves {
+        BestMoves {
+            score: Score {
+                value: ScoreValue::Cp(cp),
+                ..Default::default()
+            },
+            uci_moves: vec![uci_move.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_by_lost_win_chance() {
+        let before = [line("e2e4", 30), line("d2d4", 25)];
+        let after = |cp| win_chance(&line("", cp).score, Color::White);
+
+        let class = |played, cp| classify(&before, after(cp), played, Color::White, false);
+        assert_eq!(class("e2e4", 30), Some(MoveClassification::Best));
+        assert_eq!(class("g1f3", 20), Some(MoveClassification::Excellent));
+        assert_eq!(class("b1c3", -80), Some(MoveClassification::Inaccuracy));
+        assert_eq!(class("f2f3", -150), Some(MoveClassification::Mistake));
+        assert_eq!(class("g2g4", -400), Some(MoveClassification::Blunder));
+        assert_eq!(classify(&[], 50.0, "e2e4", Color::White, false), None);
+    }
+
+    #[test]
+    fn scores_are_seen_from_the_mover() {
+        // White-relative +400 after a black move is a blunder by black
+        let before = [line("e7e5", 0)];
+        let after = win_chance(&line("", 400).score, Color::Black);
+        assert_eq!(
+            classify(&before, after, "f7f6", Color::Black, false),
+            Some(MoveClassification::Blunder)
+        );
+    }
+
+    #[test]
+    fn detects_only_moves_and_missed_wins() {
+        let forced = [line("d1h5", 500), line("d1d2", 0)];
+        assert_eq!(
+            classify(&forced, 95.0, "d1h5", Color::White, false),
+            Some(MoveClassification::OnlyMove)
+        );
+        assert_eq!(
+            classify(&forced, 95.0, "d1h5", Color::White, true),
+            Some(MoveClassification::Brilliant)
+        );
+        assert_eq!(
+            classify(&forced, 50.0, "d1d2", Color::White, false),
+            Some(MoveClassification::MissedWin)
+        );
+    }
+
+    #[test]
+    fn sacrifices_belong_to_the_move_before() {
+        // Only white's 1.e4 sacrifices, and its flag is on the position after it
+        let moves: Vec<String> = ["e2e4", "e7e5"].map(String::from).into();
+        let mut analysis = vec![
+            MoveAnalysis {
+                best: vec![line("e2e4", 100), line("d2d4", -300)],
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("e7e5", 100), line("c7c5", 600)],
+                is_sacrifice: true,
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("g1f3", 100)],
+                ..Default::default()
+            },
+        ];
+        classify_game(&mut analysis, Chess::default(), &moves).unwrap();
+        assert_eq!(analysis[0].classification, Some(MoveClassification::Brilliant));
+        assert_eq!(analysis[1].classification, Some(MoveClassification::OnlyMove));
+        assert!(analysis[1].is_sacrifice && !analysis[2].is_sacrifice);
+    }
+
+    #[test]
+    fn unsound_sacrifices_are_cleared() {
+        let moves: Vec<String> = ["e2e4", "e7e5"].map(String::from).into();
+        let mut analysis = vec![
+            MoveAnalysis {
+                best: vec![line("d2d4", 100)],
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("e7e5", -300)],
+                is_sacrifice: true,
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("g1f3", -300)],
+                is_sacrifice: true,
+                ..Default::default()
+            },
+        ];
+        classify_game(&mut analysis, Chess::default(), &moves).unwrap();
+        // White's lost, black's kept
+        assert!(!analysis[1].is_sacrifice);
+        assert!(analysis[2].is_sacrifice);
+    }
+
+    #[test]
+    fn excluded_moves_are_not_classified() {
+        // The only line is black's best alternative to the e7e5 it played
+        let options = AnalysisOptions {
+            moves: ["e2e4", "e7e5"].map(String::from).into(),
+            exclude_played_moves: true,
+            ..Default::default()
+        };
+        let mut analysis = vec![
+            MoveAnalysis {
+                best: vec![line("d2d4", 30)],
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("c7c5", 40)],
+                ..Default::default()
+            },
+            MoveAnalysis {
+                best: vec![line("g1f3", 30)],
+                ..Default::default()
+            },
+        ];
+        classify_analysis(&mut analysis, Chess::default(), &options).unwrap();
+        assert!(analysis.iter().all(|entry| entry.classification.is_none()));
+    }
+
+    #[test]
+    fn prefers_wdl() {
+        let score = Score {
+            value: ScoreValue::Cp(0),
+            wdl: Some((600, 300, 100)),
+            ..Default::default()
+        };
+        assert_eq!(white_win_chance(&score), 75.0);
+        assert_eq!(win_chance(&score, Color::Black), 25.0);
+    }
+}
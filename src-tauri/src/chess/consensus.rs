@@ -0,0 +1,515 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::future::join_all;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri_specta::Event;
+use tokio::{
+    sync::{mpsc, Mutex},
+    time::timeout,
+};
+use vampirc_uci::uci::ScoreValue;
+
+use super::{
+    calculate_progress, emit_engine_status, presets::apply_preset, transport::EngineReader,
+    BestMoves, EngineEvent, EngineLaunch, EngineLog, EngineOption, EngineOptions, EngineProcess,
+    EngineStatus, GoMode,
+};
+use crate::{error::Error, AppState};
+
+const CONSENSUS_INTERVAL: Duration = Duration::from_millis(100); // Minimum time between consensus events
+
+/// One engine taking part in a multi-engine analysis.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusEngine {
+    /// The engine path, as passed to the analysis commands.
+    pub engine: String,
+    #[serde(default)]
+    #[specta(optional)]
+    pub launch: Option<EngineLaunch>,
+    /// Options for this engine only, overriding the shared ones.
+    #[serde(default)]
+    pub options: Vec<EngineOption>,
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineLines {
+    pub engine: String,
+    pub lines: Vec<BestMoves>,
+    pub progress: f64,
+    /// The engine sent `bestmove` or stopped responding.
+    pub finished: bool,
+}
+
+/// How much the engines agree on the position, judged by their first lines.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Agreement {
+    /// The best move most engines chose; ties go to the engine listed first.
+    #[specta(optional)]
+    pub best_move: Option<String>,
+    /// Engines whose best move is `best_move`.
+    pub agreeing: u32,
+    /// Engines that have reported a line.
+    pub reporting: u32,
+    /// Every reporting engine chose `best_move`.
+    pub unanimous: bool,
+    /// Difference between the highest and lowest evaluation in centipawns,
+    /// when at least two engines report and none sees a mate.
+    #[specta(optional)]
+    pub eval_spread: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusPayload {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    /// In the order the engines were given.
+    pub engines: Vec<EngineLines>,
+    pub agreement: Agreement,
+    /// The progress of the slowest engine.
+    pub progress: f64,
+}
+
+struct Participant {
+    spec: ConsensusEngine,
+    process: EngineProcess,
+    /// `bestmove`s still owed for searches that were stopped to start a new
+    /// one; output is ignored until they arrive.
+    stale_bestmoves: u32,
+}
+
+/// Engines analysing the same position for one multi-engine analysis.
+pub struct ConsensusSession {
+    engines: Vec<ConsensusEngine>,
+    participants: Vec<Arc<Mutex<Participant>>>,
+}
+
+/// Lines from one engine, sent to the task that merges them.
+struct EngineUpdate {
+    index: usize,
+    fen: String,
+    moves: Vec<String>,
+    lines: Vec<BestMoves>,
+    progress: f64,
+    finished: bool,
+}
+
+impl EngineUpdate {
+    fn new(index: usize, process: &EngineProcess, finished: bool) -> Self {
+        let lines = process.last_best_moves.clone();
+        let progress = if finished {
+            100.0
+        } else {
+            let (depth, nodes) = lines.first().map_or((0, 0), |l| (l.depth, l.nodes));
+            calculate_progress(&process.go_mode, depth, nodes, process.start.elapsed()).min(100.0)
+        };
+        Self {
+            index,
+            fen: process.options.fen.clone(),
+            moves: process.options.moves.clone(),
+            lines,
+            progress,
+            finished,
+        }
+    }
+}
+
+fn agreement(engines: &[EngineLines]) -> Agreement {
+    let best: Vec<&BestMoves> = engines.iter().filter_map(|e| e.lines.first()).collect();
+
+    let mut votes: Vec<(&str, u32)> = Vec::new();
+    for line in &best {
+        let Some(mv) = line.uci_moves.first() else {
+            continue;
+        };
+        match votes.iter_mut().find(|(voted, _)| voted == mv) {
+            Some((_, count)) => *count += 1,
+            None => votes.push((mv, 1)),
+        }
+    }
+    // `max_by_key` keeps the last maximum, so search backwards
+    let top = votes.iter().rev().max_by_key(|(_, count)| *count);
+
+    let evals: Vec<i32> = best
+        .iter()
+        .filter_map(|line| match line.score.value {
+            ScoreValue::Cp(cp) => Some(cp),
+            ScoreValue::Mate(_) => None,
+        })
+        .collect();
+    let eval_spread = (evals.len() >= 2 && evals.len() == best.len())
+        .then(|| evals.iter().max().unwrap() - evals.iter().min().unwrap());
+
+    let reporting = best.len() as u32;
+    let agreeing = top.map_or(0, |(_, count)| *count);
+    Agreement {
+        best_move: top.map(|(mv, _)| mv.to_string()),
+        agreeing,
+        reporting,
+        unanimous: reporting > 0 && agreeing == reporting,
+        eval_spread,
+    }
+}
+
+/// The shared options with the engine's preset and its own options applied.
+async fn engine_options(
+    app: &tauri::AppHandle,
+    spec: &ConsensusEngine,
+    options: &EngineOptions,
+) -> Result<EngineOptions, Error> {
+    let mut options = options.clone();
+    apply_preset(app, &spec.engine, options.preset.as_deref(), &mut options.extra_options).await?;
+    options
+        .extra_options
+        .retain(|opt| !spec.options.iter().any(|own| own.name == opt.name));
+    options.extra_options.extend(spec.options.iter().cloned());
+    Ok(options)
+}
+
+/// Starts searching the position in `options`, stopping the previous search.
+async fn search(
+    participant: &mut Participant,
+    go_mode: &GoMode,
+    options: &EngineOptions,
+    app: &tauri::AppHandle,
+) -> Result<(), Error> {
+    let options = engine_options(app, &participant.spec, options).await?;
+    if participant.process.running {
+        participant.process.stop().await?;
+        participant.stale_bestmoves += 1;
+    }
+    participant.process.set_options(options).await?;
+    participant.process.go(go_mode).await
+}
+
+/// Reads one engine's output and forwards every complete set of lines.
+async fn read_engine(
+    index: usize,
+    participant: Arc<Mutex<Participant>>,
+    mut reader: EngineReader,
+    updates: mpsc::UnboundedSender<EngineUpdate>,
+    id: String,
+    app: tauri::AppHandle,
+) {
+    loop {
+        let line = match reader.next_line().await {
+            Ok(Some(line)) => line,
+            read => {
+                if let Err(e) = read {
+                    error!("Error reading from engine stdout: {}", e);
+                }
+                let mut participant = participant.lock().await;
+                if !participant.process.quit {
+                    let status = participant.process.crash_status().await;
+                    error!("Engine {} crashed: {:?}", participant.spec.engine, status);
+                    emit_engine_status(&app, &participant.spec.engine, &id, status);
+                    let _ = updates.send(EngineUpdate::new(index, &participant.process, true));
+                }
+                break;
+            }
+        };
+
+        let mut guard = participant.lock().await;
+        let current = &mut *guard;
+        let process = &mut current.process;
+        process.logs.push(EngineLog::Engine(line.clone()));
+
+        let update = match process.parse_line(&line) {
+            Ok(EngineEvent::BestMove { .. }) if current.stale_bestmoves > 0 => {
+                current.stale_bestmoves -= 1;
+                process.reset_analysis_state();
+                None
+            }
+            _ if current.stale_bestmoves > 0 => None,
+            Ok(EngineEvent::Info(best_moves)) => {
+                if best_moves.multipv as usize == process.best_moves.len() + 1 {
+                    process.best_moves.push(best_moves);
+                }
+                (process.best_moves.len() >= process.real_multipv.max(1) as usize).then(|| {
+                    process.last_best_moves = std::mem::take(&mut process.best_moves);
+                    EngineUpdate::new(index, process, false)
+                })
+            }
+            Ok(EngineEvent::BestMove { .. }) => {
+                process.running = false;
+                Some(EngineUpdate::new(index, process, true))
+            }
+            Ok(EngineEvent::CurrentMove(_) | EngineEvent::Other) | Err(_) => None,
+        };
+
+        if let Some(update) = update {
+            if updates.send(update).is_err() {
+                break;
+            }
+        }
+    }
+    debug!("Engine reader {} of {} finished", index, id);
+}
+
+/// Merges the engines' lines into consensus events, emitted at most every
+/// `CONSENSUS_INTERVAL` except when an engine finishes.
+async fn merge_updates(
+    mut updates: mpsc::UnboundedReceiver<EngineUpdate>,
+    id: String,
+    engines: Vec<String>,
+    app: tauri::AppHandle,
+) {
+    let mut position = (String::new(), Vec::new());
+    let mut lines: Vec<EngineLines> = engines
+        .into_iter()
+        .map(|engine| EngineLines {
+            engine,
+            lines: Vec::new(),
+            progress: 0.0,
+            finished: false,
+        })
+        .collect();
+    let mut last_emit: Option<Instant> = None;
+    let mut pending = false;
+
+    loop {
+        let update = match timeout(CONSENSUS_INTERVAL, updates.recv()).await {
+            Ok(Some(update)) => Some(update),
+            Ok(None) => break,
+            Err(_) => None,
+        };
+
+        let mut urgent = false;
+        if let Some(update) = update {
+            if (&update.fen, &update.moves) != (&position.0, &position.1) {
+                position = (update.fen, update.moves);
+                for engine in &mut lines {
+                    engine.lines.clear();
+                    engine.progress = 0.0;
+                    engine.finished = false;
+                }
+            }
+            let engine = &mut lines[update.index];
+            engine.lines = update.lines;
+            engine.progress = update.progress;
+            engine.finished = update.finished;
+            urgent = update.finished;
+            pending = true;
+        }
+
+        let due = last_emit.map_or(true, |t| t.elapsed() >= CONSENSUS_INTERVAL);
+        if pending && (urgent || due) {
+            let payload = ConsensusPayload {
+                id: id.clone(),
+                fen: position.0.clone(),
+                moves: position.1.clone(),
+                agreement: agreement(&lines),
+                progress: lines.iter().map(|l| l.progress).fold(100.0, f64::min),
+                engines: lines.clone(),
+            };
+            if let Err(e) = payload.emit(&app) {
+                warn!("Failed to emit consensus payload: {}", e);
+            }
+            last_emit = Some(Instant::now());
+            pending = false;
+        }
+    }
+    info!("Multi-engine analysis {} finished", id);
+}
+
+pub(super) async fn kill_session(session: &ConsensusSession) {
+    for participant in &session.participants {
+        let mut participant = participant.lock().await;
+        if let Err(e) = participant.process.kill().await {
+            warn!("Failed to kill engine {}: {}", participant.spec.engine, e);
+        }
+    }
+}
+
+/// Analyses one position with several engines at once and emits their lines
+/// together with how much they agree. Calling it again with the same engines
+/// moves them to the new position; other engines replace the session.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_multi_engine_analysis(
+    id: String,
+    engines: Vec<ConsensusEngine>,
+    go_mode: GoMode,
+    options: EngineOptions,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    if engines.is_empty() {
+        return Err(Error::NoEngines);
+    }
+    info!("Starting multi-engine analysis {} with {} engines", id, engines.len());
+
+    let existing = state.consensus_sessions.get(&id).map(|s| s.clone());
+    if let Some(session) = existing {
+        if session.engines == engines {
+            for participant in &session.participants {
+                search(&mut *participant.lock().await, &go_mode, &options, &app).await?;
+            }
+            return Ok(());
+        }
+        state.consensus_sessions.remove(&id);
+        kill_session(&session).await;
+    }
+
+    let started = join_all(engines.iter().map(|spec| {
+        let path = PathBuf::from(&spec.engine);
+        let launch = spec.launch.clone().unwrap_or_default();
+        async move { EngineProcess::new(path, &launch).await }
+    }))
+    .await;
+
+    let mut processes = Vec::with_capacity(started.len());
+    let mut failure = None;
+    for result in started {
+        match result {
+            Ok(process) => processes.push(process),
+            Err(e) => failure = failure.or(Some(e)),
+        }
+    }
+    if let Some(e) = failure {
+        error!("Failed to start engines for multi-engine analysis: {}", e);
+        for (mut process, _) in processes {
+            let _ = process.kill().await;
+        }
+        return Err(e);
+    }
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut participants = Vec::with_capacity(processes.len());
+    for (index, (spec, (process, reader))) in engines.iter().zip(processes).enumerate() {
+        let participant = Arc::new(Mutex::new(Participant {
+            spec: spec.clone(),
+            process,
+            stale_bestmoves: 0,
+        }));
+        tokio::spawn(read_engine(
+            index,
+            participant.clone(),
+            reader,
+            sender.clone(),
+            id.clone(),
+            app.clone(),
+        ));
+        participants.push(participant);
+    }
+    let names = engines.iter().map(|spec| spec.engine.clone()).collect();
+    tokio::spawn(merge_updates(receiver, id.clone(), names, app.clone()));
+
+    let session = ConsensusSession {
+        engines,
+        participants,
+    };
+    for participant in &session.participants {
+        let mut participant = participant.lock().await;
+        if let Err(e) = search(&mut participant, &go_mode, &options, &app).await {
+            drop(participant);
+            kill_session(&session).await;
+            return Err(e);
+        }
+        emit_engine_status(&app, &participant.spec.engine, &id, EngineStatus::Started);
+    }
+    state.consensus_sessions.insert(id, Arc::new(session));
+    Ok(())
+}
+
+/// Stops every engine's search; the engines stay up for the next position.
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_multi_engine_analysis(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    let Some(session) = state.consensus_sessions.get(&id).map(|s| s.clone()) else {
+        return Ok(());
+    };
+    debug!("Stopping multi-engine analysis {}", id);
+    for participant in &session.participants {
+        let mut participant = participant.lock().await;
+        if let Err(e) = participant.process.stop().await {
+            warn!("Failed to stop engine {}: {}", participant.spec.engine, e);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kill_multi_engine_analysis(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    if let Some((_, session)) = state.consensus_sessions.remove(&id) {
+        info!("Killing multi-engine analysis {}", id);
+        kill_session(&session).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vampirc_uci::uci::Score;
+
+    fn engine(uci_move: &str, score: ScoreValue) -> EngineLines {
+        EngineLines {
+            engine: "engine".to_string(),
+            lines: vec![BestMoves {
+                score: Score {
+                    value: score,
+                    ..Default::default()
+                },
+                uci_moves: vec![uci_move.to_string()],
+                ..Default::default()
+            }],
+            progress: 50.0,
+            finished: false,
+        }
+    }
+
+    #[test]
+    fn majority_move_wins() {
+        let idle = EngineLines {
+            lines: vec![],
+            ..engine("e2e4", ScoreValue::Cp(0))
+        };
+        let engines = [
+            engine("d2d4", ScoreValue::Cp(30)),
+            engine("e2e4", ScoreValue::Cp(25)),
+            engine("e2e4", ScoreValue::Cp(-10)),
+            idle,
+        ];
+        assert_eq!(
+            agreement(&engines),
+            Agreement {
+                best_move: Some("e2e4".to_string()),
+                agreeing: 2,
+                reporting: 3,
+                unanimous: false,
+                eval_spread: Some(40),
+            }
+        );
+    }
+
+    #[test]
+    fn mates_have_no_spread() {
+        let engines = [
+            engine("h5f7", ScoreValue::Mate(1)),
+            engine("h5f7", ScoreValue::Cp(900)),
+        ];
+        let result = agreement(&engines);
+        assert!(result.unanimous);
+        assert_eq!(result.eval_spread, None);
+
+        assert_eq!(agreement(&[]), Agreement::default());
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::UciMove, CastlingMode, Chess, EnPassantMode, Move, Position,
+};
+use specta::Type;
+
+use super::{
+    classify::{classify_game, played_moves, win_chance, PlayedMove},
+    play::PlayerColor,
+    MoveAnalysis, MoveClassification,
+};
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum CriticalKind {
+    /// A mistake or blunder that swung the game.
+    TurningPoint,
+    /// A won position let slip.
+    MissedWin,
+    /// Only one move held, and it was found.
+    OnlyMove,
+}
+
+/// A move worth going back to, with what is needed to explain it or to
+/// make a puzzle of the position before it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct CriticalMoment {
+    pub kind: CriticalKind,
+    /// Index of the move, and of the analysis of the position before it.
+    pub index: u32,
+    pub move_number: u32,
+    pub color: PlayerColor,
+    /// The position before the move.
+    pub fen: String,
+    pub played: String,
+    pub played_uci: String,
+    #[specta(optional)]
+    pub best: Option<String>,
+    #[specta(optional)]
+    pub best_uci: Option<String>,
+    /// The engine's line from the position, in SAN.
+    pub best_line: Vec<String>,
+    /// The mover's win chance in percent, before and after the move.
+    pub win_before: f64,
+    pub win_after: f64,
+    /// How much worse, in win chance, the second best move was.
+    #[specta(optional)]
+    pub second_best_loss: Option<f64>,
+    #[specta(optional)]
+    pub classification: Option<MoveClassification>,
+    pub is_sacrifice: bool,
+    /// The win chance at stake, which moments are ranked by: what a turning
+    /// point or missed win lost, or what any other move than an only move
+    /// would have.
+    pub weight: f64,
+}
+
+/// How much worse the second line is than the first for the mover.
+fn second_best_loss(entry: &MoveAnalysis, played: &PlayedMove) -> Option<f64> {
+    let [first, second, ..] = entry.best.as_slice() else {
+        return None;
+    };
+    Some(win_chance(&first.score, played.turn) - win_chance(&second.score, played.turn))
+}
+
+fn moment(
+    pos: &Chess,
+    (mv, uci): (&Move, &str),
+    played: &PlayedMove,
+    entry: &MoveAnalysis,
+    is_sacrifice: bool,
+) -> Option<CriticalMoment> {
+    let second_best_loss = second_best_loss(entry, played);
+    let loss = played.win_before - played.win_after;
+    let (kind, weight) = match entry.classification? {
+        MoveClassification::MissedWin => (CriticalKind::MissedWin, loss),
+        MoveClassification::Mistake | MoveClassification::Blunder => {
+            (CriticalKind::TurningPoint, loss)
+        }
+        MoveClassification::OnlyMove | MoveClassification::Brilliant => {
+            (CriticalKind::OnlyMove, second_best_loss?)
+        }
+        _ => return None,
+    };
+
+    let best = entry.best.first();
+    Some(CriticalMoment {
+        kind,
+        index: played.index as u32,
+        move_number: pos.fullmoves().get(),
+        color: played.turn.into(),
+        fen: Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string(),
+        played: SanPlus::from_move(pos.clone(), mv).to_string(),
+        played_uci: uci.to_string(),
+        best: best.and_then(|line| line.san_moves.first().cloned()),
+        best_uci: best.and_then(|line| line.uci_moves.first().cloned()),
+        best_line: best.map(|line| line.san_moves.clone()).unwrap_or_default(),
+        win_before: played.win_before,
+        win_after: played.win_after,
+        second_best_loss,
+        classification: entry.classification,
+        is_sacrifice,
+        weight,
+    })
+}
+
+/// The critical moments of a classified game analysis, where entry `i` is
+/// the position before `moves[i]`, most important first.
+pub(super) fn critical_moments(
+    analysis: &[MoveAnalysis],
+    start: Chess,
+    moves: &[String],
+) -> Result<Vec<CriticalMoment>, Error> {
+    let mut played = played_moves(analysis, start.clone(), moves)?.into_iter().peekable();
+    let mut moments = Vec::new();
+    let mut pos = start;
+    for (index, uci) in moves.iter().enumerate() {
+        let mv = UciMove::from_ascii(uci.as_bytes())?.to_move(&pos)?;
+        if let Some(played) = played.next_if(|played| played.index == index) {
+            // A move's sacrifice flag is on the position it leads to
+            let is_sacrifice = analysis.get(index + 1).is_some_and(|next| next.is_sacrifice);
+            moments.extend(moment(&pos, (&mv, uci), &played, &analysis[index], is_sacrifice));
+        }
+        pos.play_unchecked(&mv);
+    }
+
+    moments.sort_by(|a, b| b.weight.total_cmp(&a.weight).then(a.index.cmp(&b.index)));
+    Ok(moments)
+}
+
+/// Ranks the critical moments of an analysed game, from `analyze_game`.
+/// Moves are classified again, so analyses stored before classes were
+/// added work too.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_critical_moments(
+    fen: String,
+    moves: Vec<String>,
+    mut analysis: Vec<MoveAnalysis>,
+) -> Result<Vec<CriticalMoment>, Error> {
+    let start: Chess = Fen::from_ascii(fen.as_bytes())?.into_position(CastlingMode::Chess960)?;
+    classify_game(&mut analysis, start.clone(), &moves)?;
+    critical_moments(&analysis, start, &moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::BestMoves;
+    use vampirc_uci::uci::{Score, ScoreValue};
+
+    fn line(uci_move: &str, san_move: &str, cp: i32) -> BestMoves {
+        BestMoves {
+            score: Score {
+                value: ScoreValue::Cp(cp),
+                ..Default::default()
+            },
+            uci_moves: vec![uci_move.to_string()],
+            san_moves: vec![san_move.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn entry(best: Vec<BestMoves>) -> MoveAnalysis {
+        MoveAnalysis {
+            best,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ranks_turning_points_and_only_moves() {
+        // 1.e4 g5?? 2.Qh5, the only move that keeps the advantage
+        let moves: Vec<String> = ["e2e4", "g7g5", "d1h5"].map(String::from).into();
+        let mut analysis = vec![
+            entry(vec![line("e2e4", "e4", 30), line("d2d4", "d4", 25)]),
+            entry(vec![line("e7e5", "e5", 30), line("c7c5", "c5", 35)]),
+            entry(vec![line("d1h5", "Qh5", 500), line("d2d4", "d4", 0)]),
+            MoveAnalysis {
+                is_sacrifice: true,
+                ..entry(vec![line("f7f6", "f6", 500)])
+            },
+        ];
+        classify_game(&mut analysis, Chess::default(), &moves).unwrap();
+
+        let moments = critical_moments(&analysis, Chess::default(), &moves).unwrap();
+        assert_eq!(moments.len(), 2);
+
+        let only_move = &moments[0];
+        assert_eq!(only_move.kind, CriticalKind::OnlyMove);
+        assert_eq!((only_move.index, only_move.move_number), (2, 2));
+        assert_eq!(only_move.color, PlayerColor::White);
+        assert_eq!(only_move.fen, "rnbqkbnr/pppppp1p/8/6p1/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(only_move.played, "Qh5");
+        assert!(only_move.is_sacrifice);
+
+        let blunder = &moments[1];
+        assert_eq!(blunder.kind, CriticalKind::TurningPoint);
+        assert_eq!(blunder.color, PlayerColor::Black);
+        assert_eq!((blunder.played.as_str(), blunder.best.as_deref()), ("g5", Some("e5")));
+        assert!(blunder.weight > 20.0 && blunder.weight < only_move.weight);
+        assert!(!blunder.is_sacrifice);
+    }
+}
@@ -0,0 +1,859 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::UciMove, ByColor, CastlingMode, Chess, Color, EnPassantMode,
+    Move, Position,
+};
+use specta::Type;
+use tauri_specta::Event;
+use tokio::time::timeout;
+use vampirc_uci::uci::{Score, ScoreValue};
+
+use super::{
+    transport::EngineReader, EngineLaunch, EngineOption, EngineOptions, EngineProcess, GoMode,
+    PlayersTime,
+};
+use crate::{
+    db::{Outcome, PgnGame},
+    error::Error,
+    AppState,
+};
+
+/// Extra time granted on top of the remaining clock before a silent engine
+/// is declared lost on time.
+const CLOCK_GRACE: Duration = Duration::from_secs(5);
+
+/// Score reported for a mate when checking adjudication thresholds.
+const MATE_SCORE: i32 = 100_000;
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchEngine {
+    pub name: String,
+    pub path: PathBuf,
+    pub options: Vec<EngineOption>,
+    #[specta(optional)]
+    pub launch: Option<EngineLaunch>,
+}
+
+impl MatchEngine {
+    pub(super) async fn spawn(&self) -> Result<(EngineProcess, EngineReader), Error> {
+        EngineProcess::new(self.path.clone(), &self.launch.clone().unwrap_or_default()).await
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Type, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchOpening {
+    pub fen: String,
+    pub moves: Vec<String>,
+}
+
+/// Clock settings, in milliseconds.
+#[derive(Deserialize, Debug, Clone, Copy, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchTimeControl {
+    pub initial: u32,
+    pub increment: u32,
+}
+
+impl MatchTimeControl {
+    /// Formats the time control as a PGN `TimeControl` tag value.
+    pub(super) fn to_pgn(self) -> String {
+        format!("{}+{}", self.initial / 1000, self.increment / 1000)
+    }
+}
+
+/// Rules for ending a game early based on the engines' own evaluations.
+#[derive(Deserialize, Debug, Clone, Default, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Adjudication {
+    /// Resign when the mover's score stays below `-resign_score` centipawns...
+    #[specta(optional)]
+    pub resign_score: Option<i32>,
+    /// ...for this many consecutive moves of that engine.
+    pub resign_move_count: u32,
+    /// Declare a draw when both engines report `|score| <= draw_score`...
+    #[specta(optional)]
+    pub draw_score: Option<i32>,
+    /// ...for this many consecutive plies...
+    pub draw_move_count: u32,
+    /// ...starting at this full move number.
+    pub draw_move_number: u32,
+    /// Declare a draw once the game reaches this many full moves.
+    #[specta(optional)]
+    pub max_moves: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchSettings {
+    pub event: String,
+    pub time_control: MatchTimeControl,
+    pub openings: Vec<MatchOpening>,
+    /// Play every opening twice with colors reversed.
+    pub swap_colors: bool,
+    pub rounds: u32,
+    pub adjudication: Adjudication,
+    pub pgn_path: PathBuf,
+    /// Let engines think on their opponent's time.
+    #[serde(default)]
+    pub ponder: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoves,
+    Repetition,
+    Resignation,
+    DrawAdjudication,
+    MaxMoves,
+    TimeForfeit,
+    IllegalMove,
+    EngineFailure,
+    Aborted,
+}
+
+impl Termination {
+    pub(super) fn describe(self, winner: Option<Color>) -> String {
+        let side = |c: Color| if c.is_white() { "White" } else { "Black" };
+        match (self, winner) {
+            (Termination::Checkmate, Some(c)) => format!("{} mates", side(c)),
+            (Termination::Resignation, Some(c)) => format!("{} resigns", side(c.other())),
+            (Termination::TimeForfeit, Some(c)) => format!("{} loses on time", side(c.other())),
+            (Termination::IllegalMove, Some(c)) => {
+                format!("{} makes an illegal move", side(c.other()))
+            }
+            (Termination::EngineFailure, Some(c)) => {
+                format!("{} engine disconnects", side(c.other()))
+            }
+            (Termination::Stalemate, _) => "Draw by stalemate".to_string(),
+            (Termination::InsufficientMaterial, _) => "Draw by insufficient material".to_string(),
+            (Termination::FiftyMoves, _) => "Draw by fifty moves rule".to_string(),
+            (Termination::Repetition, _) => "Draw by 3-fold repetition".to_string(),
+            (Termination::DrawAdjudication, _) => "Draw by adjudication".to_string(),
+            (Termination::MaxMoves, _) => "Draw by move limit".to_string(),
+            _ => "Game aborted".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchGame {
+    pub round: u32,
+    pub white: String,
+    pub black: String,
+    pub result: Outcome,
+    pub termination: Termination,
+    pub moves: Vec<String>,
+    pub pgn: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchResult {
+    pub games: Vec<MatchGame>,
+    /// Score of the first engine: wins, draws, losses.
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+#[derive(Clone, Serialize, Debug, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchProgress {
+    pub id: String,
+    pub round: u32,
+    pub total_rounds: u32,
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub white_clock: u32,
+    pub black_clock: u32,
+    pub score: Option<Score>,
+}
+
+#[derive(Clone, Serialize, Debug, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchGameFinished {
+    pub id: String,
+    pub game: MatchGame,
+}
+
+struct Player {
+    process: EngineProcess,
+    reader: EngineReader,
+    engine: MatchEngine,
+    ponder: bool,
+}
+
+impl Player {
+    async fn start(engine: &MatchEngine, ponder: bool) -> Result<Self, Error> {
+        let (mut process, reader) = engine.spawn().await?;
+        if let Err(e) = process.new_game().await {
+            if let Err(e) = process.kill().await {
+                debug!("Failed to quit engine {}: {}", engine.name, e);
+            }
+            return Err(e);
+        }
+
+        let mut engine = engine.clone();
+        let ponder = ponder && process.can_ponder();
+        if ponder {
+            engine.options.retain(|o| o.name != "Ponder");
+            engine.options.extend(process.ponder_option());
+        }
+        Ok(Self {
+            process,
+            reader,
+            engine,
+            ponder,
+        })
+    }
+
+    async fn quit(&mut self) {
+        if let Err(e) = self.process.kill().await {
+            debug!("Failed to quit engine {}: {}", self.engine.name, e);
+        }
+    }
+}
+
+/// Starts the engines of a game, white first. When one fails to start, the
+/// other is quit and the color that failed is returned.
+async fn start_players(
+    white: &MatchEngine,
+    black: &MatchEngine,
+    ponder: bool,
+) -> Result<ByColor<Player>, Color> {
+    let white_player = match Player::start(white, ponder).await {
+        Ok(player) => player,
+        Err(e) => {
+            warn!("Engine {} failed to start: {}", white.name, e);
+            return Err(Color::White);
+        }
+    };
+    match Player::start(black, ponder).await {
+        Ok(black_player) => Ok(ByColor {
+            white: white_player,
+            black: black_player,
+        }),
+        Err(e) => {
+            warn!("Engine {} failed to start: {}", black.name, e);
+            let mut white_player = white_player;
+            white_player.quit().await;
+            Err(Color::Black)
+        }
+    }
+}
+
+/// Everything a game loop needs to know besides the engines themselves.
+pub(super) struct GameContext<'a> {
+    pub id: &'a str,
+    pub round: u32,
+    pub total_rounds: u32,
+    pub settings: &'a MatchSettings,
+    pub cancelled: &'a AtomicBool,
+    pub app: &'a tauri::AppHandle,
+}
+
+/// A position key that ignores the move counters, used for repetition checks.
+pub(super) fn position_key(pos: &Chess) -> String {
+    Fen::from_position(pos.clone(), EnPassantMode::Legal)
+        .to_string()
+        .split_whitespace()
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Converts a white-relative score to centipawns from `color`'s point of view.
+pub(super) fn score_for(score: &Score, color: Color) -> i32 {
+    let cp = match score.value {
+        ScoreValue::Cp(cp) => cp,
+        ScoreValue::Mate(m) if m > 0 => MATE_SCORE - m,
+        ScoreValue::Mate(m) => -MATE_SCORE - m,
+    };
+    if color.is_white() {
+        cp
+    } else {
+        -cp
+    }
+}
+
+fn format_score(score: &Score, color: Color) -> String {
+    match score.value {
+        ScoreValue::Mate(m) => {
+            let m = if color.is_white() { m } else { -m };
+            if m > 0 {
+                format!("+M{}", m)
+            } else {
+                format!("-M{}", -m)
+            }
+        }
+        ScoreValue::Cp(_) => format!("{:+.2}", score_for(score, color) as f64 / 100.0),
+    }
+}
+
+/// Tracks the eval-based adjudication counters across a game.
+#[derive(Default)]
+pub(super) struct Adjudicator {
+    resign_streak: ByColor<u32>,
+    draw_streak: u32,
+}
+
+impl Adjudicator {
+    /// Feeds the evaluation of the engine that just moved and returns the
+    /// adjudicated result, if any.
+    pub fn update(
+        &mut self,
+        rules: &Adjudication,
+        mover: Color,
+        score: Option<&Score>,
+        fullmoves: u32,
+    ) -> Option<(Option<Color>, Termination)> {
+        let Some(score) = score else {
+            *self.resign_streak.get_mut(mover) = 0;
+            self.draw_streak = 0;
+            return None;
+        };
+        let cp = score_for(score, mover);
+
+        if let Some(resign_score) = rules.resign_score {
+            let streak = self.resign_streak.get_mut(mover);
+            if cp <= -resign_score {
+                *streak += 1;
+                if *streak >= rules.resign_move_count.max(1) {
+                    return Some((Some(mover.other()), Termination::Resignation));
+                }
+            } else {
+                *streak = 0;
+            }
+        }
+
+        if let Some(draw_score) = rules.draw_score {
+            if fullmoves >= rules.draw_move_number && cp.abs() <= draw_score {
+                self.draw_streak += 1;
+                if self.draw_streak >= rules.draw_move_count.max(1) * 2 {
+                    return Some((None, Termination::DrawAdjudication));
+                }
+            } else {
+                self.draw_streak = 0;
+            }
+        }
+
+        None
+    }
+}
+
+/// Checks whether the game is over by the rules of chess.
+pub(super) fn rules_outcome(
+    pos: &Chess,
+    repetitions: &HashMap<String, u32>,
+) -> Option<(Option<Color>, Termination)> {
+    if pos.is_checkmate() {
+        Some((Some(pos.turn().other()), Termination::Checkmate))
+    } else if pos.is_stalemate() {
+        Some((None, Termination::Stalemate))
+    } else if pos.is_insufficient_material() {
+        Some((None, Termination::InsufficientMaterial))
+    } else if pos.halfmoves() >= 100 {
+        Some((None, Termination::FiftyMoves))
+    } else if repetitions.get(&position_key(pos)).copied().unwrap_or(0) >= 3 {
+        Some((None, Termination::Repetition))
+    } else {
+        None
+    }
+}
+
+pub(super) fn outcome_of(winner: Option<Color>, termination: Termination) -> Outcome {
+    match (winner, termination) {
+        (_, Termination::Aborted) => Outcome::Unknown,
+        (Some(Color::White), _) => Outcome::WhiteWin,
+        (Some(Color::Black), _) => Outcome::BlackWin,
+        (None, _) => Outcome::Draw,
+    }
+}
+
+pub(super) fn start_position(fen: &str) -> Result<Chess, Error> {
+    let fen: Fen = fen.parse()?;
+    match fen.into_position(CastlingMode::Chess960) {
+        Ok(p) => Ok(p),
+        Err(e) => Ok(e.ignore_too_much_material()?),
+    }
+}
+
+/// Plays `mv` on `pos` and appends it to the movetext, with a move number
+/// where PGN expects one.
+pub(super) fn append_move(movetext: &mut String, pos: &mut Chess, mv: &Move, comment: Option<String>) {
+    if pos.turn().is_white() {
+        movetext.push_str(&format!("{}. ", pos.fullmoves()));
+    } else if movetext.is_empty() {
+        movetext.push_str(&format!("{}... ", pos.fullmoves()));
+    }
+    let san = SanPlus::from_move_and_play_unchecked(pos, mv);
+    movetext.push_str(&san.to_string());
+    movetext.push(' ');
+    if let Some(comment) = comment {
+        movetext.push_str(&format!("{{{}}} ", comment));
+    }
+}
+
+fn clock_mode(clocks: &ByColor<i64>, tc: MatchTimeControl) -> GoMode {
+    GoMode::PlayersTime(PlayersTime {
+        white: clocks.white.max(0) as u32,
+        black: clocks.black.max(0) as u32,
+        winc: tc.increment,
+        binc: tc.increment,
+    })
+}
+
+/// Plays a single game between two engines and returns the finished record,
+/// including its PGN.
+pub(super) async fn play_game(
+    white: &MatchEngine,
+    black: &MatchEngine,
+    opening: &MatchOpening,
+    ctx: &GameContext<'_>,
+) -> Result<MatchGame, Error> {
+    info!(
+        "Starting match game {}: {} vs {}",
+        ctx.round, white.name, black.name
+    );
+
+    let mut pos = start_position(&opening.fen)?;
+    let start_fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+    let mut moves = opening.moves.clone();
+    let mut movetext = String::new();
+    for m in &opening.moves {
+        let mv = UciMove::from_ascii(m.as_bytes())?.to_move(&pos)?;
+        append_move(&mut movetext, &mut pos, &mv, None);
+    }
+
+    let tc = ctx.settings.time_control;
+    let mut clocks = ByColor {
+        white: tc.initial as i64,
+        black: tc.initial as i64,
+    };
+    let mut repetitions: HashMap<String, u32> = HashMap::new();
+    *repetitions.entry(position_key(&pos)).or_default() += 1;
+    let mut adjudicator = Adjudicator::default();
+
+    // A game whose engine fails to start is forfeited, like one that fails
+    // to move
+    let (winner, termination) = match start_players(white, black, ctx.settings.ponder).await {
+        Err(failed) => (Some(failed.other()), Termination::EngineFailure),
+        Ok(mut players) => {
+            let outcome = loop {
+                if ctx.cancelled.load(Ordering::Relaxed) {
+                    break (None, Termination::Aborted);
+                }
+                if let Some(result) = rules_outcome(&pos, &repetitions) {
+                    break result;
+                }
+                if let Some(max_moves) = ctx.settings.adjudication.max_moves {
+                    if pos.fullmoves().get() > max_moves {
+                        break (None, Termination::MaxMoves);
+                    }
+                }
+
+                let turn = pos.turn();
+                let go_mode = clock_mode(&clocks, tc);
+                let player = players.get_mut(turn);
+                let started = Instant::now();
+                let ponder_hit = match player
+                    .process
+                    .resolve_ponder(&opening.fen, &moves, &mut player.reader)
+                    .await
+                {
+                    Ok(hit) => hit,
+                    Err(e) => {
+                        warn!("Engine {} failed to stop pondering: {}", player.engine.name, e);
+                        break (Some(turn.other()), Termination::EngineFailure);
+                    }
+                };
+                if !ponder_hit {
+                    let options = EngineOptions {
+                        fen: opening.fen.clone(),
+                        moves: moves.clone(),
+                        extra_options: player.engine.options.clone(),
+                        ..Default::default()
+                    };
+                    let started = match player.process.set_options(options).await {
+                        Ok(()) => player.process.go_on_clock(&go_mode).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = started {
+                        warn!("Engine {} failed to start searching: {}", player.engine.name, e);
+                        break (Some(turn.other()), Termination::EngineFailure);
+                    }
+                }
+
+                let deadline = Duration::from_millis(clocks.get(turn).max(0) as u64) + CLOCK_GRACE;
+                let search = timeout(
+                    deadline,
+                    player.process.wait_for_best_move(&mut player.reader),
+                )
+                .await;
+                let elapsed = started.elapsed();
+
+                let search = match search {
+                    Ok(Ok(search)) => search,
+                    Ok(Err(e)) => {
+                        warn!("Engine {} failed during match: {}", player.engine.name, e);
+                        break (Some(turn.other()), Termination::EngineFailure);
+                    }
+                    Err(_) => break (Some(turn.other()), Termination::TimeForfeit),
+                };
+
+                // The grace covers the time spent reading and sending commands, so
+                // only flag an engine that overstepped it, as the deadline does
+                let clock = clocks.get_mut(turn);
+                *clock -= elapsed.as_millis() as i64;
+                if *clock < -(CLOCK_GRACE.as_millis() as i64) {
+                    break (Some(turn.other()), Termination::TimeForfeit);
+                }
+                *clock = (*clock).max(0) + tc.increment as i64;
+
+                let mv = match UciMove::from_ascii(search.best_move.as_bytes())
+                    .ok()
+                    .and_then(|uci| uci.to_move(&pos).ok())
+                {
+                    Some(mv) => mv,
+                    None => {
+                        warn!(
+                            "Engine {} played illegal move {}",
+                            player.engine.name, search.best_move
+                        );
+                        break (Some(turn.other()), Termination::IllegalMove);
+                    }
+                };
+
+                let score = search.lines.first().map(|line| line.score.clone());
+                let depth = search.lines.first().map_or(0, |line| line.depth);
+                let comment = match &score {
+                    Some(score) => format!(
+                        "{}/{} {:.1}s",
+                        format_score(score, turn),
+                        depth,
+                        elapsed.as_secs_f64()
+                    ),
+                    None => format!("{:.1}s", elapsed.as_secs_f64()),
+                };
+                let fullmoves = pos.fullmoves().get();
+                append_move(&mut movetext, &mut pos, &mv, Some(comment));
+                moves.push(search.best_move);
+                *repetitions.entry(position_key(&pos)).or_default() += 1;
+
+                emit_progress(ctx, &pos, &moves, &clocks, score.clone());
+
+                if let (true, Some(ponder_move)) = (player.ponder, &search.ponder) {
+                    let go_mode = clock_mode(&clocks, tc);
+                    if let Err(e) = player.process.ponder(&go_mode, ponder_move).await {
+                        debug!(
+                            "Engine {} cannot ponder on {}: {}",
+                            player.engine.name, ponder_move, e
+                        );
+                    }
+                }
+
+                if let Some(result) =
+                    adjudicator.update(&ctx.settings.adjudication, turn, score.as_ref(), fullmoves)
+                {
+                    // A mate on the board takes precedence over the adjudicated result
+                    if let Some(rules) = rules_outcome(&pos, &repetitions) {
+                        break rules;
+                    }
+                    break result;
+                }
+            };
+
+            for player in [&mut players.white, &mut players.black] {
+                player.quit().await;
+            }
+            outcome
+        }
+    };
+
+    let result = outcome_of(winner, termination);
+    movetext.push_str(&format!("{{{}}} ", termination.describe(winner)));
+
+    let is_standard_start = start_fen == Fen::default().to_string();
+
+    let pgn = PgnGame {
+        event: Some(ctx.settings.event.clone()),
+        site: Some("?".to_string()),
+        date: Some(chrono::Local::now().format("%Y.%m.%d").to_string()),
+        round: Some(ctx.round.to_string()),
+        white: Some(white.name.clone()),
+        black: Some(black.name.clone()),
+        result: Some(result.to_string()),
+        time_control: Some(tc.to_pgn()),
+        eco: None,
+        white_elo: None,
+        black_elo: None,
+        ply_count: Some(moves.len().to_string()),
+        fen: (!is_standard_start).then_some(start_fen),
+        moves: movetext,
+    };
+
+    let mut buffer = Vec::new();
+    pgn.write(&mut buffer)?;
+
+    Ok(MatchGame {
+        round: ctx.round,
+        white: white.name.clone(),
+        black: black.name.clone(),
+        result,
+        termination,
+        moves,
+        pgn: String::from_utf8(buffer)?,
+    })
+}
+
+fn emit_progress(
+    ctx: &GameContext<'_>,
+    pos: &Chess,
+    moves: &[String],
+    clocks: &ByColor<i64>,
+    score: Option<Score>,
+) {
+    let payload = MatchProgress {
+        id: ctx.id.to_string(),
+        round: ctx.round,
+        total_rounds: ctx.total_rounds,
+        fen: Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string(),
+        moves: moves.to_vec(),
+        white_clock: clocks.white.max(0) as u32,
+        black_clock: clocks.black.max(0) as u32,
+        score,
+    };
+    if let Err(e) = payload.emit(ctx.app) {
+        warn!("Failed to emit match progress: {}", e);
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn start_engine_match(
+    id: String,
+    first: MatchEngine,
+    second: MatchEngine,
+    settings: MatchSettings,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<MatchResult, Error> {
+    info!("Starting engine match {}: {} vs {}", id, first.name, second.name);
+
+    let running = RunningMatch::register(&state, &id);
+    let cancelled = running.cancelled.clone();
+
+    let openings = if settings.openings.is_empty() {
+        vec![MatchOpening {
+            fen: Fen::default().to_string(),
+            moves: vec![],
+        }]
+    } else {
+        settings.openings.clone()
+    };
+    let games_per_opening = if settings.swap_colors { 2 } else { 1 };
+    let total_rounds = settings.rounds * openings.len() as u32 * games_per_opening;
+
+    let mut result = MatchResult::default();
+    let mut round = 0;
+
+    'outer: for _ in 0..settings.rounds {
+        for opening in &openings {
+            for reversed in [false, true].into_iter().take(games_per_opening as usize) {
+                if cancelled.load(Ordering::Relaxed) {
+                    break 'outer;
+                }
+                round += 1;
+
+                let (white, black) = if reversed {
+                    (&second, &first)
+                } else {
+                    (&first, &second)
+                };
+                let ctx = GameContext {
+                    id: &id,
+                    round,
+                    total_rounds,
+                    settings: &settings,
+                    cancelled: &cancelled,
+                    app: &app,
+                };
+
+                let game = match play_game(white, black, opening, &ctx).await {
+                    Ok(game) => game,
+                    Err(e) => {
+                        warn!("Engine match {} game {} failed: {}", id, round, e);
+                        continue;
+                    }
+                };
+                if game.termination == Termination::Aborted {
+                    break 'outer;
+                }
+
+                append_pgn(&settings.pgn_path, &game.pgn)?;
+
+                let first_won = match game.result {
+                    Outcome::WhiteWin => Some(!reversed),
+                    Outcome::BlackWin => Some(reversed),
+                    _ => None,
+                };
+                match first_won {
+                    Some(true) => result.wins += 1,
+                    Some(false) => result.losses += 1,
+                    None => result.draws += 1,
+                }
+
+                MatchGameFinished {
+                    id: id.clone(),
+                    game: game.clone(),
+                }
+                .emit(&app)?;
+                result.games.push(game);
+            }
+        }
+    }
+
+    drop(running);
+    info!(
+        "Engine match {} finished: +{} ={} -{}",
+        id, result.wins, result.draws, result.losses
+    );
+    Ok(result)
+}
+
+/// Registers a running match or tournament so `stop_engine_match` can find
+/// it, until it is dropped on whatever path the match ends.
+pub(super) struct RunningMatch<'a> {
+    state: &'a AppState,
+    id: String,
+    pub cancelled: Arc<AtomicBool>,
+}
+
+impl<'a> RunningMatch<'a> {
+    pub fn register(state: &'a AppState, id: &str) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        state.engine_matches.insert(id.to_string(), cancelled.clone());
+        Self {
+            state,
+            id: id.to_string(),
+            cancelled,
+        }
+    }
+}
+
+impl Drop for RunningMatch<'_> {
+    fn drop(&mut self) {
+        self.state.engine_matches.remove(&self.id);
+    }
+}
+
+/// Stops a running match or tournament after the games in progress are aborted.
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_engine_match(id: String, state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    if let Some(cancelled) = state.engine_matches.get(&id) {
+        info!("Stopping engine match {}", id);
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Appends a finished game to the match PGN file so it can be imported later.
+pub(super) fn append_pgn(path: &PathBuf, pgn: &str) -> Result<(), Error> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(pgn.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cp(value: i32) -> Score {
+        Score {
+            value: ScoreValue::Cp(value),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resign_after_consecutive_bad_scores() {
+        let rules = Adjudication {
+            resign_score: Some(500),
+            resign_move_count: 3,
+            ..Default::default()
+        };
+        let mut adjudicator = Adjudicator::default();
+
+        // White-relative scores: black is losing badly
+        assert!(adjudicator.update(&rules, Color::Black, Some(&cp(600)), 20).is_none());
+        assert!(adjudicator.update(&rules, Color::White, Some(&cp(650)), 21).is_none());
+        assert!(adjudicator.update(&rules, Color::Black, Some(&cp(700)), 21).is_none());
+        assert_eq!(
+            adjudicator.update(&rules, Color::Black, Some(&cp(800)), 22),
+            Some((Some(Color::White), Termination::Resignation))
+        );
+    }
+
+    #[test]
+    fn draw_requires_both_sides() {
+        let rules = Adjudication {
+            draw_score: Some(10),
+            draw_move_count: 2,
+            draw_move_number: 30,
+            ..Default::default()
+        };
+        let mut adjudicator = Adjudicator::default();
+
+        assert!(adjudicator.update(&rules, Color::White, Some(&cp(0)), 29).is_none());
+        assert!(adjudicator.update(&rules, Color::White, Some(&cp(0)), 30).is_none());
+        assert!(adjudicator.update(&rules, Color::Black, Some(&cp(5)), 30).is_none());
+        assert!(adjudicator.update(&rules, Color::White, Some(&cp(-5)), 31).is_none());
+        assert_eq!(
+            adjudicator.update(&rules, Color::Black, Some(&cp(0)), 31),
+            Some((None, Termination::DrawAdjudication))
+        );
+    }
+
+    #[test]
+    fn detects_threefold_repetition() {
+        let mut pos = Chess::default();
+        let mut repetitions: HashMap<String, u32> = HashMap::new();
+        *repetitions.entry(position_key(&pos)).or_default() += 1;
+
+        for _ in 0..2 {
+            for m in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let mv = UciMove::from_ascii(m.as_bytes()).unwrap().to_move(&pos).unwrap();
+                pos.play_unchecked(&mv);
+                *repetitions.entry(position_key(&pos)).or_default() += 1;
+            }
+        }
+
+        assert_eq!(
+            rules_outcome(&pos, &repetitions),
+            Some((None, Termination::Repetition))
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, EnPassantMode, Position};
+use specta::Type;
+use tauri_specta::Event;
+
+use super::{
+    analyze_with_retries, emit_engine_status, ensure_multipv_option, presets::apply_preset,
+    BestMoves, EngineLaunch, EngineOption, EngineOptions, EngineProcess, EngineStatus, GoMode,
+    ReportProgress,
+};
+use crate::{
+    db::{read_game_tree, TreeMove},
+    error::Error,
+};
+
+/// The analysis of one node of a game tree.
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeAnalysis {
+    /// Child indices from the root, as the frontend addresses tree nodes;
+    /// empty for the start position.
+    pub path: Vec<u32>,
+    pub fen: String,
+    /// Empty when the engine failed on this node.
+    pub best: Vec<BestMoves>,
+    /// Why the engine failed on this node.
+    #[specta(optional)]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeAnalysisOptions {
+    /// The game to analyse; only the first game of the PGN is used.
+    pub pgn: String,
+    /// A saved preset for this engine, applied under `uci_options`.
+    #[serde(default)]
+    #[specta(optional)]
+    pub preset: Option<String>,
+}
+
+/// Analyses every position of a PGN game, sidelines and nested variations
+/// included. Finished positions (mate, stalemate) are skipped.
+#[tauri::command]
+#[specta::specta]
+pub async fn analyze_game_tree(
+    id: String,
+    engine: String,
+    go_mode: GoMode,
+    options: TreeAnalysisOptions,
+    mut uci_options: Vec<EngineOption>,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+) -> Result<Vec<NodeAnalysis>, Error> {
+    apply_preset(&app, &engine, options.preset.as_deref(), &mut uci_options).await?;
+
+    let game = read_game_tree(&options.pgn)?;
+    let start = game.position;
+    let start_fen = Fen::from_position(start.clone(), EnPassantMode::Legal).to_string();
+    let mut nodes = vec![TreeMove {
+        path: Vec::new(),
+        uci_moves: Vec::new(),
+        position: start.clone(),
+    }];
+    nodes.extend(game.tree.moves(start)?);
+    nodes.retain(|node| !node.position.is_game_over());
+    info!("Analyzing game tree {}: {} positions", id, nodes.len());
+
+    let (mut proc, mut reader) =
+        EngineProcess::new(PathBuf::from(&engine), &launch.unwrap_or_default()).await?;
+    emit_engine_status(&app, &engine, &id, EngineStatus::Started);
+    ensure_multipv_option(&mut uci_options, &proc.config);
+    let mut restarts = 0;
+
+    let mut analysis = Vec::with_capacity(nodes.len());
+    for (i, node) in nodes.iter().enumerate() {
+        ReportProgress {
+            progress: (i as f64 / nodes.len() as f64) * 100.0,
+            id: id.clone(),
+            finished: false,
+        }
+        .emit(&app)?;
+
+        let engine_options = EngineOptions {
+            fen: start_fen.clone(),
+            moves: node.uci_moves.clone(),
+            extra_options: uci_options.clone(),
+            ..Default::default()
+        };
+        let result = analyze_with_retries(
+            &mut proc,
+            &mut reader,
+            engine_options,
+            &go_mode,
+            &mut restarts,
+            (&app, &engine, &id),
+        )
+        .await;
+
+        let (best, error) = match result {
+            Ok(best) => (best, None),
+            Err(e) => {
+                warn!("Failed to analyze node {:?}: {}", node.path, e);
+                (Vec::new(), Some(e.to_string()))
+            }
+        };
+        analysis.push(NodeAnalysis {
+            path: node.path.iter().map(|&i| i as u32).collect(),
+            fen: Fen::from_position(node.position.clone(), EnPassantMode::Legal).to_string(),
+            best,
+            error,
+        });
+    }
+
+    if let Err(e) = proc.kill().await {
+        warn!("Failed to quit engine after tree analysis: {}", e);
+    }
+    ReportProgress {
+        progress: 100.0,
+        id,
+        finished: true,
+    }
+    .emit(&app)?;
+    Ok(analysis)
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use super::EngineLog;
+
+/// Entries kept per engine; the oldest are dropped first.
+const MAX_LOG_ENTRIES: usize = 5000;
+
+/// An engine's log, shared with the task that reads its stderr.
+#[derive(Debug, Clone, Default)]
+pub(super) struct EngineLogs(Arc<Mutex<VecDeque<EngineLog>>>);
+
+impl EngineLogs {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<EngineLog>> {
+        // A panic while holding the lock cannot leave the queue inconsistent
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn push(&self, entry: EngineLog) {
+        let mut entries = self.lock();
+        if entries.len() == MAX_LOG_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> Vec<EngineLog> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Puts the history of a previous engine in front of this one's, so a
+    /// restarted engine keeps the log of the one that crashed.
+    pub fn prepend(&self, older: Vec<EngineLog>) {
+        let mut entries = self.lock();
+        for entry in older.into_iter().rev() {
+            if entries.len() == MAX_LOG_ENTRIES {
+                break;
+            }
+            entries.push_front(entry);
+        }
+    }
+
+    /// The last `count` lines the engine wrote to stderr, oldest first.
+    pub fn last_stderr(&self, count: usize) -> Vec<String> {
+        let entries = self.lock();
+        let mut lines: Vec<String> = entries
+            .iter()
+            .rev()
+            .filter_map(|entry| match entry {
+                EngineLog::Stderr(line) => Some(line.clone()),
+                _ => None,
+            })
+            .take(count)
+            .collect();
+        lines.reverse();
+        lines
+    }
+
+    /// Writes one line per entry, marked with where it came from.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        for entry in self.lock().iter() {
+            match entry {
+                EngineLog::Gui(command) => writeln!(writer, "> {}", command.trim_end())?,
+                EngineLog::Engine(line) => writeln!(writer, "< {line}")?,
+                EngineLog::Stderr(line) => writeln!(writer, "! {line}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_oldest_entries() {
+        let logs = EngineLogs::default();
+        for i in 0..MAX_LOG_ENTRIES + 10 {
+            logs.push(EngineLog::Engine(format!("info depth {i}")));
+        }
+        let entries = logs.entries();
+        assert_eq!(entries.len(), MAX_LOG_ENTRIES);
+        assert!(matches!(&entries[0], EngineLog::Engine(line) if line == "info depth 10"));
+
+        logs.prepend(vec![EngineLog::Gui("uci\n".to_string())]);
+        assert_eq!(logs.entries().len(), MAX_LOG_ENTRIES);
+    }
+
+    #[test]
+    fn writes_marked_lines() {
+        let logs = EngineLogs::default();
+        logs.prepend(vec![EngineLog::Gui("uci\n".to_string())]);
+        logs.push(EngineLog::Stderr("Failed to load nn-1.nnue".to_string()));
+        logs.push(EngineLog::Engine("uciok".to_string()));
+        assert_eq!(logs.last_stderr(5), vec!["Failed to load nn-1.nnue"]);
+
+        let mut out = Vec::new();
+        logs.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "> uci\n! Failed to load nn-1.nnue\n< uciok\n"
+        );
+    }
+}
@@ -0,0 +1,2151 @@
+mod annotate;
+mod batch;
+mod benchmark;
+mod cache;
+mod classify;
+mod consensus;
+mod critical;
+mod engine_match;
+mod game_tree;
+mod logs;
+mod novelty;
+mod options;
+mod play;
+mod presets;
+mod registry;
+mod sacrifice;
+mod summary;
+mod tournament;
+mod transport;
+mod xboard;
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use derivative::Derivative;
+use governor::{Quota, RateLimiter};
+use log::{debug, error, info, trace, warn};
+use nonzero_ext::*;
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::UciMove, CastlingMode, Chess, Color, EnPassantMode, Position,
+};
+use specta::Type;
+use tauri_specta::Event;
+use tokio::{
+    io::AsyncWriteExt,
+    process::Child,
+    sync::Mutex,
+    time::timeout,
+};
+use vampirc_uci::{
+    parse_one,
+    uci::{Score, ScoreValue},
+    UciInfoAttribute, UciMessage, UciOptionConfig,
+};
+
+use self::logs::EngineLogs;
+use self::transport::{EngineConnection, EngineReader, EngineSource, EngineWriter};
+pub use self::transport::EngineLaunch;
+use self::xboard::Xboard;
+use crate::{
+    error::Error,
+    AppState,
+};
+
+pub use self::annotate::{annotate_db_game, annotate_game_pgn, AnnotateOptions};
+pub use self::batch::{
+    cancel_batch_analysis, list_batch_analyses, pause_batch_analysis, resume_batch_analysis,
+    start_batch_analysis, BatchControl, BatchJob, BatchStatus,
+};
+pub use self::benchmark::{
+    benchmark_engine, BenchmarkMethod, EngineBenchmark, PositionBenchmark,
+};
+pub use self::cache::{clear_analysis_cache, AnalysisCache};
+pub use self::classify::MoveClassification;
+pub use self::consensus::{
+    kill_multi_engine_analysis, start_multi_engine_analysis, stop_multi_engine_analysis,
+    ConsensusPayload, ConsensusSession,
+};
+pub use self::critical::{get_critical_moments, CriticalKind, CriticalMoment};
+pub use self::novelty::Novelty;
+pub use self::options::{check_engine_options, InvalidOption, InvalidReason};
+pub use self::presets::{
+    delete_engine_preset, list_engine_presets, load_engine_preset, save_engine_preset, EnginePreset,
+};
+use self::presets::apply_preset;
+pub use self::registry::{
+    add_engine, list_engines, remove_engine, update_engine, ProtocolKind, RegisteredEngine,
+};
+use self::cache::{analysis_cache, AnalysisCacheKey, CachedAnalysis};
+pub use self::summary::{get_game_summary, save_game_summary, GameAnalysis, GameSummary, PlayerSummary};
+pub use self::game_tree::{analyze_game_tree, NodeAnalysis, TreeAnalysisOptions};
+pub use self::engine_match::{
+    start_engine_match, stop_engine_match, MatchGameFinished, MatchProgress,
+};
+pub use self::play::{
+    close_play_session, play_session_move, resign_play_session, save_play_session,
+    start_play_session, takeback_play_session, PlaySession,
+};
+pub use self::tournament::{start_tournament, TournamentUpdate};
+
+// Constants for timing and rate limiting
+const ENGINE_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+const TICK_DURATION: Duration = Duration::from_millis(20); // Increased from 10ms to reduce polling frequency
+const MIN_EVENT_INTERVAL: Duration = Duration::from_millis(50); // Target ≤50ms latency
+const MAX_EVENT_INTERVAL: Duration = Duration::from_millis(150); // Increased base timeout threshold
+const ENGINE_STOP_DELAY: Duration = Duration::from_millis(50);
+const ENGINE_EXIT_WAIT: Duration = Duration::from_millis(500); // Time for a crashed engine to report its exit code
+const CRASH_STDERR_LINES: usize = 20; // Stderr lines reported with a crash
+const MAX_ENGINE_RESTARTS: u32 = 3; // Per live analysis session
+const POSITION_RETRIES: u32 = 2; // Engine restarts allowed per position in game analysis
+const PONDER_STOP_TIMEOUT: Duration = Duration::from_secs(5); // Time to wait for the discarded `bestmove` of a missed ponder
+const EVENTS_PER_SECOND: u32 = 15; // Reduced from 20 to prevent spam
+
+#[derive(Debug, Clone, Serialize, Type)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum EngineLog {
+    Gui(String),
+    Engine(String),
+    Stderr(String),
+}
+
+/// Protocol an engine speaks, detected when it is started.
+#[derive(Debug)]
+enum EngineProtocol {
+    Uci,
+    Xboard(Xboard),
+}
+
+/// A line of engine output, independent of the engine's protocol.
+enum EngineEvent {
+    Info(BestMoves),
+    CurrentMove(CurrentMove),
+    BestMove {
+        best_move: String,
+        ponder: Option<String>,
+    },
+    Other,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct EngineProcess {
+    #[derivative(Debug = "ignore")]
+    stdin: EngineWriter,
+    #[derivative(Debug = "ignore")]
+    child: Option<Child>,
+    path: PathBuf,
+    launch: EngineLaunch,
+    protocol: EngineProtocol,
+    config: EngineConfig,
+    last_depth: u32,
+    best_moves: Vec<BestMoves>,
+    last_best_moves: Vec<BestMoves>,
+    current_move: Option<CurrentMove>,
+    last_progress: f32,
+    last_event_sent: Option<Instant>,
+    options: EngineOptions,
+    go_mode: GoMode,
+    running: bool,
+    /// Set while the engine ponders on the position in `options`, which
+    /// includes the predicted reply.
+    pondering: bool,
+    /// Set once `quit` was sent, so the engine exiting is not a crash.
+    quit: bool,
+    real_multipv: u16,
+    logs: EngineLogs,
+    start: Instant,
+}
+
+impl EngineProcess {
+    async fn new(path: PathBuf, launch: &EngineLaunch) -> Result<(Self, EngineReader), Error> {
+        info!("Initializing engine from path: {:?}", path);
+        
+        let source = EngineSource::parse(&path.to_string_lossy(), launch)?;
+        let EngineConnection {
+            writer: mut stdin,
+            reader: mut lines,
+            mut child,
+            logs,
+        } = transport::connect(&source).await?;
+        
+        // Initialize communication with timeout
+        // The UCI probe may use up its whole timeout before CECP starts
+        let probe = launch.uci_probe_timeout();
+        let (protocol, config) = match timeout(ENGINE_INIT_TIMEOUT + probe, Self::initialize(&mut stdin, &mut lines, &logs, probe)).await {
+            Ok(Ok(result)) => {
+                info!("Engine initialized successfully: {:?}", path);
+                result
+            }
+            Ok(Err(e)) => {
+                error!("Failed to initialize engine {:?}: {}", path, e);
+                if let Some(child) = child.as_mut() {
+                    let _ = child.kill().await;
+                }
+                return Err(e);
+            }
+            Err(_) => {
+                error!("Engine initialization timeout: {:?}", path);
+                if let Some(child) = child.as_mut() {
+                    let _ = child.kill().await;
+                }
+                return Err(Error::EngineTimeout);
+            }
+        };
+
+        Ok((
+            Self {
+                stdin,
+                child,
+                path,
+                launch: launch.clone(),
+                protocol,
+                config,
+                last_depth: 0,
+                best_moves: Vec::new(),
+                last_best_moves: Vec::new(),
+                current_move: None,
+                last_progress: 0.0,
+                last_event_sent: None,
+                logs,
+                options: EngineOptions::default(),
+                real_multipv: 0,
+                go_mode: GoMode::Infinite,
+                running: false,
+                pondering: false,
+                quit: false,
+                start: Instant::now(),
+            },
+            lines,
+        ))
+    }
+
+    /// Starts the handshake with `uci` and falls back to CECP when the engine
+    /// rejects it or stays silent.
+    async fn initialize(
+        stdin: &mut EngineWriter,
+        lines: &mut EngineReader,
+        logs: &EngineLogs,
+        uci_timeout: Duration,
+    ) -> Result<(EngineProtocol, EngineConfig), Error> {
+        debug!("Starting UCI initialization");
+        let mut config = EngineConfig::default();
+        let mut is_uci = false;
+        
+        // Send UCI command
+        Self::send_command_with_log(stdin, "uci\n", logs).await?;
+        
+        loop {
+            let line = match timeout(uci_timeout, lines.next_line()).await {
+                Ok(line) => line?.ok_or(Error::EngineTimeout)?,
+                Err(_) if is_uci => continue,
+                Err(_) => {
+                    debug!("No UCI response, trying CECP");
+                    break;
+                }
+            };
+            trace!("Engine response: {}", line);
+            logs.push(EngineLog::Engine(line.clone()));
+            
+            if xboard::rejects_uci(&line) {
+                debug!("Engine rejected uci, trying CECP");
+                break;
+            }
+            
+            match parse_one(&line) {
+                UciMessage::Id { name: Some(name), .. } => {
+                    is_uci = true;
+                    config.name = name;
+                }
+                UciMessage::Option(opt) => {
+                    is_uci = true;
+                    config.options.push(opt);
+                }
+                UciMessage::UciOk => {
+                    debug!("Received uciok, sending isready");
+                    Self::send_command_with_log(stdin, "isready\n", logs).await?;
+                    
+                    // Wait for readyok
+                    while let Some(ready_line) = lines.next_line().await? {
+                        trace!("Engine ready response: {}", ready_line);
+                        logs.push(EngineLog::Engine(ready_line.clone()));
+                        
+                        if ready_line == "readyok" {
+                            debug!("Engine is ready");
+                            return Ok((EngineProtocol::Uci, config));
+                        }
+                    }
+                    return Err(Error::EngineTimeout);
+                }
+                _ => {
+                    is_uci |= line.starts_with("id ") || line.starts_with("info ");
+                }
+            }
+        }
+        
+        let xboard = Xboard::initialize(stdin, lines, logs, &mut config).await?;
+        Ok((EngineProtocol::Xboard(xboard), config))
+    }
+
+    async fn send_command_with_log(
+        stdin: &mut EngineWriter,
+        command: &str,
+        logs: &EngineLogs
+    ) -> Result<(), Error> {
+        trace!("Sending command: {}", command.trim());
+        
+        stdin.write_all(command.as_bytes()).await
+            .map_err(|e| {
+                error!("Failed to send command '{}': {}", command.trim(), e);
+                Error::Io(e)
+            })?;
+        
+        // Flush stdin immediately to ensure command is sent to engine without delay
+        stdin.flush().await
+            .map_err(|e| {
+                error!("Failed to flush command '{}': {}", command.trim(), e);
+                Error::Io(e)
+            })?;
+        
+        debug!("Command sent and flushed: {}", command.trim());
+        logs.push(EngineLog::Gui(command.to_string()));
+        Ok(())
+    }
+
+    async fn set_option<T>(&mut self, name: &str, value: T) -> Result<(), Error>
+    where
+        T: Display,
+    {
+        let msg = match &self.protocol {
+            EngineProtocol::Uci => match options::find_option(&self.config, name) {
+                Some(UciOptionConfig::Button { .. }) => format!("setoption name {}\n", name),
+                _ => format!("setoption name {} value {}\n", name, value),
+            },
+            EngineProtocol::Xboard(xboard) => {
+                match xboard.option_command(name, &value.to_string(), &self.config) {
+                    Some(msg) => msg,
+                    None => {
+                        debug!("Skipping option not supported by the engine: {}", name);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        debug!("Setting engine option: {} = {}", name, value);
+        
+        Self::send_command_with_log(&mut self.stdin, &msg, &self.logs).await
+    }
+
+    async fn set_options(&mut self, mut options: EngineOptions) -> Result<(), Error> {
+        debug!("Setting engine options for position: {}", options.fen);
+        
+        // Parse and validate position
+        let fen: Fen = options.fen.parse()
+            .map_err(|e| {
+                error!("Invalid FEN: {}", options.fen);
+                e
+            })?;
+        
+        let mut pos: Chess = match fen.into_position(CastlingMode::Chess960) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("Position error, attempting to ignore extra material: {}", e);
+                e.ignore_too_much_material()?
+            }
+        };
+        
+        // Apply moves and validate
+        for (i, move_str) in options.moves.iter().enumerate() {
+            let uci = UciMove::from_ascii(move_str.as_bytes())
+                .map_err(|e| {
+                    error!("Invalid UCI move at index {}: {}", i, move_str);
+                    e
+                })?;
+            
+            let mv = uci.to_move(&pos)
+                .map_err(|e| {
+                    error!("Illegal move at index {}: {} in position {}", i, move_str, pos.board());
+                    e
+                })?;
+            
+            pos.play_unchecked(&mv);
+        }
+        
+        if let EngineProtocol::Uci = self.protocol {
+            options::retain_known_options(&self.config, &mut options.extra_options)?;
+        }
+
+        // Calculate effective MultiPV
+        // CECP thinking output has no MultiPV index
+        let multipv = match self.protocol {
+            EngineProtocol::Uci if options::find_option(&self.config, "MultiPV").is_some() => {
+                Self::calculate_multipv(&options, &pos)
+            }
+            _ => 1,
+        };
+        self.real_multipv = multipv;
+        
+        debug!("Calculated MultiPV: {} (legal moves: {})", multipv, pos.legal_moves().len());
+
+        // Set options that have changed
+        let changed_options: Vec<_> = options.extra_options.iter()
+            .filter(|new_opt| {
+                self.options.extra_options.iter()
+                    .find(|current_opt| current_opt.name == new_opt.name)
+                    .map_or(true, |current_opt| current_opt.value != new_opt.value)
+            })
+            .filter(|opt| !options::is_skipped(&self.config, &opt.name))
+            .collect();
+
+        for option in changed_options {
+            self.set_option(&option.name, &option.value).await?;
+        }
+
+        // Update position if needed
+        if options.fen != self.options.fen || options.moves != self.options.moves {
+            self.set_position(&options.fen, &options.moves).await?;
+        }
+        
+        // Reset analysis state
+        self.reset_analysis_state();
+        self.options = options;
+        
+        debug!("Engine options set successfully");
+        Ok(())
+    }
+
+    fn calculate_multipv(options: &EngineOptions, pos: &Chess) -> u16 {
+        options
+            .extra_options
+            .iter()
+            .find(|x| x.name == "MultiPV")
+            .and_then(|x| x.value.parse().ok())
+            .unwrap_or(1)
+            .min(Self::root_move_count(options, pos) as u16)
+    }
+
+    fn root_move_count(options: &EngineOptions, pos: &Chess) -> usize {
+        match options.root_moves(pos) {
+            Ok(Some(moves)) => moves.len(),
+            _ => pos.legal_moves().len(),
+        }
+    }
+
+    /// Seeds the search state with a cached analysis, so the engine's shallow
+    /// results are not shown until it gets past the cached depth.
+    fn restore_cached(&mut self, cached: &CachedAnalysis) {
+        self.last_depth = cached.depth;
+        self.last_best_moves = cached.lines.clone();
+        self.last_progress =
+            calculate_progress(&self.go_mode, cached.depth, 0, Duration::ZERO) as f32;
+    }
+
+    fn reset_analysis_state(&mut self) {
+        self.last_depth = 0;
+        self.best_moves.clear();
+        self.last_best_moves.clear();
+        self.current_move = None;
+    }
+
+    async fn set_position(&mut self, fen: &str, moves: &[String]) -> Result<(), Error> {
+        debug!("Setting position: FEN={}, moves={}", fen, moves.len());
+        match &self.protocol {
+            EngineProtocol::Uci => {
+                let msg = if moves.is_empty() {
+                    format!("position fen {fen}\n")
+                } else {
+                    format!("position fen {fen} moves {}\n", moves.join(" "))
+                };
+                Self::send_command_with_log(&mut self.stdin, &msg, &self.logs).await?;
+            }
+            // Checked here, but only sent with the search, see `start_search`
+            EngineProtocol::Xboard(xboard) => {
+                xboard.position_commands(fen, moves)?;
+            }
+        }
+
+        self.options.fen = fen.to_string();
+        self.options.moves = moves.to_vec();
+        Ok(())
+    }
+
+    async fn go(&mut self, mode: &GoMode) -> Result<(), Error> {
+        self.start_search(mode, false).await
+    }
+
+    /// Searches on the players' clocks alone, for games between engines or
+    /// against the player. `go` caps `PlayersTime` searches at a second.
+    async fn go_on_clock(&mut self, mode: &GoMode) -> Result<(), Error> {
+        self.start_search(mode, true).await
+    }
+
+    async fn start_search(&mut self, mode: &GoMode, on_clock: bool) -> Result<(), Error> {
+        self.go_mode = mode.clone();
+        let pos = position_after(&self.options.fen, &self.options.moves)?;
+        let root_moves = self.options.root_moves(&pos)?;
+        let msg = match &mut self.protocol {
+            EngineProtocol::Uci => Self::format_go_command(mode, root_moves.as_deref(), on_clock),
+            EngineProtocol::Xboard(xboard) => {
+                // `go` takes the engine out of force mode and it plays its move
+                // on its own board, so set the position up before every search
+                let mut msg = xboard.position_commands(&self.options.fen, &self.options.moves)?;
+                msg.push_str(&xboard.exclude_commands(&pos, root_moves.as_deref())?);
+                msg.push_str(&xboard.go_command(mode, pos.turn()));
+                msg
+            }
+        };
+        
+        info!("Starting engine analysis: {}", msg.trim());
+        Self::send_command_with_log(&mut self.stdin, &msg, &self.logs).await?;
+        
+        self.running = true;
+        self.start = Instant::now();
+        self.last_event_sent = None;
+        Ok(())
+    }
+
+    fn format_go_command(mode: &GoMode, root_moves: Option<&[String]>, on_clock: bool) -> String {
+        let mut cmd = match mode {
+            GoMode::Depth(depth) => format!("go depth {depth}"),
+            GoMode::Time(time) => format!("go movetime {time}"),
+            GoMode::Nodes(nodes) => format!("go nodes {nodes}"),
+            GoMode::PlayersTime(PlayersTime { white, black, winc, binc }) if on_clock => {
+                format!("go wtime {white} btime {black} winc {winc} binc {binc}")
+            }
+            GoMode::PlayersTime(PlayersTime { white, black, winc, binc }) => {
+                format!("go wtime {white} btime {black} winc {winc} binc {binc} movetime 1000")
+            }
+            GoMode::Infinite => "go infinite".to_string(),
+        };
+        // searchmoves must come last, it consumes the rest of the line
+        if let Some(moves) = root_moves {
+            cmd.push_str(" searchmoves ");
+            cmd.push_str(&moves.join(" "));
+        }
+        cmd.push('\n');
+        cmd
+    }
+
+    /// Reads engine output until `bestmove` arrives, keeping the last complete
+    /// MultiPV set so callers get the lines the move was chosen from.
+    async fn wait_for_best_move(
+        &mut self,
+        reader: &mut EngineReader,
+    ) -> Result<BestMoveResult, Error> {
+        while let Some(line) = reader.next_line().await? {
+            trace!("Engine line: {}", line);
+            self.logs.push(EngineLog::Engine(line.clone()));
+
+            match self.parse_line(&line) {
+                Ok(EngineEvent::Info(best_moves)) => {
+                    if best_moves.multipv as usize == self.best_moves.len() + 1 {
+                        self.best_moves.push(best_moves);
+                        if self.best_moves.len() >= self.real_multipv.max(1) as usize {
+                            self.last_best_moves = std::mem::take(&mut self.best_moves);
+                        }
+                    }
+                }
+                Ok(EngineEvent::BestMove { best_move, ponder }) => {
+                    self.running = false;
+                    return Ok(BestMoveResult {
+                        best_move,
+                        ponder,
+                        lines: self.last_best_moves.clone(),
+                    });
+                }
+                Ok(EngineEvent::CurrentMove(_) | EngineEvent::Other) | Err(_) => {}
+            }
+        }
+
+        Err(Error::EngineExited)
+    }
+
+    async fn new_game(&mut self) -> Result<(), Error> {
+        let msg = match &mut self.protocol {
+            EngineProtocol::Uci => "ucinewgame\n",
+            EngineProtocol::Xboard(xboard) => xboard.new_game(),
+        };
+        Self::send_command_with_log(&mut self.stdin, msg, &self.logs).await?;
+        self.options = EngineOptions::default();
+        self.pondering = false;
+        self.reset_analysis_state();
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        if self.running {
+            info!("Stopping engine analysis");
+            let msg = match &mut self.protocol {
+                EngineProtocol::Uci => "stop\n",
+                EngineProtocol::Xboard(xboard) => xboard.stop_command(),
+            };
+            Self::send_command_with_log(&mut self.stdin, msg, &self.logs).await?;
+            self.running = false;
+            self.pondering = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the engine can think on the opponent's time. CECP engines
+    /// choose their own ponder move, so only UCI pondering is supported.
+    fn can_ponder(&self) -> bool {
+        matches!(self.protocol, EngineProtocol::Uci)
+    }
+
+    /// `Ponder=true`, when the engine advertises the option.
+    fn ponder_option(&self) -> Option<EngineOption> {
+        matches!(
+            options::find_option(&self.config, "Ponder"),
+            Some(UciOptionConfig::Check { .. })
+        )
+        .then(|| EngineOption {
+            name: "Ponder".to_string(),
+            value: "true".to_string(),
+        })
+    }
+
+    /// Starts thinking on the opponent's time, assuming they reply with
+    /// `ponder_move`. The clocks in `mode` are the ones the engine will
+    /// search with after `ponderhit`.
+    async fn ponder(&mut self, mode: &GoMode, ponder_move: &str) -> Result<(), Error> {
+        if !self.can_ponder() {
+            return Err(Error::EngineUnsupported("ponder".to_string()));
+        }
+        let mut moves = self.options.moves.clone();
+        moves.push(ponder_move.to_string());
+        self.set_options(EngineOptions {
+            moves,
+            ..self.options.clone()
+        })
+        .await?;
+
+        let msg = Self::format_go_command(mode, None, true).replacen("go ", "go ponder ", 1);
+        debug!("Pondering on {}", ponder_move);
+        Self::send_command_with_log(&mut self.stdin, &msg, &self.logs).await?;
+
+        self.go_mode = mode.clone();
+        self.running = true;
+        self.pondering = true;
+        self.start = Instant::now();
+        self.last_event_sent = None;
+        Ok(())
+    }
+
+    /// Ends pondering once the opponent has moved. If the engine predicted
+    /// the position reached by `moves` it gets `ponderhit` and keeps
+    /// searching, and `true` is returned; otherwise the ponder search is
+    /// stopped and its move discarded, and a new search must be started.
+    async fn resolve_ponder(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        reader: &mut EngineReader,
+    ) -> Result<bool, Error> {
+        if !std::mem::take(&mut self.pondering) {
+            return Ok(false);
+        }
+        if self.options.fen == fen && self.options.moves == moves {
+            info!("Ponder hit after {}", moves.last().map_or("", |m| m.as_str()));
+            Self::send_command_with_log(&mut self.stdin, "ponderhit\n", &self.logs).await?;
+            return Ok(true);
+        }
+
+        debug!("Ponder miss, stopping the ponder search");
+        self.stop().await?;
+        timeout(PONDER_STOP_TIMEOUT, self.wait_for_best_move(reader))
+            .await
+            .map_err(|_| Error::EngineTimeout)??;
+        Ok(false)
+    }
+
+    /// Parses a line of engine output for the current position.
+    fn parse_line(&self, line: &str) -> Result<EngineEvent, Error> {
+        match &self.protocol {
+            EngineProtocol::Uci => match parse_one(line) {
+                UciMessage::Info(attrs) => match parse_current_move(&attrs) {
+                    Some(current_move) => Ok(EngineEvent::CurrentMove(current_move)),
+                    None => Ok(EngineEvent::Info(parse_uci_info(
+                        attrs,
+                        &self.options.fen.parse()?,
+                        &self.options.moves,
+                    )?)),
+                },
+                UciMessage::BestMove { best_move, ponder } => Ok(EngineEvent::BestMove {
+                    best_move: best_move.to_string(),
+                    ponder: ponder.map(|m| m.to_string()),
+                }),
+                _ => Ok(EngineEvent::Other),
+            },
+            EngineProtocol::Xboard(xboard) => xboard.parse_line(line, &self.options),
+        }
+    }
+
+    /// Waits briefly for the engine process to exit and returns its exit
+    /// code, which is `None` for remote engines or if it was killed by a signal.
+    async fn exit_code(&mut self) -> Option<i32> {
+        let child = self.child.as_mut()?;
+        match timeout(ENGINE_EXIT_WAIT, child.wait()).await {
+            Ok(Ok(status)) => status.code(),
+            _ => None,
+        }
+    }
+
+    /// Describes a crash once the engine's output has ended.
+    async fn crash_status(&mut self) -> EngineStatus {
+        EngineStatus::Crashed {
+            exit_code: self.exit_code().await,
+            stderr: self.logs.last_stderr(CRASH_STDERR_LINES),
+        }
+    }
+
+    /// Starts a fresh engine from the same path and launch settings, keeping
+    /// the logs. The new engine is idle; callers decide whether to resume.
+    async fn respawn(&mut self) -> Result<EngineReader, Error> {
+        info!("Restarting engine {:?}", self.path);
+        let (mut process, reader) = Self::new(self.path.clone(), &self.launch).await?;
+        process.logs.prepend(self.logs.entries());
+        *self = process;
+        Ok(reader)
+    }
+
+    /// Replaces a crashed engine and resumes the search it was running with
+    /// the same options and go mode.
+    async fn restart(&mut self) -> Result<EngineReader, Error> {
+        let options = self.options.clone();
+        let go_mode = self.go_mode.clone();
+        let was_running = self.running;
+
+        let reader = self.respawn().await?;
+        self.set_options(options).await?;
+        if was_running {
+            self.go(&go_mode).await?;
+        }
+        Ok(reader)
+    }
+
+    async fn kill(&mut self) -> Result<(), Error> {
+        info!("Terminating engine process");
+        self.quit = true;
+        Self::send_command_with_log(&mut self.stdin, "quit\n", &self.logs).await?;
+        self.running = false;
+        Ok(())
+    }
+}
+
+/// Outcome of a single search that ran until the engine sent `bestmove`.
+#[derive(Debug, Clone)]
+struct BestMoveResult {
+    best_move: String,
+    /// The reply the engine expects, for pondering.
+    ponder: Option<String>,
+    lines: Vec<BestMoves>,
+}
+
+/// Plays `moves` from `fen`, tolerating positions with too much material.
+fn position_after(fen: &str, moves: &[String]) -> Result<Chess, Error> {
+    let fen: Fen = fen.parse()?;
+    let mut pos: Chess = match fen.into_position(CastlingMode::Chess960) {
+        Ok(p) => p,
+        Err(e) => e.ignore_too_much_material()?,
+    };
+    for m in moves {
+        let mv = UciMove::from_ascii(m.as_bytes())?.to_move(&pos)?;
+        pos.play_unchecked(&mv);
+    }
+    Ok(pos)
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Derivative, Type)]
+#[derivative(Default)]
+pub struct BestMoves {
+    // Counters are sent as JSON numbers, which the frontend reads as numbers
+    #[specta(type = f64)]
+    nodes: u64,
+    depth: u32,
+    #[serde(default)]
+    seldepth: u32,
+    score: Score,
+    /// The score is only a bound (white-relative), not an exact value.
+    #[serde(rename = "lowerBound", default)]
+    lower_bound: bool,
+    #[serde(rename = "upperBound", default)]
+    upper_bound: bool,
+    #[serde(rename = "uciMoves")]
+    uci_moves: Vec<String>,
+    #[serde(rename = "sanMoves")]
+    san_moves: Vec<String>,
+    #[derivative(Default(value = "1"))]
+    multipv: u16,
+    #[specta(type = f64)]
+    nps: u64,
+    /// Hash table usage in permille.
+    #[serde(default)]
+    hashfull: u32,
+    #[serde(default)]
+    #[specta(type = f64)]
+    tbhits: u64,
+    /// Search time in milliseconds.
+    #[serde(default)]
+    #[specta(type = f64)]
+    time: u64,
+}
+
+/// Root move the engine is searching, from `currmove`/`currmovenumber`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentMove {
+    pub uci_move: String,
+    pub number: u32,
+}
+
+#[derive(Serialize, Debug, Clone, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct BestMovesPayload {
+    pub best_lines: Vec<BestMoves>,
+    pub engine: String,
+    pub tab: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub progress: f64,
+    pub current_move: Option<CurrentMove>,
+}
+
+/// Tells the UI when an engine starts, dies or is brought back, since a
+/// crashed engine otherwise looks like one that is still thinking.
+#[derive(Serialize, Debug, Clone, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineStatusPayload {
+    pub engine: String,
+    /// The analysis id for game analysis.
+    pub tab: String,
+    pub status: EngineStatus,
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum EngineStatus {
+    Started,
+    Crashed {
+        /// `None` for remote engines and processes killed by a signal.
+        #[serde(rename = "exitCode")]
+        exit_code: Option<i32>,
+        /// The last lines the engine wrote to stderr.
+        stderr: Vec<String>,
+    },
+    Restarted {
+        attempt: u32,
+    },
+}
+
+fn emit_engine_status(app: &tauri::AppHandle, engine: &str, tab: &str, status: EngineStatus) {
+    let payload = EngineStatusPayload {
+        engine: engine.to_string(),
+        tab: tab.to_string(),
+        status,
+    };
+    if let Err(e) = payload.emit(app) {
+        warn!("Failed to emit engine status: {}", e);
+    }
+}
+
+fn invert_score(score: Score) -> Score {
+    let new_value = match score.value {
+        ScoreValue::Cp(x) => ScoreValue::Cp(-x),
+        ScoreValue::Mate(x) => ScoreValue::Mate(-x),
+    };
+    let new_wdl = score.wdl.map(|(w, d, l)| (l, d, w));
+    // A lower bound for one side is an upper bound for the other
+    Score {
+        value: new_value,
+        wdl: new_wdl,
+        lower_bound: score.upper_bound,
+        upper_bound: score.lower_bound,
+    }
+}
+
+/// `currmove` is sent on its own info line, without a PV.
+fn parse_current_move(attrs: &[UciInfoAttribute]) -> Option<CurrentMove> {
+    let mut current_move = None;
+    let mut number = 0;
+    for attr in attrs {
+        match attr {
+            UciInfoAttribute::CurrMove(mv) => current_move = Some(mv.to_string()),
+            UciInfoAttribute::CurrMoveNum(n) => number = *n as u32,
+            _ => {}
+        }
+    }
+    current_move.map(|uci_move| CurrentMove { uci_move, number })
+}
+
+fn parse_uci_info(
+    attrs: Vec<UciInfoAttribute>,
+    fen: &Fen,
+    moves: &[String],
+) -> Result<BestMoves, Error> {
+    trace!("Parsing UCI info attributes: {} attributes", attrs.len());
+    
+    let mut best_moves = BestMoves::default();
+    let mut has_depth = false;
+    let mut has_multipv = false;
+    let mut has_pv = false;
+
+    let mut pos: Chess = match fen.clone().into_position(CastlingMode::Chess960) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!("Position error in parse_uci_info, attempting to ignore extra material");
+            e.ignore_too_much_material()?
+        }
+    };
+    
+    // Apply moves to get current position
+    for (i, move_str) in moves.iter().enumerate() {
+        let uci = UciMove::from_ascii(move_str.as_bytes())
+            .map_err(|e| {
+                error!("Invalid UCI move in parse_uci_info at index {}: {}", i, move_str);
+                e
+            })?;
+        let mv = uci.to_move(&pos)?;
+        pos.play_unchecked(&mv);
+    }
+    
+    let turn = pos.turn();
+
+    for attr in attrs {
+        match attr {
+            UciInfoAttribute::Pv(moves) => {
+                trace!("Processing PV with {} moves", moves.len());
+                has_pv = true;
+                let mut temp_pos = pos.clone();
+                
+                for mv in moves {
+                    let uci: UciMove = mv.to_string().parse()?;
+                    let m = uci.to_move(&temp_pos)?;
+                    let san = SanPlus::from_move_and_play_unchecked(&mut temp_pos, &m);
+                    best_moves.san_moves.push(san.to_string());
+                    best_moves.uci_moves.push(uci.to_string());
+                }
+            }
+            UciInfoAttribute::Nps(nps) => {
+                best_moves.nps = nps as u64;
+            }
+            UciInfoAttribute::Nodes(nodes) => {
+                best_moves.nodes = nodes as u64;
+            }
+            UciInfoAttribute::SelDepth(seldepth) => {
+                best_moves.seldepth = seldepth as u32;
+            }
+            UciInfoAttribute::HashFull(hashfull) => {
+                best_moves.hashfull = hashfull as u32;
+            }
+            UciInfoAttribute::TbHits(tbhits) => {
+                best_moves.tbhits = tbhits as u64;
+            }
+            UciInfoAttribute::Time(time) => {
+                best_moves.time = time.num_milliseconds().max(0) as u64;
+            }
+            UciInfoAttribute::Depth(depth) => {
+                best_moves.depth = depth;
+                has_depth = true;
+                trace!("Found depth: {}", depth);
+            }
+            UciInfoAttribute::MultiPv(multipv) => {
+                best_moves.multipv = multipv;
+                has_multipv = true;
+                trace!("Found multipv: {}", multipv);
+            }
+            UciInfoAttribute::Score(score) => {
+                trace!("Found score: {:?}", score);
+                best_moves.score = score;
+            }
+            _ => {}
+        }
+    }
+
+    if !has_pv || best_moves.san_moves.is_empty() {
+        trace!("No PV found in UCI info (has_pv={}, moves={})", has_pv, best_moves.san_moves.len());
+        return Err(Error::NoMovesFound);
+    }
+
+    // Invert score for black to move
+    if turn == Color::Black {
+        best_moves.score = invert_score(best_moves.score);
+    }
+    best_moves.lower_bound = best_moves.score.lower_bound.unwrap_or(false);
+    best_moves.upper_bound = best_moves.score.upper_bound.unwrap_or(false);
+
+    debug!("Successfully parsed UCI info: depth={} (has_depth={}), multipv={} (has_multipv={}), moves={}", 
+           best_moves.depth, has_depth, best_moves.multipv, has_multipv, best_moves.san_moves.len());
+    
+    Ok(best_moves)
+}
+
+// Engine management commands
+#[tauri::command]
+#[specta::specta]
+pub async fn kill_engines(tab: String, state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    info!("Killing all engines for tab: {}", tab);
+    
+    let keys: Vec<_> = state
+        .engine_processes
+        .iter()
+        .filter(|entry| entry.key().0.starts_with(&tab))
+        .map(|entry| entry.key().clone())
+        .collect();
+
+    debug!("Found {} engines to kill for tab: {}", keys.len(), tab);
+    
+    for key in keys {
+        if let Some(process_guard) = state.engine_processes.get(&key) {
+            let mut process = process_guard.lock().await;
+            if let Err(e) = process.kill().await {
+                warn!("Failed to kill engine {:?}: {}", key, e);
+            }
+        }
+        state.engine_processes.remove(&key);
+    }
+
+    let sessions: Vec<_> = state
+        .consensus_sessions
+        .iter()
+        .filter(|entry| entry.key().starts_with(&tab))
+        .map(|entry| entry.key().clone())
+        .collect();
+    for id in sessions {
+        if let Some((_, session)) = state.consensus_sessions.remove(&id) {
+            consensus::kill_session(&session).await;
+        }
+    }
+    
+    info!("Completed killing engines for tab: {}", tab);
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kill_engine(
+    engine: String,
+    tab: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    let key = (tab.clone(), engine.clone());
+    info!("Killing engine: tab={}, engine={}", tab, engine);
+    
+    if let Some(process) = state.engine_processes.get(&key) {
+        let mut process = process.lock().await;
+        if let Err(e) = process.kill().await {
+            warn!("Failed to kill engine {:?}: {}", key, e);
+        }
+    }
+    
+    state.engine_processes.remove(&key);
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_engine(
+    engine: String,
+    tab: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    let key = (tab.clone(), engine.clone());
+    debug!("Stopping engine: tab={}, engine={}", tab, engine);
+    
+    if let Some(process) = state.engine_processes.get(&key) {
+        let mut process = process.lock().await;
+        if let Err(e) = process.stop().await {
+            warn!("Failed to stop engine {:?}: {}", key, e);
+        }
+    } else {
+        debug!("Engine not found: {:?}", key);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_engine_logs(
+    engine: String,
+    tab: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<EngineLog>, Error> {
+    let key = (tab, engine);
+    if let Some(process) = state.engine_processes.get(&key) {
+        let process = process.lock().await;
+        Ok(process.logs.entries())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Writes an engine's log to a text file, one line per message.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_engine_logs(
+    engine: String,
+    tab: String,
+    file: PathBuf,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    let key = (tab, engine);
+    let logs = match state.engine_processes.get(&key) {
+        Some(process) => process.lock().await.logs.clone(),
+        None => EngineLogs::default(),
+    };
+
+    let mut writer = BufWriter::new(File::create(&file)?);
+    logs.write_to(&mut writer)?;
+    writer.flush()?;
+    info!("Exported engine logs for {:?} to {}", key, file.display());
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_best_moves(
+    id: String,
+    engine: String,
+    tab: String,
+    go_mode: GoMode,
+    mut options: EngineOptions,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<(f32, Vec<BestMoves>)>, Error> {
+    let path = PathBuf::from(&engine);
+    apply_preset(&app, &engine, options.preset.as_deref(), &mut options.extra_options).await?;
+    let key = (tab.clone(), engine.clone());
+
+    info!("Getting best moves: id={}, engine={}, tab={}", id, engine, tab);
+    debug!("Analysis options: FEN={}, moves={}", options.fen, options.moves.len());
+
+    // Check if engine is already running with same parameters
+    if let Some(process_guard) = state.engine_processes.get(&key) {
+        let process = process_guard.lock().await;
+        if options == process.options && go_mode == process.go_mode && process.running {
+            debug!("Engine already running with same parameters, returning cached results");
+            return Ok(Some((process.last_progress, process.last_best_moves.clone())));
+        }
+    }
+
+    // Lines limited to some root moves are not the position's best lines
+    let cache = analysis_cache(&app).filter(|_| !options.restricts_moves());
+    let cache_key = AnalysisCacheKey::new(&engine, &options)?;
+    let cached = cache.as_ref().and_then(|cache| {
+        cache
+            .get(&cache_key)
+            .map_err(|e| warn!("Failed to read analysis cache: {}", e))
+            .ok()
+            .flatten()
+    });
+
+    if let Some(cached) = &cached {
+        if matches!(go_mode, GoMode::Depth(depth) if cached.depth >= depth) {
+            info!("Using cached analysis at depth {} for {}", cached.depth, cache_key.fen);
+            if let Some(process_guard) = state.engine_processes.get(&key) {
+                let mut process = process_guard.lock().await;
+                if let Err(e) = process.stop().await {
+                    warn!("Failed to stop existing engine: {}", e);
+                }
+            }
+            BestMovesPayload {
+                best_lines: cached.lines.clone(),
+                engine: id,
+                tab,
+                fen: options.fen,
+                moves: options.moves,
+                progress: 100.0,
+                current_move: None,
+            }
+            .emit(&app)?;
+            return Ok(Some((100.0, cached.lines.clone())));
+        }
+    }
+
+    if let Some(process_guard) = state.engine_processes.get(&key) {
+        let mut process = process_guard.lock().await;
+        info!("Stopping existing engine for parameter change");
+        if let Err(e) = process.stop().await {
+            warn!("Failed to stop existing engine: {}", e);
+        }
+        
+        drop(process); // Release lock before delay
+        tokio::time::sleep(ENGINE_STOP_DELAY).await;
+        
+        let mut process = process_guard.lock().await;
+        if let Err(e) = process.set_options(options.clone()).await {
+            error!("Failed to set engine options: {}", e);
+            return Err(e);
+        }
+        
+        if let Err(e) = process.go(&go_mode).await {
+            error!("Failed to start engine analysis: {}", e);
+            return Err(e);
+        }
+
+        if let Some(cached) = &cached {
+            process.restore_cached(cached);
+            return Ok(Some((process.last_progress, cached.lines.clone())));
+        }
+        
+        return Ok(None);
+    }
+
+    // Create new engine process
+    info!("Creating new engine process");
+    let (mut process, reader) = match EngineProcess::new(path.clone(), &launch.unwrap_or_default()).await {
+        Ok((proc, reader)) => (proc, reader),
+        Err(e) => {
+            error!("Failed to create engine process for {:?}: {}", path, e);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = process.set_options(options.clone()).await {
+        error!("Failed to set initial options: {}", e);
+        return Err(e);
+    }
+
+    if let Err(e) = process.go(&go_mode).await {
+        error!("Failed to start initial analysis: {}", e);
+        return Err(e);
+    }
+
+    let initial = cached.map(|cached| {
+        process.restore_cached(&cached);
+        (process.last_progress, cached.lines)
+    });
+    emit_engine_status(&app, &id, &tab, EngineStatus::Started);
+
+    let process = Arc::new(Mutex::new(process));
+    state.engine_processes.insert(key.clone(), process.clone());
+
+    // Start engine communication loop
+    let engine_processes_ref = state.engine_processes.clone();
+    tokio::spawn(engine_communication_loop(
+        process.clone(),
+        reader,
+        key.clone(),
+        id,
+        tab.clone(),
+        app,
+        cache,
+        engine_processes_ref,
+    ));
+
+    Ok(initial)
+}
+
+async fn engine_communication_loop(
+    process: Arc<Mutex<EngineProcess>>,
+    mut reader: EngineReader,
+    key: (String, String),
+    id: String,
+    tab: String,
+    app: tauri::AppHandle,
+    cache: Option<Arc<AnalysisCache>>,
+    engine_processes: DashMap<(String, String), Arc<tokio::sync::Mutex<EngineProcess>>>,
+) {
+    info!("Starting engine communication loop for: {:?}", key);
+    
+    let rate_limiter = RateLimiter::direct(Quota::per_second(nonzero!(EVENTS_PER_SECOND)));
+    let mut last_best_moves_payload: Option<BestMovesPayload> = None;
+    let mut first_result_sent = false;
+    let mut timeout_count = 0;
+    let mut last_timeout_emit = Instant::now();
+    let mut restarts = 0;
+
+    let result = async {
+        loop {
+            match timeout(TICK_DURATION, reader.next_line()).await {
+                Ok(Ok(Some(line))) => {
+                    debug!("Raw engine output: {}", line);
+                    
+                    // Reset timeout counter on successful read
+                    timeout_count = 0;
+                    
+                    let mut proc = process.lock().await;
+                    proc.logs.push(EngineLog::Engine(line.clone()));
+                    
+                    match proc.parse_line(&line) {
+                        Ok(EngineEvent::Info(best_moves)) => {
+                            let depth_before = proc.last_depth;
+                            if let Err(e) = handle_info_message(
+                                &mut proc,
+                                best_moves,
+                                &rate_limiter,
+                                &mut last_best_moves_payload,
+                                &mut first_result_sent,
+                                &id,
+                                &tab,
+                                &app
+                            ).await {
+                                warn!("Failed to handle info message: {}", e);
+                            }
+                            if proc.last_depth > depth_before {
+                                if let Some(cache) = &cache {
+                                    store_analysis(cache, &key.1, &proc);
+                                }
+                            }
+                        }
+                        Ok(EngineEvent::BestMove { .. }) => {
+                            debug!("Received bestmove, analysis complete");
+                            
+                            let payload = BestMovesPayload {
+                                best_lines: proc.last_best_moves.clone(),
+                                engine: id.clone(),
+                                tab: tab.clone(),
+                                fen: proc.options.fen.clone(),
+                                moves: proc.options.moves.clone(),
+                                progress: 100.0,
+                                current_move: proc.current_move.clone(),
+                            };
+
+                            info!("Emitting final bestmove payload for engine {} on tab {}", id, tab);
+
+                            match payload.emit(&app) {
+                                Ok(_) => {
+                                    debug!("Successfully emitted bestmove payload");
+                                    tokio::time::sleep(Duration::from_millis(30)).await;
+                                }
+                                Err(e) => {
+                                    error!("Failed to emit final bestmove payload: {:?}", e);
+                                }
+                            }
+
+                            if let Some(cache) = &cache {
+                                store_analysis(cache, &key.1, &proc);
+                            }
+
+                            info!("Analysis complete, cleaning up engine process");
+                            
+                            proc.last_progress = 100.0;
+                            proc.running = false;
+                        }
+                        Ok(EngineEvent::CurrentMove(current_move)) => {
+                            proc.current_move = Some(current_move);
+                        }
+                        Ok(EngineEvent::Other) => {
+                            trace!("Unhandled engine message: {}", line);
+                        }
+                        Err(e) => {
+                            trace!("Failed to parse engine message {}: {}", line, e);
+                        }
+                    }
+                }
+                Ok(read) => {
+                    match read {
+                        Ok(_) => debug!("Engine closed stdout"),
+                        Err(e) => error!("Error reading from engine stdout: {}", e),
+                    }
+
+                    let mut proc = process.lock().await;
+                    if proc.quit {
+                        break;
+                    }
+                    let status = proc.crash_status().await;
+                    error!("Engine {:?} crashed: {:?}", key, status);
+                    emit_engine_status(&app, &id, &tab, status);
+
+                    if !proc.launch.auto_restart || restarts >= MAX_ENGINE_RESTARTS {
+                        break;
+                    }
+                    restarts += 1;
+                    reader = match proc.restart().await {
+                        Ok(reader) => reader,
+                        Err(e) => {
+                            // The engine is gone for good, so this is its final status
+                            let status = proc.crash_status().await;
+                            emit_engine_status(&app, &id, &tab, status);
+                            return Err(e);
+                        }
+                    };
+                    emit_engine_status(
+                        &app,
+                        &id,
+                        &tab,
+                        EngineStatus::Restarted { attempt: restarts },
+                    );
+                }
+                Err(_) => {
+                    timeout_count += 1;
+                    
+                    // Only consider emitting timeout payloads if we have pending data
+                    // and enough time has passed since the last timeout emission
+                    if let Some(ref payload) = last_best_moves_payload {
+                        let now = Instant::now();
+                        let time_since_last_emit = now.duration_since(last_timeout_emit);
+                        
+                        // Use exponential backoff for timeout intervals to reduce spam
+                        let timeout_threshold = if timeout_count < 10 {
+                            MAX_EVENT_INTERVAL
+                        } else if timeout_count < 50 {
+                            Duration::from_millis(200) // 200ms after initial period
+                        } else {
+                            Duration::from_millis(500) // 500ms for long periods of no activity
+                        };
+                        
+                        let proc = process.lock().await;
+                        let should_emit_timeout = proc.last_event_sent.map_or(
+                            time_since_last_emit >= timeout_threshold,
+                            |t| t.elapsed() >= timeout_threshold
+                        );
+                        
+                        if should_emit_timeout {
+                            trace!("Emitting timeout payload after {}ms (timeout_count: {})", 
+                                   timeout_threshold.as_millis(), timeout_count);
+                            
+                            if let Err(e) = payload.emit(&app) {
+                                warn!("Failed to emit timeout payload: {}", e);
+                            } else {
+                                let mut proc = process.lock().await;
+                                proc.last_event_sent = Some(now);
+                                last_timeout_emit = now;
+                            }
+                            
+                            last_best_moves_payload = None;
+                        }
+                    }
+                }
+            }
+        }
+        Ok::<_, Error>(())
+    }.await;
+
+    if let Err(e) = result {
+        error!("Engine communication loop error for {:?}: {}", key, e);
+    }
+    
+    info!("Engine communication loop finished for: {:?}", key);
+    engine_processes.remove(&key);
+}
+
+fn store_analysis(cache: &Arc<AnalysisCache>, engine: &str, proc: &EngineProcess) {
+    // Lines limited to some root moves are not the position's best lines
+    if proc.options.restricts_moves() {
+        return;
+    }
+    let key = match AnalysisCacheKey::new(engine, &proc.options) {
+        Ok(key) => key,
+        Err(e) => {
+            warn!("Failed to store analysis in cache: {}", e);
+            return;
+        }
+    };
+    // The write blocks on SQLite, so keep it off the loop holding the engine lock
+    let cache = Arc::clone(cache);
+    let lines = proc.last_best_moves.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = cache.put(&key, &lines) {
+            warn!("Failed to store analysis in cache: {}", e);
+        }
+    });
+}
+
+async fn handle_info_message(
+    proc: &mut EngineProcess,
+    best_moves: BestMoves,
+    rate_limiter: &RateLimiter<
+        governor::state::direct::NotKeyed,
+        governor::state::InMemoryState,
+        governor::clock::DefaultClock,
+        governor::middleware::NoOpMiddleware
+    >,
+    last_best_moves_payload: &mut Option<BestMovesPayload>,
+    first_result_sent: &mut bool,
+    id: &str,
+    tab: &str,
+    app: &tauri::AppHandle,
+) -> Result<(), Error> {
+    let multipv = best_moves.multipv;
+    let cur_depth = best_moves.depth;
+    let cur_nodes = best_moves.nodes;
+    
+    debug!("Received info: depth={}, multipv={}, nodes={}, last_depth={}", 
+           cur_depth, multipv, cur_nodes, proc.last_depth);
+    
+    if multipv as usize == proc.best_moves.len() + 1 {
+        proc.best_moves.push(best_moves);
+        debug!("Added move to collection: total={}/{}", proc.best_moves.len(), proc.real_multipv);
+        
+        if multipv == proc.real_multipv {
+            let all_same_depth = proc.best_moves.iter().all(|x| x.depth == cur_depth);
+            debug!("Complete multipv set: all_same_depth={}, cur_depth={}, last_depth={}", 
+                   all_same_depth, cur_depth, proc.last_depth);
+            
+            if all_same_depth && cur_depth >= proc.last_depth {
+                let progress = calculate_progress(&proc.go_mode, cur_depth, cur_nodes, proc.start.elapsed());
+                
+                let payload = BestMovesPayload {
+                    best_lines: proc.best_moves.clone(),
+                    engine: id.to_string(),
+                    tab: tab.to_string(),
+                    fen: proc.options.fen.clone(),
+                    moves: proc.options.moves.clone(),
+                    progress,
+                    current_move: proc.current_move.clone(),
+                };
+                
+                let should_emit = if !*first_result_sent {
+                    debug!("Emitting first analysis result: depth={}, multipv={}", cur_depth, multipv);
+                    true
+                } else if cur_depth > proc.last_depth {
+                    // Always emit when we progress to a new depth
+                    debug!("Depth progression detected: {} -> {}", proc.last_depth, cur_depth);
+                    true
+                } else if proc.last_event_sent.map_or(false, |t| t.elapsed() >= MIN_EVENT_INTERVAL) && rate_limiter.check().is_ok() {
+                    debug!("Emitting update for same depth due to time interval: depth={}", cur_depth);
+                    true
+                } else {
+                    // Only store payload for timeout emission if it represents meaningful progress
+                    // (new depth or significant time has passed since last event)
+                    if cur_depth > proc.last_depth || 
+                       proc.last_event_sent.map_or(true, |t| t.elapsed() >= Duration::from_millis(200)) {
+                        trace!("Storing meaningful payload for potential timeout emission: depth={}", cur_depth);
+                        *last_best_moves_payload = Some(payload.clone());
+                    }
+                    false
+                };
+                
+                if should_emit {
+                    debug!("Emitting analysis result: depth={}, progress={:.2}%", cur_depth, progress);
+                    proc.last_event_sent = Some(Instant::now());
+                    payload.emit(app)?;
+                    *first_result_sent = true;
+                    *last_best_moves_payload = None;
+                    
+                    // Only update last_depth when we actually emit a result for a new depth
+                    if cur_depth > proc.last_depth {
+                        proc.last_depth = cur_depth;
+                        debug!("Updated last_depth to: {}", proc.last_depth);
+                    }
+                }
+
+                proc.last_best_moves = proc.best_moves.clone();
+                proc.last_progress = progress as f32;
+            }
+            proc.best_moves.clear();
+        }
+    }
+    
+    Ok(())
+}
+
+fn calculate_progress(go_mode: &GoMode, depth: u32, nodes: u64, elapsed: Duration) -> f64 {
+    match go_mode {
+        GoMode::Depth(target_depth) => {
+            (depth as f64 / *target_depth as f64) * 100.0
+        }
+        GoMode::Time(target_time) => {
+            (elapsed.as_millis() as f64 / *target_time as f64) * 100.0
+        }
+        GoMode::Nodes(target_nodes) => {
+            (nodes as f64 / *target_nodes as f64) * 100.0
+        }
+        GoMode::PlayersTime(_) => {
+            (depth as f64 / 20.0).min(0.99) * 100.0 // Assume ~20 depth target
+        }
+        GoMode::Infinite => {
+            // Use time-based estimation for infinite analysis
+            let time_factor = (elapsed.as_secs() as f64 / 30.0).min(0.99); // 30 second estimation
+            time_factor * 100.0
+        }
+    }
+}
+
+// Rest of the original structs and implementations
+#[derive(Deserialize, Debug, Clone, Type, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[derivative(Default)]
+pub struct EngineOptions {
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub extra_options: Vec<EngineOption>,
+    /// Only search these root moves.
+    #[serde(default)]
+    pub search_moves: Vec<String>,
+    /// Search every legal root move except these.
+    #[serde(default)]
+    pub exclude_moves: Vec<String>,
+    /// A saved preset for this engine, applied under `extra_options`.
+    #[serde(default)]
+    #[specta(optional)]
+    pub preset: Option<String>,
+}
+
+impl EngineOptions {
+    fn restricts_moves(&self) -> bool {
+        !self.search_moves.is_empty() || !self.exclude_moves.is_empty()
+    }
+
+    /// Returns the root moves the engine may search, or `None` when every
+    /// move is allowed. Excluded moves are sent as `searchmoves` with the
+    /// remaining legal moves, since UCI has no way to exclude a move.
+    fn root_moves(&self, pos: &Chess) -> Result<Option<Vec<String>>, Error> {
+        if !self.restricts_moves() {
+            return Ok(None);
+        }
+        let normalize = |moves: &[String]| -> Result<Vec<String>, Error> {
+            moves
+                .iter()
+                .map(|m| {
+                    let mv = UciMove::from_ascii(m.as_bytes())?.to_move(pos)?;
+                    Ok(mv.to_uci(CastlingMode::Standard).to_string())
+                })
+                .collect()
+        };
+
+        let excluded = normalize(&self.exclude_moves)?;
+        let candidates = if self.search_moves.is_empty() {
+            pos.legal_moves()
+                .iter()
+                .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+                .collect()
+        } else {
+            normalize(&self.search_moves)?
+        };
+        let root_moves: Vec<String> = candidates
+            .into_iter()
+            .filter(|m| !excluded.contains(m))
+            .collect();
+        if root_moves.is_empty() {
+            return Err(Error::NoMovesFound);
+        }
+        Ok(Some(root_moves))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type, PartialEq, Eq)]
+pub struct EngineOption {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type, PartialEq, Eq)]
+#[serde(tag = "t", content = "c")]
+pub enum GoMode {
+    PlayersTime(PlayersTime),
+    Depth(u32),
+    Time(u32),
+    Nodes(u32),
+    Infinite,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type, PartialEq, Eq)]
+pub struct PlayersTime {
+    white: u32,
+    black: u32,
+    winc: u32,
+    binc: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Type)]
+pub struct MoveAnalysis {
+    best: Vec<BestMoves>,
+    novelty: bool,
+    is_sacrifice: bool,
+    /// White's chance to win the position, in percent.
+    #[serde(default)]
+    #[specta(optional)]
+    win_chance: Option<f64>,
+    /// How good the move played from this position was.
+    #[serde(default)]
+    #[specta(optional)]
+    classification: Option<MoveClassification>,
+    /// What the reference database had, on the position reached by the
+    /// novelty.
+    #[serde(default)]
+    #[specta(optional)]
+    novelty_info: Option<Novelty>,
+}
+
+#[derive(Deserialize, Debug, Default, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisOptions {
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub annotate_novelties: bool,
+    pub reference_db: Option<PathBuf>,
+    pub reversed: bool,
+    /// Analyze the best alternative to each played move instead.
+    #[serde(default)]
+    pub exclude_played_moves: bool,
+    /// A saved preset for this engine, applied under `uci_options`.
+    #[serde(default)]
+    #[specta(optional)]
+    pub preset: Option<String>,
+}
+
+#[derive(Clone, Type, serde::Serialize, Event)]
+pub struct ReportProgress {
+    pub progress: f64,
+    pub id: String,
+    pub finished: bool,
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn analyze_game(
+    id: String,
+    engine: String,
+    go_mode: GoMode,
+    options: AnalysisOptions,
+    mut uci_options: Vec<EngineOption>,
+    launch: Option<EngineLaunch>,
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<GameAnalysis, Error> {
+    apply_preset(&app, &engine, options.preset.as_deref(), &mut uci_options).await?;
+    info!("Starting game analysis: id={}, engine={}", id, engine);
+    debug!("Analysis options: FEN={}, moves={}, novelties={}", 
+           options.fen, options.moves.len(), options.annotate_novelties);
+    
+    let path = PathBuf::from(&engine);
+    let mut analysis: Vec<MoveAnalysis> = Vec::new();
+
+    let (mut proc, mut reader) = match EngineProcess::new(path.clone(), &launch.unwrap_or_default()).await {
+        Ok((p, r)) => (p, r),
+        Err(e) => {
+            error!("Failed to create engine for game analysis: {}", e);
+            return Err(e);
+        }
+    };
+    emit_engine_status(&app, &engine, &id, EngineStatus::Started);
+    let mut restarts = 0;
+
+    // Parse initial position
+    let fen = Fen::from_ascii(options.fen.as_bytes())
+        .map_err(|e| {
+            error!("Invalid FEN in analyze_game: {}", options.fen);
+            e
+        })?;
+
+    let mut chess: Chess = fen.clone().into_position(CastlingMode::Chess960)
+        .map_err(|e| {
+            error!("Failed to create position from FEN: {}", e);
+            e
+        })?;
+    
+    // Build positions to analyze
+    let mut positions_to_analyze = build_analysis_positions(&mut chess, &fen, &options)?;
+    
+    let novelty = if options.annotate_novelties {
+        novelty::find_novelty(&positions_to_analyze, &options.reference_db, &state)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to check novelties: {}", e);
+                None
+            })
+    } else {
+        None
+    };
+
+    if options.reversed {
+        debug!("Reversing analysis order");
+        positions_to_analyze.reverse();
+    }
+
+    let total_positions = positions_to_analyze.len();
+    
+    info!("Analyzing {} positions", total_positions);
+
+    for (i, (position_fen, moves, is_sacrifice)) in positions_to_analyze.iter().enumerate() {
+        debug!("Analyzing position {}/{}: {} moves", i + 1, total_positions, moves.len());
+        
+        // Emit progress update
+        ReportProgress {
+            progress: (i as f64 / total_positions as f64) * 100.0,
+            id: id.clone(),
+            finished: false,
+        }.emit(&app)?;
+
+        // Setup engine options for this position
+        let mut analysis_options = uci_options.clone();
+        ensure_multipv_option(&mut analysis_options, &proc.config);
+
+        let only_move = position_fen
+            .clone()
+            .into_position::<Chess>(CastlingMode::Chess960)
+            .is_ok_and(|pos| pos.legal_moves().len() == 1);
+        let exclude_moves = match options.moves.get(moves.len()) {
+            // Excluding the only legal move would leave nothing to search
+            Some(played) if options.exclude_played_moves && !only_move => vec![played.clone()],
+            _ => vec![],
+        };
+
+        let engine_options = EngineOptions {
+            fen: options.fen.clone(),
+            moves: moves.clone(),
+            extra_options: analysis_options,
+            exclude_moves,
+            ..Default::default()
+        };
+
+        let result = analyze_with_retries(
+            &mut proc,
+            &mut reader,
+            engine_options,
+            &go_mode,
+            &mut restarts,
+            (&app, &engine, &id),
+        )
+        .await;
+
+        // Failed positions keep an empty entry so results stay aligned with moves
+        let mut current_analysis = MoveAnalysis::default();
+        match result {
+            Ok(best_moves) => {
+                current_analysis.best = best_moves;
+            }
+            Err(e) => {
+                warn!("Failed to analyze position {}: {}", i, e);
+            }
+        }
+
+        // Set sacrifice flag
+        current_analysis.is_sacrifice = *is_sacrifice;
+
+        analysis.push(current_analysis);
+    }
+
+    if options.reversed {
+        debug!("Reversing analysis results back to original order");
+        analysis.reverse();
+    }
+
+    if let Some((index, novelty)) = novelty {
+        info!("Novelty found at position {}", index);
+        if let Some(entry) = analysis.get_mut(index) {
+            entry.novelty = true;
+            entry.novelty_info = Some(novelty);
+        }
+    }
+
+    let start: Chess = fen.into_position(CastlingMode::Chess960)?;
+    classify::classify_analysis(&mut analysis, start.clone(), &options)?;
+    let summary = summary::summarize_analysis(&analysis, start, &options)?;
+
+    // Final progress update
+    ReportProgress {
+        progress: 100.0,
+        id: id.clone(),
+        finished: true,
+    }.emit(&app)?;
+    
+    info!("Game analysis completed: {} positions analyzed", analysis.len());
+    Ok(GameAnalysis {
+        moves: analysis,
+        summary,
+    })
+}
+
+fn build_analysis_positions(
+    chess: &mut Chess,
+    initial_fen: &Fen,
+    options: &AnalysisOptions
+) -> Result<Vec<(Fen, Vec<String>, bool)>, Error> {
+    let mut positions = vec![(initial_fen.clone(), vec![], false)];
+
+    for (i, move_str) in options.moves.iter().enumerate() {
+        let uci = UciMove::from_ascii(move_str.as_bytes())
+            .map_err(|e| {
+                error!("Invalid move at index {}: {}", i, move_str);
+                e
+            })?;
+        
+        let mv = uci.to_move(chess)
+            .map_err(|e| {
+                error!("Illegal move at index {}: {} in position {}", i, move_str, chess.board());
+                e
+            })?;
+        
+        let is_sacrifice = sacrifice::is_sacrifice(chess, &mv);
+        chess.play_unchecked(&mv);
+        let current_pos = chess.clone();
+        
+        if !chess.is_game_over() {
+            let moves_so_far: Vec<String> = options.moves.iter().take(i + 1).cloned().collect();
+            
+            positions.push((
+                Fen::from_position(current_pos, EnPassantMode::Legal),
+                moves_so_far,
+                is_sacrifice,
+            ));
+        }
+    }
+
+    debug!("Built {} positions for analysis", positions.len());
+    Ok(positions)
+}
+
+/// Asks for a second line, needed to judge how forced a move was, when the
+/// engine supports MultiPV.
+fn ensure_multipv_option(options: &mut Vec<EngineOption>, config: &EngineConfig) {
+    const DEFAULT_MULTIPV: &str = "2";
+
+    if options::find_option(config, "MultiPV").is_none() {
+        return;
+    }
+
+    if let Some(multipv_option) = options.iter_mut().find(|x| x.name == "MultiPV") {
+        multipv_option.value = DEFAULT_MULTIPV.to_string();
+    } else {
+        options.push(EngineOption {
+            name: "MultiPV".to_string(),
+            value: DEFAULT_MULTIPV.to_string(),
+        });
+    }
+}
+
+/// Analyses a position, restarting the engine if it dies on it.
+/// `source` is the app, engine and analysis id that status events go to.
+async fn analyze_with_retries(
+    proc: &mut EngineProcess,
+    reader: &mut EngineReader,
+    options: EngineOptions,
+    go_mode: &GoMode,
+    restarts: &mut u32,
+    (app, engine, id): (&tauri::AppHandle, &str, &str),
+) -> Result<Vec<BestMoves>, Error> {
+    let mut attempt = 0;
+    loop {
+        match analyze_position(proc, reader, options.clone(), go_mode).await {
+            Err(Error::EngineExited | Error::Io(_)) if attempt < POSITION_RETRIES => {
+                attempt += 1;
+                warn!(
+                    "Engine crashed {} moves after {}, retry {}/{}",
+                    options.moves.len(),
+                    options.fen,
+                    attempt,
+                    POSITION_RETRIES
+                );
+                let status = proc.crash_status().await;
+                emit_engine_status(app, engine, id, status);
+                *reader = proc.respawn().await?;
+                *restarts += 1;
+                emit_engine_status(app, engine, id, EngineStatus::Restarted { attempt: *restarts });
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Runs one search of game analysis, from setting up the position to `bestmove`.
+async fn analyze_position(
+    proc: &mut EngineProcess,
+    reader: &mut EngineReader,
+    options: EngineOptions,
+    go_mode: &GoMode,
+) -> Result<Vec<BestMoves>, Error> {
+    proc.set_options(options).await?;
+    proc.go(go_mode).await?;
+    analyze_single_position(proc, reader).await
+}
+
+/// Reads the search to its `bestmove`, returning the last complete set of
+/// lines rather than the partial one the engine may be halfway through.
+async fn analyze_single_position(
+    proc: &mut EngineProcess,
+    reader: &mut EngineReader,
+) -> Result<Vec<BestMoves>, Error> {
+    Ok(proc.wait_for_best_move(reader).await?.lines)
+}
+
+// Engine configuration
+#[derive(Type, Default, Serialize, Debug)]
+pub struct EngineConfig {
+    pub name: String,
+    pub options: Vec<UciOptionConfig>,
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_engine_config(
+    path: PathBuf,
+    launch: Option<EngineLaunch>,
+) -> Result<EngineConfig, Error> {
+    info!("Getting engine configuration from: {:?}", path);
+    
+    let launch = launch.unwrap_or_default();
+    let source = EngineSource::parse(&path.to_string_lossy(), &launch)?;
+    let EngineConnection {
+        writer: mut stdin,
+        reader: mut stdout,
+        child,
+        logs,
+    } = transport::connect(&source).await?;
+
+    let mut config = EngineConfig::default();
+    
+    // Run the protocol handshake with timeout
+    let probe = launch.uci_probe_timeout();
+    match timeout(ENGINE_INIT_TIMEOUT + probe, EngineProcess::initialize(&mut stdin, &mut stdout, &logs, probe)).await {
+        Ok(Ok((_, engine_config))) => {
+            config = engine_config;
+            info!("Successfully retrieved engine config: name={}, options={}", 
+                  config.name, config.options.len());
+        }
+        Ok(Err(e)) => {
+            warn!("Failed to get engine config: {}", e);
+        }
+        Err(_) => {
+            warn!("Timeout getting engine config from: {:?}", path);
+        }
+    }
+    
+    // Ensure child process is terminated; remote connections close on drop
+    if let Some(mut child) = child {
+        let _ = child.kill().await;
+    }
+    
+    // Fallback name if not provided
+    if config.name.is_empty() {
+        config.name = path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Unknown Engine")
+            .to_string();
+    }
+    
+    debug!("Engine config result: name='{}', options={}", config.name, config.options.len());
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        net::TcpListener,
+    };
+
+    fn info(line: &str) -> Vec<UciInfoAttribute> {
+        match parse_one(line) {
+            UciMessage::Info(attrs) => attrs,
+            other => panic!("not an info line: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_full_info_line() {
+        let fen: Fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+            .parse()
+            .unwrap();
+        let best = parse_uci_info(
+            info("info depth 20 seldepth 31 multipv 1 score cp 35 lowerbound nodes 5000000000 nps 1200000 hashfull 512 tbhits 7 time 4166 pv e7e5 g1f3"),
+            &fen,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(best.depth, 20);
+        assert_eq!(best.seldepth, 31);
+        assert_eq!(best.nodes, 5_000_000_000);
+        assert_eq!(best.nps, 1_200_000);
+        assert_eq!(best.hashfull, 512);
+        assert_eq!(best.tbhits, 7);
+        assert_eq!(best.time, 4166);
+        assert_eq!(best.san_moves, vec!["e5", "Nf3"]);
+        // black's lower bound is white's upper bound
+        assert!(matches!(best.score.value, ScoreValue::Cp(-35)));
+        assert!(best.upper_bound && !best.lower_bound);
+    }
+
+    #[test]
+    fn parses_current_move() {
+        let current = parse_current_move(&info("info depth 12 currmove e2e4 currmovenumber 3"));
+        assert_eq!(
+            current,
+            Some(CurrentMove {
+                uci_move: "e2e4".to_string(),
+                number: 3,
+            })
+        );
+        assert!(parse_current_move(&info("info depth 12 score cp 10 pv e2e4")).is_none());
+    }
+
+    #[test]
+    fn excluded_moves_become_searchmoves() {
+        // Only the king can move: Kb1, Kb2 and Ka2
+        let options = EngineOptions {
+            fen: "k7/8/8/8/8/8/8/K7 w - - 0 1".to_string(),
+            exclude_moves: vec!["a1b2".to_string()],
+            ..Default::default()
+        };
+        let pos = position_after(&options.fen, &options.moves).unwrap();
+        let root_moves = options.root_moves(&pos).unwrap().unwrap();
+        assert_eq!(root_moves.len(), 2);
+        assert!(!root_moves.contains(&"a1b2".to_string()));
+        assert_eq!(
+            EngineProcess::format_go_command(&GoMode::Depth(20), Some(&root_moves), false),
+            format!("go depth 20 searchmoves {}\n", root_moves.join(" "))
+        );
+
+        let unrestricted = EngineOptions {
+            exclude_moves: vec![],
+            ..options
+        };
+        assert!(unrestricted.root_moves(&pos).unwrap().is_none());
+    }
+
+    /// Answers every `go` with two complete MultiPV sets and the start of a
+    /// third before `bestmove`.
+    async fn serve_multipv_engine(listener: TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let reply = match line.as_str() {
+                "uci" => "id name Fake\noption name MultiPV type spin default 1 min 1 max 500\nuciok\n",
+                "isready" => "readyok\n",
+                "quit" => break,
+                go if go.starts_with("go ") => concat!(
+                    "info depth 1 multipv 1 score cp 20 nodes 10 nps 100 pv e2e4\n",
+                    "info depth 1 multipv 2 score cp 10 nodes 10 nps 100 pv d2d4\n",
+                    "info depth 2 multipv 1 score cp 30 nodes 20 nps 100 pv e2e4 e7e5\n",
+                    "info depth 2 multipv 2 score cp 15 nodes 20 nps 100 pv d2d4 d7d5\n",
+                    "info depth 3 multipv 1 score cp 25 nodes 30 nps 100 pv e2e4 e7e5\n",
+                    "bestmove e2e4\n",
+                ),
+                _ => continue,
+            };
+            write.write_all(reply.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn game_analysis_returns_the_last_complete_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve_multipv_engine(listener));
+
+        let (mut proc, mut reader) = EngineProcess::new(
+            PathBuf::from(format!("tcp://{address}")),
+            &EngineLaunch::default(),
+        )
+        .await
+        .unwrap();
+        let options = EngineOptions {
+            fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+            extra_options: vec![EngineOption {
+                name: "MultiPV".to_string(),
+                value: "2".to_string(),
+            }],
+            ..Default::default()
+        };
+        let lines = analyze_position(&mut proc, &mut reader, options, &GoMode::Depth(3))
+            .await
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.depth == 2));
+        assert_eq!(lines[0].uci_moves, vec!["e2e4", "e7e5"]);
+        proc.kill().await.unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, san::SanPlus, uci::UciMove, CastlingMode, Chess, Position};
+use specta::Type;
+
+use super::play::PlayerColor;
+use crate::{
+    db::{is_position_in_db, position_reference, GameQueryJs, PositionQueryJs, PositionReference},
+    error::Error,
+    AppState,
+};
+
+/// The move that left the reference database, with what had been played in
+/// the last position it knows.
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Novelty {
+    pub move_number: u32,
+    pub color: PlayerColor,
+    pub san: String,
+    pub reference: PositionReference,
+}
+
+fn move_label(move_number: u32, color: PlayerColor, san: &str) -> String {
+    match color {
+        PlayerColor::White => format!("{move_number}.{san}"),
+        PlayerColor::Black => format!("{move_number}...{san}"),
+    }
+}
+
+impl Novelty {
+    /// Like "N: 14.Nd5 — previously 14.Be2 (37 games)", with the most
+    /// played move and the number of games it was played in.
+    pub fn comment(&self) -> String {
+        let novelty = move_label(self.move_number, self.color, &self.san);
+        match self.reference.continuations.first() {
+            Some(stats) => {
+                let games = stats.white + stats.draw + stats.black;
+                format!(
+                    "N: {novelty} — previously {} ({games} {})",
+                    move_label(self.move_number, self.color, &stats.move_),
+                    if games == 1 { "game" } else { "games" }
+                )
+            }
+            None => format!("N: {novelty}"),
+        }
+    }
+}
+
+async fn in_reference(
+    fen: &Fen,
+    reference_db: &PathBuf,
+    state: &tauri::State<'_, AppState>,
+) -> Result<bool, Error> {
+    let query = PositionQueryJs {
+        fen: fen.to_string(),
+        type_: "exact".to_string(),
+    };
+    is_position_in_db(
+        reference_db.clone(),
+        GameQueryJs::new().position(query),
+        state.clone(),
+    )
+    .await
+}
+
+/// Finds the first position of a game, in the order it was played, that
+/// the reference database doesn't have, and the novelty that led to it.
+/// `positions` pairs every position with the moves that reached it, and the
+/// index returned is that of the position after the novelty.
+pub(super) async fn find_novelty(
+    positions: &[(Fen, Vec<String>, bool)],
+    reference_db: &Option<PathBuf>,
+    state: &tauri::State<'_, AppState>,
+) -> Result<Option<(usize, Novelty)>, Error> {
+    let Some(reference_db) = reference_db else {
+        return Err(Error::MissingReferenceDatabase);
+    };
+
+    for (i, (fen, moves, _)) in positions.iter().enumerate() {
+        if in_reference(fen, reference_db, state).await? {
+            continue;
+        }
+        // Out of book from the start, so no move was new
+        let (Some(played), Some(book_index)) = (moves.last(), i.checked_sub(1)) else {
+            return Ok(None);
+        };
+        let (book_fen, _, _) = &positions[book_index];
+
+        let book: Chess = book_fen.clone().into_position(CastlingMode::Chess960)?;
+        let mv = UciMove::from_ascii(played.as_bytes())?.to_move(&book)?;
+        let novelty = Novelty {
+            move_number: book.fullmoves().get(),
+            color: book.turn().into(),
+            san: SanPlus::from_move(book.clone(), &mv).to_string(),
+            reference: position_reference(reference_db.clone(), &book_fen.to_string(), state)
+                .await?,
+        };
+        return Ok(Some((i, novelty)));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::PositionStats;
+
+    fn novelty(color: PlayerColor, continuations: Vec<PositionStats>) -> Novelty {
+        Novelty {
+            move_number: 14,
+            color,
+            san: "Nd5".to_string(),
+            reference: PositionReference {
+                games: 40,
+                continuations,
+                top_game: None,
+            },
+        }
+    }
+
+    #[test]
+    fn comments_name_the_previous_move() {
+        let stats = PositionStats {
+            move_: "Be2".to_string(),
+            white: 15,
+            draw: 12,
+            black: 10,
+        };
+        assert_eq!(
+            novelty(PlayerColor::White, vec![stats]).comment(),
+            "N: 14.Nd5 — previously 14.Be2 (37 games)"
+        );
+        assert_eq!(novelty(PlayerColor::Black, vec![]).comment(), "N: 14...Nd5");
+    }
+}
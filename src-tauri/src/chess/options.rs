@@ -0,0 +1,256 @@
+use std::{fmt, path::PathBuf};
+
+use log::{debug, warn};
+use serde::Serialize;
+use specta::Type;
+use vampirc_uci::UciOptionConfig;
+
+use super::{EngineConfig, EngineLaunch, EngineOption, EngineProcess, EngineProtocol};
+use crate::error::Error;
+
+/// Options a GUI may send without the engine advertising them. The engine
+/// simply searches a single line without MultiPV, so it is skipped instead.
+const OPTIONAL_OPTIONS: &[&str] = &["MultiPV"];
+
+/// An option value rejected before being sent to the engine.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidOption {
+    pub name: String,
+    pub value: String,
+    pub reason: InvalidReason,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(tag = "t", content = "c")]
+pub enum InvalidReason {
+    /// The engine does not advertise the option.
+    Unknown,
+    NotBoolean,
+    NotInteger,
+    OutOfRange { min: Option<i64>, max: Option<i64> },
+    NotInCombo(Vec<String>),
+}
+
+impl fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={} ", self.name, self.value)?;
+        match &self.reason {
+            InvalidReason::Unknown => write!(f, "(unknown option)"),
+            InvalidReason::NotBoolean => write!(f, "(expected true or false)"),
+            InvalidReason::NotInteger => write!(f, "(expected an integer)"),
+            InvalidReason::OutOfRange { min, max } => {
+                let bound = |b: &Option<i64>| b.map_or("?".to_string(), |b| b.to_string());
+                write!(f, "(must be between {} and {})", bound(min), bound(max))
+            }
+            InvalidReason::NotInCombo(vars) => write!(f, "(must be one of {})", vars.join(", ")),
+        }
+    }
+}
+
+pub(super) fn option_name(option: &UciOptionConfig) -> &str {
+    match option {
+        UciOptionConfig::Check { name, .. }
+        | UciOptionConfig::Spin { name, .. }
+        | UciOptionConfig::Combo { name, .. }
+        | UciOptionConfig::Button { name }
+        | UciOptionConfig::String { name, .. } => name,
+    }
+}
+
+/// Looks up an advertised option; UCI option names are case insensitive.
+pub(super) fn find_option<'a>(config: &'a EngineConfig, name: &str) -> Option<&'a UciOptionConfig> {
+    config
+        .options
+        .iter()
+        .find(|opt| option_name(opt).eq_ignore_ascii_case(name))
+}
+
+/// Whether an option is dropped instead of sent, because it is optional and
+/// the engine does not know it.
+pub(super) fn is_skipped(config: &EngineConfig, name: &str) -> bool {
+    let skipped = OPTIONAL_OPTIONS.contains(&name) && find_option(config, name).is_none();
+    if skipped {
+        debug!("Engine {} has no {} option, skipping it", config.name, name);
+    }
+    skipped
+}
+
+fn validate_option(config: &EngineConfig, option: &EngineOption) -> Result<(), InvalidReason> {
+    let Some(advertised) = find_option(config, &option.name) else {
+        return Err(InvalidReason::Unknown);
+    };
+    let value = option.value.trim();
+    match advertised {
+        UciOptionConfig::Check { .. } => {
+            if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+                Ok(())
+            } else {
+                Err(InvalidReason::NotBoolean)
+            }
+        }
+        UciOptionConfig::Spin { min, max, .. } => {
+            let value: i64 = value.parse().map_err(|_| InvalidReason::NotInteger)?;
+            if min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max) {
+                return Err(InvalidReason::OutOfRange {
+                    min: *min,
+                    max: *max,
+                });
+            }
+            Ok(())
+        }
+        UciOptionConfig::Combo { var, .. } => {
+            if var.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+                Ok(())
+            } else {
+                Err(InvalidReason::NotInCombo(var.clone()))
+            }
+        }
+        UciOptionConfig::Button { .. } | UciOptionConfig::String { .. } => Ok(()),
+    }
+}
+
+/// The options that do not match what the engine advertised.
+fn invalid_options(config: &EngineConfig, options: &[EngineOption]) -> Vec<InvalidOption> {
+    options
+        .iter()
+        .filter(|option| !is_skipped(config, &option.name))
+        .filter_map(|option| {
+            validate_option(config, option).err().map(|reason| InvalidOption {
+                name: option.name.clone(),
+                value: option.value.clone(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Checks every option against what the engine advertised, reporting all
+/// invalid ones at once.
+pub(super) fn validate_options(config: &EngineConfig, options: &[EngineOption]) -> Result<(), Error> {
+    let invalid = invalid_options(config, options);
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidEngineOptions(invalid))
+    }
+}
+
+/// Drops the options the engine does not advertise, as engines ignore them
+/// anyway, and rejects advertised options with invalid values. Presets and
+/// settings are checked strictly with [`validate_options`] instead.
+pub(super) fn retain_known_options(
+    config: &EngineConfig,
+    options: &mut Vec<EngineOption>,
+) -> Result<(), Error> {
+    options.retain(|option| {
+        let known = OPTIONAL_OPTIONS.contains(&option.name.as_str())
+            || find_option(config, &option.name).is_some();
+        if !known {
+            warn!("Engine {} has no {} option, ignoring it", config.name, option.name);
+        }
+        known
+    });
+    validate_options(config, options)
+}
+
+/// Starts the engine and lists the options it would reject, so they can be
+/// shown next to the settings instead of as one error message.
+#[tauri::command]
+#[specta::specta]
+pub async fn check_engine_options(
+    path: PathBuf,
+    options: Vec<EngineOption>,
+    launch: Option<EngineLaunch>,
+) -> Result<Vec<InvalidOption>, Error> {
+    let (mut process, _reader) = EngineProcess::new(path, &launch.unwrap_or_default()).await?;
+    let invalid = match process.protocol {
+        EngineProtocol::Uci => invalid_options(&process.config, &options),
+        EngineProtocol::Xboard(_) => Vec::new(),
+    };
+    process.kill().await?;
+    Ok(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(name: &str, value: &str) -> EngineOption {
+        EngineOption {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn config() -> EngineConfig {
+        EngineConfig {
+            name: "Test".to_string(),
+            options: vec![
+                UciOptionConfig::Spin {
+                    name: "Hash".to_string(),
+                    default: Some(16),
+                    min: Some(1),
+                    max: Some(1024),
+                },
+                UciOptionConfig::Check {
+                    name: "Ponder".to_string(),
+                    default: Some(false),
+                },
+                UciOptionConfig::Combo {
+                    name: "Style".to_string(),
+                    default: Some("Normal".to_string()),
+                    var: vec!["Solid".to_string(), "Normal".to_string()],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn accepts_advertised_values() {
+        let options = [
+            option("hash", "256"),
+            option("Ponder", "TRUE"),
+            option("Style", "solid"),
+            option("MultiPV", "3"),
+        ];
+        assert!(validate_options(&config(), &options).is_ok());
+        assert!(is_skipped(&config(), "MultiPV"));
+    }
+
+    #[test]
+    fn lists_every_invalid_option() {
+        let options = [
+            option("Hash", "4096"),
+            option("Ponder", "yes"),
+            option("Style", "Wild"),
+            option("Threads", "4"),
+        ];
+        let Err(Error::InvalidEngineOptions(invalid)) = validate_options(&config(), &options) else {
+            panic!("options should be rejected");
+        };
+        let reasons: Vec<_> = invalid.into_iter().map(|o| o.reason).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                InvalidReason::OutOfRange {
+                    min: Some(1),
+                    max: Some(1024)
+                },
+                InvalidReason::NotBoolean,
+                InvalidReason::NotInCombo(vec!["Solid".to_string(), "Normal".to_string()]),
+                InvalidReason::Unknown,
+            ]
+        );
+    }
+
+    #[test]
+    fn searches_drop_unknown_options() {
+        let mut options = vec![option("UCI_Chess960", "true"), option("Hash", "64")];
+        assert!(retain_known_options(&config(), &mut options).is_ok());
+        assert_eq!(options, vec![option("Hash", "64")]);
+
+        let mut options = vec![option("UCI_Chess960", "true"), option("Hash", "0")];
+        assert!(retain_known_options(&config(), &mut options).is_err());
+    }
+}
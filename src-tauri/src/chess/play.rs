@@ -0,0 +1,588 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, uci::UciMove, ByColor, Chess, Color, EnPassantMode, Position};
+use specta::Type;
+use tokio::{sync::Mutex, time::timeout};
+use vampirc_uci::UciOptionConfig;
+
+use super::{
+    engine_match::{
+        append_move, outcome_of, position_key, rules_outcome, start_position, MatchEngine,
+        MatchTimeControl, Termination,
+    },
+    options::find_option,
+    transport::EngineReader,
+    EngineConfig, EngineOption, EngineOptions, EngineProcess, GoMode, PlayersTime,
+};
+use crate::{
+    db::{import_pgn_game, Outcome, PgnGame},
+    error::Error,
+    AppState,
+};
+
+/// Extra time granted on top of the engine's clock before it forfeits.
+const CLOCK_GRACE: Duration = Duration::from_secs(5);
+
+/// Rough Elo range covered by the `Skill Level` option of Stockfish-like
+/// engines, used when an engine has no `UCI_Elo`.
+const SKILL_LEVEL_ELO_RANGE: (u32, u32) = (1350, 2850);
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaySettings {
+    pub fen: String,
+    /// Color played by the engine.
+    pub engine_color: PlayerColor,
+    pub time_control: MatchTimeControl,
+    /// Target strength, applied through `UCI_LimitStrength`/`UCI_Elo`, or
+    /// approximated with `Skill Level` when that is all the engine offers.
+    #[specta(optional)]
+    pub elo: Option<u32>,
+    /// Explicit `Skill Level`, used when no Elo is requested.
+    #[specta(optional)]
+    pub skill_level: Option<i64>,
+    /// Let the engine think on the user's time.
+    #[serde(default)]
+    pub ponder: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum PlayerColor {
+    White,
+    Black,
+}
+
+impl From<PlayerColor> for Color {
+    fn from(color: PlayerColor) -> Self {
+        match color {
+            PlayerColor::White => Color::White,
+            PlayerColor::Black => Color::Black,
+        }
+    }
+}
+
+impl From<Color> for PlayerColor {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::White => PlayerColor::White,
+            Color::Black => PlayerColor::Black,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayState {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    /// Remaining time in milliseconds.
+    pub white_clock: u32,
+    pub black_clock: u32,
+    pub result: Outcome,
+    pub termination: Option<Termination>,
+    /// The engine's reply to the last user move, if it made one.
+    pub engine_move: Option<String>,
+}
+
+/// A game in progress between the user and an engine.
+pub struct PlaySession {
+    process: EngineProcess,
+    reader: EngineReader,
+    extra_options: Vec<EngineOption>,
+    engine_name: String,
+    engine_color: Color,
+    time_control: MatchTimeControl,
+    ponder: bool,
+    fen: String,
+    moves: Vec<String>,
+    /// Positions before each move, plus the current one at the end.
+    positions: Vec<Chess>,
+    /// Clocks before each move, parallel to `positions`.
+    clocks: Vec<ByColor<i64>>,
+    turn_started: Instant,
+    result: Option<(Option<Color>, Termination)>,
+}
+
+impl PlaySession {
+    fn position(&self) -> &Chess {
+        self.positions.last().expect("session always has a position")
+    }
+
+    fn clock(&self) -> ByColor<i64> {
+        *self.clocks.last().expect("session always has a clock")
+    }
+
+    fn state(&self, id: &str, engine_move: Option<String>) -> PlayState {
+        let clock = self.clock();
+        PlayState {
+            id: id.to_string(),
+            fen: Fen::from_position(self.position().clone(), EnPassantMode::Legal).to_string(),
+            moves: self.moves.clone(),
+            white_clock: clock.white.max(0) as u32,
+            black_clock: clock.black.max(0) as u32,
+            result: self
+                .result
+                .map_or(Outcome::Unknown, |(winner, t)| outcome_of(winner, t)),
+            termination: self.result.map(|(_, t)| t),
+            engine_move,
+        }
+    }
+
+    fn check_result(&mut self) {
+        let mut repetitions: HashMap<String, u32> = HashMap::new();
+        for pos in &self.positions {
+            *repetitions.entry(position_key(pos)).or_default() += 1;
+        }
+        if let Some(result) = rules_outcome(self.position(), &repetitions) {
+            self.result = Some(result);
+        }
+    }
+
+    /// Charges the time since the turn started to the side to move and plays
+    /// the move, returning `false` if that side ran out of time first.
+    fn apply_move(&mut self, uci: &str, elapsed: Duration) -> Result<bool, Error> {
+        let mut pos = self.position().clone();
+        let turn = pos.turn();
+        let mv = UciMove::from_ascii(uci.as_bytes())?.to_move(&pos)?;
+
+        let mut clock = self.clock();
+        let remaining = clock.get_mut(turn);
+        *remaining -= elapsed.as_millis() as i64;
+        if *remaining < 0 {
+            self.result = Some((Some(turn.other()), Termination::TimeForfeit));
+            return Ok(false);
+        }
+        *remaining += self.time_control.increment as i64;
+
+        pos.play_unchecked(&mv);
+        self.positions.push(pos);
+        self.clocks.push(clock);
+        self.moves.push(uci.to_string());
+        self.turn_started = Instant::now();
+        self.check_result();
+        Ok(true)
+    }
+
+    fn go_mode(&self) -> GoMode {
+        let clock = self.clock();
+        GoMode::PlayersTime(PlayersTime {
+            white: clock.white.max(0) as u32,
+            black: clock.black.max(0) as u32,
+            winc: self.time_control.increment,
+            binc: self.time_control.increment,
+        })
+    }
+
+    async fn engine_move(&mut self) -> Result<Option<String>, Error> {
+        let clock = self.clock();
+        let ponder_hit = self
+            .process
+            .resolve_ponder(&self.fen, &self.moves, &mut self.reader)
+            .await?;
+        if !ponder_hit {
+            self.process
+                .set_options(EngineOptions {
+                    fen: self.fen.clone(),
+                    moves: self.moves.clone(),
+                    extra_options: self.extra_options.clone(),
+                    ..Default::default()
+                })
+                .await?;
+            let mode = self.go_mode();
+            self.process.go_on_clock(&mode).await?;
+        }
+
+        let deadline =
+            Duration::from_millis(clock.get(self.engine_color).max(0) as u64) + CLOCK_GRACE;
+        let search = match timeout(
+            deadline,
+            self.process.wait_for_best_move(&mut self.reader),
+        )
+        .await
+        {
+            Ok(search) => search?,
+            Err(_) => {
+                self.result = Some((Some(self.engine_color.other()), Termination::TimeForfeit));
+                return Ok(None);
+            }
+        };
+
+        let elapsed = self.turn_started.elapsed();
+        match self.apply_move(&search.best_move, elapsed) {
+            Ok(true) => {
+                if let Some(ponder_move) = search.ponder {
+                    self.start_pondering(&ponder_move).await;
+                }
+                Ok(Some(search.best_move))
+            }
+            Ok(false) => Ok(None),
+            Err(e) => {
+                warn!(
+                    "Engine {} played illegal move {}: {}",
+                    self.engine_name, search.best_move, e
+                );
+                self.result = Some((Some(self.engine_color.other()), Termination::IllegalMove));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Thinks on the user's time, assuming they reply with `ponder_move`.
+    /// Pondering is only an optimization, so failures are just logged.
+    async fn start_pondering(&mut self, ponder_move: &str) {
+        if !self.ponder || self.result.is_some() {
+            return;
+        }
+        let mode = self.go_mode();
+        if let Err(e) = self.process.ponder(&mode, ponder_move).await {
+            warn!(
+                "Engine {} failed to ponder on {}: {}",
+                self.engine_name, ponder_move, e
+            );
+        }
+    }
+
+    fn pgn(&self, white: String, black: String) -> Result<String, Error> {
+        let mut pos = start_position(&self.fen)?;
+        let mut movetext = String::new();
+        for m in &self.moves {
+            let mv = UciMove::from_ascii(m.as_bytes())?.to_move(&pos)?;
+            append_move(&mut movetext, &mut pos, &mv, None);
+        }
+        if let Some((winner, termination)) = self.result {
+            movetext.push_str(&format!("{{{}}} ", termination.describe(winner)));
+        }
+
+        let start_fen =
+            Fen::from_position(self.positions[0].clone(), EnPassantMode::Legal).to_string();
+        let result = self
+            .result
+            .map_or(Outcome::Unknown, |(winner, t)| outcome_of(winner, t));
+        let pgn = PgnGame {
+            event: Some("Engine game".to_string()),
+            site: Some("?".to_string()),
+            date: Some(chrono::Local::now().format("%Y.%m.%d").to_string()),
+            round: Some("-".to_string()),
+            white: Some(white),
+            black: Some(black),
+            result: Some(result.to_string()),
+            time_control: Some(self.time_control.to_pgn()),
+            eco: None,
+            white_elo: None,
+            black_elo: None,
+            ply_count: Some(self.moves.len().to_string()),
+            fen: (start_fen != Fen::default().to_string()).then_some(start_fen),
+            moves: movetext,
+        };
+
+        let mut buffer = Vec::new();
+        pgn.write(&mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+fn spin_range(config: &EngineConfig, option: &str) -> Option<(i64, i64)> {
+    match find_option(config, option)? {
+        UciOptionConfig::Spin { min, max, .. } => {
+            Some((min.unwrap_or(i64::MIN), max.unwrap_or(i64::MAX)))
+        }
+        _ => None,
+    }
+}
+
+/// Builds the options that limit the engine's strength, using only the
+/// options the engine actually advertises.
+fn strength_options(config: &EngineConfig, settings: &PlaySettings) -> Vec<EngineOption> {
+    let option = |name: &str, value: String| EngineOption {
+        name: name.to_string(),
+        value,
+    };
+
+    if let Some(elo) = settings.elo {
+        if let (true, Some((min, max))) = (
+            find_option(config, "UCI_LimitStrength").is_some(),
+            spin_range(config, "UCI_Elo"),
+        ) {
+            return vec![
+                option("UCI_LimitStrength", "true".to_string()),
+                option("UCI_Elo", (elo as i64).clamp(min, max).to_string()),
+            ];
+        }
+        if let Some((min, max)) = spin_range(config, "Skill Level") {
+            let (low, high) = SKILL_LEVEL_ELO_RANGE;
+            let fraction = (elo.clamp(low, high) - low) as f64 / (high - low) as f64;
+            let level = min + (fraction * (max - min) as f64).round() as i64;
+            return vec![option("Skill Level", level.to_string())];
+        }
+        warn!("Engine {} cannot limit its strength", config.name);
+    } else if let Some(level) = settings.skill_level {
+        if let Some((min, max)) = spin_range(config, "Skill Level") {
+            return vec![option("Skill Level", level.clamp(min, max).to_string())];
+        }
+    }
+
+    vec![]
+}
+
+fn get_session(
+    state: &tauri::State<'_, AppState>,
+    id: &str,
+) -> Result<Arc<Mutex<PlaySession>>, Error> {
+    state
+        .play_sessions
+        .get(id)
+        .map(|session| session.clone())
+        .ok_or_else(|| Error::PlaySessionNotFound(id.to_string()))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn start_play_session(
+    id: String,
+    engine: MatchEngine,
+    settings: PlaySettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<PlayState, Error> {
+    info!("Starting play session {} against {}", id, engine.name);
+
+    let start = start_position(&settings.fen)?;
+    let (mut process, reader) = engine.spawn().await?;
+    if let Err(e) = process.new_game().await {
+        if let Err(e) = process.kill().await {
+            warn!("Failed to quit engine for play session {}: {}", id, e);
+        }
+        return Err(e);
+    }
+
+    let mut extra_options: Vec<EngineOption> = engine
+        .options
+        .into_iter()
+        .filter(|o| !matches!(o.name.as_str(), "UCI_LimitStrength" | "UCI_Elo" | "Skill Level"))
+        .collect();
+    extra_options.extend(strength_options(&process.config, &settings));
+    let ponder = settings.ponder && process.can_ponder();
+    if ponder {
+        extra_options.extend(process.ponder_option());
+    }
+
+    let initial = settings.time_control.initial as i64;
+    let mut session = PlaySession {
+        process,
+        reader,
+        extra_options,
+        engine_name: engine.name,
+        engine_color: settings.engine_color.into(),
+        time_control: settings.time_control,
+        ponder,
+        fen: settings.fen,
+        moves: Vec::new(),
+        positions: vec![start],
+        clocks: vec![ByColor {
+            white: initial,
+            black: initial,
+        }],
+        turn_started: Instant::now(),
+        result: None,
+    };
+    session.check_result();
+
+    let engine_to_move = session.position().turn() == session.engine_color;
+    let engine_move = if session.result.is_none() && engine_to_move {
+        match session.engine_move().await {
+            Ok(engine_move) => engine_move,
+            Err(e) => {
+                if let Err(e) = session.process.kill().await {
+                    warn!("Failed to quit engine for play session {}: {}", id, e);
+                }
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+
+    let play_state = session.state(&id, engine_move);
+    state
+        .play_sessions
+        .insert(id, Arc::new(Mutex::new(session)));
+    Ok(play_state)
+}
+
+/// Plays the user's move and, unless the game ended, waits for the engine's reply.
+#[tauri::command]
+#[specta::specta]
+pub async fn play_session_move(
+    id: String,
+    uci_move: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<PlayState, Error> {
+    let session = get_session(&state, &id)?;
+    let mut session = session.lock().await;
+
+    if session.result.is_some() || session.position().turn() == session.engine_color {
+        return Ok(session.state(&id, None));
+    }
+
+    let elapsed = session.turn_started.elapsed();
+    if !session.apply_move(&uci_move, elapsed)? || session.result.is_some() {
+        return Ok(session.state(&id, None));
+    }
+
+    let engine_move = session.engine_move().await?;
+    Ok(session.state(&id, engine_move))
+}
+
+/// Takes back the user's last move together with the engine's reply. When
+/// the engine moved first and there is no user move to go back to, the
+/// engine plays its first move again.
+#[tauri::command]
+#[specta::specta]
+pub async fn takeback_play_session(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<PlayState, Error> {
+    let session = get_session(&state, &id)?;
+    let mut session = session.lock().await;
+
+    let user_color = session.engine_color.other();
+    while !session.moves.is_empty() {
+        session.moves.pop();
+        session.positions.pop();
+        session.clocks.pop();
+        if session.position().turn() == user_color {
+            break;
+        }
+    }
+    session.result = None;
+    session.turn_started = Instant::now();
+
+    let engine_move = if session.position().turn() == session.engine_color {
+        session.engine_move().await?
+    } else {
+        None
+    };
+    Ok(session.state(&id, engine_move))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn resign_play_session(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<PlayState, Error> {
+    let session = get_session(&state, &id)?;
+    let mut session = session.lock().await;
+
+    if session.result.is_none() {
+        session.result = Some((Some(session.engine_color), Termination::Resignation));
+    }
+    Ok(session.state(&id, None))
+}
+
+/// Saves the session's game into a database and returns its PGN.
+#[tauri::command]
+#[specta::specta]
+pub async fn save_play_session(
+    id: String,
+    file: PathBuf,
+    white: String,
+    black: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, Error> {
+    let session = get_session(&state, &id)?;
+    let session = session.lock().await;
+
+    let pgn = session.pgn(white, black)?;
+    import_pgn_game(&state, &file, &pgn)?;
+    Ok(pgn)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn close_play_session(id: String, state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    if let Some((_, session)) = state.play_sessions.remove(&id) {
+        let mut session = session.lock().await;
+        if let Err(e) = session.process.kill().await {
+            warn!("Failed to quit engine for play session {}: {}", id, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(elo: Option<u32>, skill_level: Option<i64>) -> PlaySettings {
+        PlaySettings {
+            fen: Fen::default().to_string(),
+            engine_color: PlayerColor::Black,
+            time_control: MatchTimeControl {
+                initial: 60_000,
+                increment: 0,
+            },
+            elo,
+            skill_level,
+            ponder: false,
+        }
+    }
+
+    fn spin(name: &str, min: i64, max: i64) -> UciOptionConfig {
+        UciOptionConfig::Spin {
+            name: name.to_string(),
+            default: Some(min),
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+
+    #[test]
+    fn prefers_uci_elo() {
+        let config = EngineConfig {
+            name: "Test".to_string(),
+            options: vec![
+                UciOptionConfig::Check {
+                    name: "UCI_LimitStrength".to_string(),
+                    default: Some(false),
+                },
+                spin("UCI_Elo", 1320, 3190),
+                spin("Skill Level", 0, 20),
+            ],
+        };
+
+        let options = strength_options(&config, &settings(Some(1000), None));
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[1].name, "UCI_Elo");
+        assert_eq!(options[1].value, "1320");
+    }
+
+    #[test]
+    fn falls_back_to_skill_level() {
+        let config = EngineConfig {
+            name: "Test".to_string(),
+            options: vec![spin("Skill Level", 0, 20)],
+        };
+
+        let options = strength_options(&config, &settings(Some(2100), None));
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0].value, "10");
+
+        let options = strength_options(&config, &settings(None, Some(30)));
+        assert_eq!(options[0].value, "20");
+    }
+
+    #[test]
+    fn ignores_unadvertised_options() {
+        let config = EngineConfig::default();
+        assert!(strength_options(&config, &settings(Some(1500), Some(5))).is_empty());
+    }
+}
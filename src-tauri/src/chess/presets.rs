@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{path::BaseDirectory, Manager};
+use tokio::sync::Mutex;
+
+use super::{options, EngineLaunch, EngineOption, EngineProcess, EngineProtocol};
+use crate::error::Error;
+
+const PRESETS_FILE: &str = "presets/engine-presets.json";
+
+/// Serializes read-modify-write cycles on the presets file.
+static PRESETS_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// A named set of options for one engine, such as "Long analysis" with a
+/// bigger hash and more threads.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EnginePreset {
+    /// The engine path, as passed to the analysis commands.
+    pub engine: String,
+    pub name: String,
+    pub options: Vec<EngineOption>,
+}
+
+/// Presets by engine path, then by name.
+type Presets = BTreeMap<String, BTreeMap<String, Vec<EngineOption>>>;
+
+struct PresetStore {
+    path: PathBuf,
+}
+
+impl PresetStore {
+    fn open(app: &tauri::AppHandle) -> Result<Self, Error> {
+        Ok(Self {
+            path: app.path().resolve(PRESETS_FILE, BaseDirectory::AppData)?,
+        })
+    }
+
+    fn read(&self) -> Result<Presets, Error> {
+        if !self.path.exists() {
+            return Ok(Presets::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+
+    /// Writes through a temporary file so a crash never leaves a truncated file.
+    fn write(&self, presets: &Presets) -> Result<(), Error> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(presets)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn list(&self, engine: &str) -> Result<Vec<EnginePreset>, Error> {
+        let presets = self.read()?;
+        Ok(presets
+            .get(engine)
+            .into_iter()
+            .flatten()
+            .map(|(name, options)| EnginePreset {
+                engine: engine.to_string(),
+                name: name.clone(),
+                options: options.clone(),
+            })
+            .collect())
+    }
+
+    fn get(&self, engine: &str, name: &str) -> Result<EnginePreset, Error> {
+        let options = self
+            .read()?
+            .get(engine)
+            .and_then(|presets| presets.get(name))
+            .cloned()
+            .ok_or_else(|| Error::PresetNotFound(name.to_string()))?;
+        Ok(EnginePreset {
+            engine: engine.to_string(),
+            name: name.to_string(),
+            options,
+        })
+    }
+
+    fn put(&self, preset: EnginePreset) -> Result<(), Error> {
+        let mut presets = self.read()?;
+        presets
+            .entry(preset.engine)
+            .or_default()
+            .insert(preset.name, preset.options);
+        self.write(&presets)
+    }
+
+    fn delete(&self, engine: &str, name: &str) -> Result<(), Error> {
+        let mut presets = self.read()?;
+        if let Some(engine_presets) = presets.get_mut(engine) {
+            engine_presets.remove(name);
+            if engine_presets.is_empty() {
+                presets.remove(engine);
+            }
+        }
+        self.write(&presets)
+    }
+}
+
+/// Puts the options of a preset under the explicitly given ones, which win
+/// when both set the same option.
+fn merge_preset(preset: EnginePreset, options: &mut Vec<EngineOption>) {
+    let mut merged: Vec<EngineOption> = preset
+        .options
+        .into_iter()
+        .filter(|preset_opt| !options.iter().any(|opt| opt.name == preset_opt.name))
+        .collect();
+    merged.append(options);
+    *options = merged;
+}
+
+/// Resolves `preset` for `engine` into `options`, if a preset was named.
+pub(super) async fn apply_preset(
+    app: &tauri::AppHandle,
+    engine: &str,
+    preset: Option<&str>,
+    options: &mut Vec<EngineOption>,
+) -> Result<(), Error> {
+    let Some(name) = preset else {
+        return Ok(());
+    };
+    let _lock = PRESETS_LOCK.lock().await;
+    merge_preset(PresetStore::open(app)?.get(engine, name)?, options);
+    Ok(())
+}
+
+/// Starts the engine to check the options against the ones it advertises.
+async fn validate_preset(
+    path: &Path,
+    launch: &EngineLaunch,
+    preset: &EnginePreset,
+) -> Result<(), Error> {
+    let (mut process, _reader) = EngineProcess::new(path.to_path_buf(), launch).await?;
+    let result = match process.protocol {
+        EngineProtocol::Uci => options::validate_options(&process.config, &preset.options),
+        EngineProtocol::Xboard(_) => Ok(()),
+    };
+    process.kill().await?;
+    result
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn save_engine_preset(
+    preset: EnginePreset,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+) -> Result<(), Error> {
+    validate_preset(Path::new(&preset.engine), &launch.unwrap_or_default(), &preset).await?;
+
+    info!("Saving preset {} for {}", preset.name, preset.engine);
+    let _lock = PRESETS_LOCK.lock().await;
+    PresetStore::open(&app)?.put(preset)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_engine_presets(
+    engine: String,
+    app: tauri::AppHandle,
+) -> Result<Vec<EnginePreset>, Error> {
+    let _lock = PRESETS_LOCK.lock().await;
+    PresetStore::open(&app)?.list(&engine)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn load_engine_preset(
+    engine: String,
+    name: String,
+    app: tauri::AppHandle,
+) -> Result<EnginePreset, Error> {
+    let _lock = PRESETS_LOCK.lock().await;
+    PresetStore::open(&app)?.get(&engine, &name)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_engine_preset(
+    engine: String,
+    name: String,
+    app: tauri::AppHandle,
+) -> Result<(), Error> {
+    info!("Deleting preset {} for {}", name, engine);
+    let _lock = PRESETS_LOCK.lock().await;
+    PresetStore::open(&app)?.delete(&engine, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(name: &str, value: &str) -> EngineOption {
+        EngineOption {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn preset(name: &str, options: Vec<EngineOption>) -> EnginePreset {
+        EnginePreset {
+            engine: "/engines/stockfish".to_string(),
+            name: name.to_string(),
+            options,
+        }
+    }
+
+    #[test]
+    fn stores_presets_per_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PresetStore {
+            path: dir.path().join("engine-presets.json"),
+        };
+        assert!(store.list("/engines/stockfish").unwrap().is_empty());
+
+        store.put(preset("Deep", vec![option("Hash", "1024")])).unwrap();
+        store.put(preset("Quick", vec![option("Hash", "64")])).unwrap();
+        store.put(preset("Deep", vec![option("Hash", "2048")])).unwrap();
+
+        let names: Vec<_> = store
+            .list("/engines/stockfish")
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["Deep", "Quick"]);
+        assert_eq!(
+            store.get("/engines/stockfish", "Deep").unwrap().options,
+            vec![option("Hash", "2048")]
+        );
+        assert!(store.list("/engines/lc0").unwrap().is_empty());
+
+        store.delete("/engines/stockfish", "Deep").unwrap();
+        assert!(matches!(
+            store.get("/engines/stockfish", "Deep"),
+            Err(Error::PresetNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn explicit_options_override_preset() {
+        let mut options = vec![option("Threads", "2")];
+        merge_preset(
+            preset("Deep", vec![option("Hash", "1024"), option("Threads", "8")]),
+            &mut options,
+        );
+        assert_eq!(options, vec![option("Hash", "1024"), option("Threads", "2")]);
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specta::Type;
+use tauri::{path::BaseDirectory, Manager};
+use tokio::sync::Mutex;
+
+use super::{benchmark::EngineBenchmark, EngineLaunch, EngineOption, EngineProcess, EngineProtocol, GoMode};
+use crate::error::Error;
+
+const REGISTRY_FILE: &str = "engines/registry.json";
+/// The file the frontend kept engines in before the registry existed.
+const LEGACY_FILE: &str = "engines/engines.json";
+const REGISTRY_VERSION: u32 = 1;
+const MAX_BENCHMARKS: usize = 20; // Per engine, the oldest are dropped first
+
+/// Serializes read-modify-write cycles on the registry file.
+static REGISTRY_LOCK: Mutex<()> = Mutex::const_new(());
+
+lazy_static! {
+    /// A word of an engine name that is a version, like `17`, `v0.31.1`,
+    /// `3.0-dev` or `dev-20240310-abc`, so `x86-64` or `Lc0` are not.
+    static ref VERSION_RE: Regex =
+        Regex::new(r"(?:^|\s)(?:v|dev-)?(\d+(?:\.\d+)*(?:-[0-9A-Za-z.]+)?)(?:\s|$)").unwrap();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum ProtocolKind {
+    Uci,
+    Xboard,
+}
+
+/// A local or remote engine known to the app. The path identifies the
+/// engine, as it does in every analysis command.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredEngine {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub launch: EngineLaunch,
+    /// Detected when the engine is added; `None` for migrated engines until
+    /// they are updated.
+    #[specta(optional)]
+    pub protocol: Option<ProtocolKind>,
+    /// The name the engine reports with `id name`.
+    #[specta(optional)]
+    pub engine_id: Option<String>,
+    #[specta(optional)]
+    pub version: Option<String>,
+    /// Hash of the executable, used to notice when the engine was replaced.
+    #[specta(optional)]
+    pub binary_hash: Option<String>,
+    #[serde(default)]
+    pub options: Vec<EngineOption>,
+    #[specta(optional)]
+    pub go: Option<GoMode>,
+    #[specta(optional)]
+    pub elo: Option<u32>,
+    #[specta(optional)]
+    pub image: Option<String>,
+    pub enabled: bool,
+    /// Past benchmark runs, oldest first.
+    #[serde(default)]
+    pub benchmarks: Vec<EngineBenchmark>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RegistryFile {
+    version: u32,
+    engines: Vec<RegisteredEngine>,
+    /// Hash of the legacy file when it was last merged in.
+    #[serde(default)]
+    legacy_hash: Option<String>,
+    /// Engines that came from the legacy file, so the ones removed there are
+    /// removed here too.
+    #[serde(default)]
+    legacy_paths: Vec<String>,
+}
+
+struct Registry {
+    path: PathBuf,
+    legacy_path: PathBuf,
+}
+
+impl Registry {
+    fn open(app: &tauri::AppHandle) -> Result<Self, Error> {
+        Ok(Self {
+            path: app.path().resolve(REGISTRY_FILE, BaseDirectory::AppData)?,
+            legacy_path: app.path().resolve(LEGACY_FILE, BaseDirectory::AppData)?,
+        })
+    }
+
+    /// Reads the registry. The frontend still edits its engines in the
+    /// legacy file, so that is merged in again whenever it changed.
+    fn read(&self) -> Result<RegistryFile, Error> {
+        let mut file = if self.path.exists() {
+            serde_json::from_str(&fs::read_to_string(&self.path)?)?
+        } else {
+            RegistryFile::default()
+        };
+
+        let legacy_hash = hash_binary(&self.legacy_path);
+        if legacy_hash.is_some() && legacy_hash != file.legacy_hash {
+            info!("Syncing engines from {}", self.legacy_path.display());
+            let legacy: Value = serde_json::from_str(&fs::read_to_string(&self.legacy_path)?)?;
+            sync_legacy(&mut file, migrate_legacy(&legacy));
+            file.legacy_hash = legacy_hash;
+            self.write(&mut file)?;
+        }
+        Ok(file)
+    }
+
+    fn write(&self, file: &mut RegistryFile) -> Result<(), Error> {
+        file.version = REGISTRY_VERSION;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(file)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Merges the engines from the legacy file into the registry. What the user
+/// edits there wins; what was probed or benchmarked is kept.
+fn sync_legacy(file: &mut RegistryFile, legacy: Vec<RegisteredEngine>) {
+    let paths: Vec<String> = legacy.iter().map(|engine| engine.path.clone()).collect();
+    file.engines.retain(|engine| {
+        paths.contains(&engine.path) || !file.legacy_paths.contains(&engine.path)
+    });
+
+    for engine in legacy {
+        match file.engines.iter_mut().find(|e| e.path == engine.path) {
+            Some(existing) => {
+                existing.name = engine.name;
+                existing.version = engine.version.or(existing.version.take());
+                existing.options = engine.options;
+                existing.go = engine.go;
+                existing.elo = engine.elo;
+                existing.image = engine.image;
+                existing.enabled = engine.enabled;
+            }
+            None => file.engines.push(engine),
+        }
+    }
+    file.legacy_paths = paths;
+}
+
+/// Converts the frontend's engine list. Remote engines (lichess, chessdb)
+/// are not processes the backend can run, so only local ones are kept.
+fn migrate_legacy(legacy: &Value) -> Vec<RegisteredEngine> {
+    let str_field = |engine: &Value, key: &str| engine.get(key)?.as_str().map(str::to_string);
+
+    legacy
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|engine| engine.get("type").and_then(Value::as_str) == Some("local"))
+        .filter_map(|engine| {
+            let path = str_field(engine, "path")?;
+            let options = engine
+                .get("settings")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|setting| {
+                    let value = match setting.get("value")? {
+                        Value::String(s) => s.clone(),
+                        Value::Null => return None,
+                        value => value.to_string(),
+                    };
+                    Some(EngineOption {
+                        name: str_field(setting, "name")?,
+                        value,
+                    })
+                })
+                .collect();
+
+            Some(RegisteredEngine {
+                name: str_field(engine, "name").unwrap_or_else(|| path.clone()),
+                binary_hash: hash_binary(Path::new(&path)),
+                path,
+                launch: EngineLaunch::default(),
+                protocol: None,
+                engine_id: None,
+                version: str_field(engine, "version").filter(|v| !v.is_empty()),
+                options,
+                go: engine
+                    .get("go")
+                    .and_then(|go| serde_json::from_value(go.clone()).ok()),
+                elo: engine.get("elo").and_then(Value::as_u64).map(|elo| elo as u32),
+                image: str_field(engine, "image"),
+                enabled: engine.get("enabled").and_then(Value::as_bool).unwrap_or(true),
+                benchmarks: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Finds the version in an engine's `id name`, e.g. `16.1` in `Stockfish 16.1`.
+fn parse_version(engine_id: &str) -> Option<String> {
+    VERSION_RE
+        .captures(engine_id)
+        .map(|captures| captures[1].to_string())
+}
+
+/// FNV-1a over the executable. It only has to notice a replaced binary, so a
+/// cryptographic hash would be overkill. `None` for remote engines.
+pub(super) fn hash_binary(path: &Path) -> Option<String> {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hash = OFFSET;
+    loop {
+        let read = reader.read(&mut buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+        for byte in &buffer[..read] {
+            hash = (hash ^ *byte as u64).wrapping_mul(PRIME);
+        }
+    }
+    Some(format!("{hash:016x}"))
+}
+
+/// Starts the engine to fill in what it reports about itself.
+async fn probe(engine: &mut RegisteredEngine) -> Result<(), Error> {
+    let (mut process, _reader) =
+        EngineProcess::new(PathBuf::from(&engine.path), &engine.launch).await?;
+    engine.protocol = Some(match process.protocol {
+        EngineProtocol::Uci => ProtocolKind::Uci,
+        EngineProtocol::Xboard(_) => ProtocolKind::Xboard,
+    });
+    if !process.config.name.is_empty() {
+        engine.version = parse_version(&process.config.name);
+        engine.engine_id = Some(process.config.name.clone());
+    }
+    if let Err(e) = process.kill().await {
+        warn!("Failed to quit engine {} after probing: {}", engine.path, e);
+    }
+    Ok(())
+}
+
+/// Looks up a registered engine by path.
+async fn find_engine(
+    app: &tauri::AppHandle,
+    path: &str,
+) -> Result<Option<RegisteredEngine>, Error> {
+    let _lock = REGISTRY_LOCK.lock().await;
+    Ok(Registry::open(app)?
+        .read()?
+        .engines
+        .into_iter()
+        .find(|engine| engine.path == path))
+}
+
+/// Appends a benchmark run to the engine's entry. Returns `false` when the
+/// engine is not registered.
+pub(super) async fn record_benchmark(
+    app: &tauri::AppHandle,
+    path: &str,
+    benchmark: &EngineBenchmark,
+) -> Result<bool, Error> {
+    let _lock = REGISTRY_LOCK.lock().await;
+    let registry = Registry::open(app)?;
+    let mut file = registry.read()?;
+    let Some(engine) = file.engines.iter_mut().find(|engine| engine.path == path) else {
+        return Ok(false);
+    };
+    if engine.benchmarks.len() >= MAX_BENCHMARKS {
+        engine.benchmarks.remove(0);
+    }
+    engine.benchmarks.push(benchmark.clone());
+    registry.write(&mut file)?;
+    Ok(true)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_engines(app: tauri::AppHandle) -> Result<Vec<RegisteredEngine>, Error> {
+    let _lock = REGISTRY_LOCK.lock().await;
+    Ok(Registry::open(&app)?.read()?.engines)
+}
+
+/// Registers an engine after checking that it starts and speaks UCI or CECP.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_engine(
+    path: String,
+    name: Option<String>,
+    launch: Option<EngineLaunch>,
+    app: tauri::AppHandle,
+) -> Result<RegisteredEngine, Error> {
+    let mut engine = RegisteredEngine {
+        name: name.unwrap_or_default(),
+        binary_hash: hash_binary(Path::new(&path)),
+        path,
+        launch: launch.unwrap_or_default(),
+        protocol: None,
+        engine_id: None,
+        version: None,
+        options: Vec::new(),
+        go: None,
+        elo: None,
+        image: None,
+        enabled: true,
+        benchmarks: Vec::new(),
+    };
+    probe(&mut engine).await?;
+    if engine.name.is_empty() {
+        engine.name = engine.engine_id.clone().unwrap_or_else(|| engine.path.clone());
+    }
+
+    let _lock = REGISTRY_LOCK.lock().await;
+    let registry = Registry::open(&app)?;
+    let mut file = registry.read()?;
+    if file.engines.iter().any(|e| e.path == engine.path) {
+        return Err(Error::EngineAlreadyRegistered(engine.path));
+    }
+    info!("Registering engine {} at {}", engine.name, engine.path);
+    file.engines.push(engine.clone());
+    registry.write(&mut file)?;
+    Ok(engine)
+}
+
+/// Saves user changes to an engine. The engine is probed again when its
+/// binary or launch settings changed, or it has never been probed.
+#[tauri::command]
+#[specta::specta]
+pub async fn update_engine(
+    mut engine: RegisteredEngine,
+    app: tauri::AppHandle,
+) -> Result<RegisteredEngine, Error> {
+    let existing = find_engine(&app, &engine.path)
+        .await?
+        .ok_or_else(|| Error::EngineNotRegistered(engine.path.clone()))?;
+
+    engine.binary_hash = hash_binary(Path::new(&engine.path));
+    if engine.binary_hash != existing.binary_hash
+        || engine.launch != existing.launch
+        || existing.protocol.is_none()
+    {
+        probe(&mut engine).await?;
+    } else {
+        engine.protocol = existing.protocol;
+        engine.engine_id = existing.engine_id;
+        engine.version = engine.version.or(existing.version);
+    }
+    engine.benchmarks = existing.benchmarks;
+
+    let _lock = REGISTRY_LOCK.lock().await;
+    let registry = Registry::open(&app)?;
+    let mut file = registry.read()?;
+    let Some(slot) = file.engines.iter_mut().find(|e| e.path == engine.path) else {
+        return Err(Error::EngineNotRegistered(engine.path));
+    };
+    *slot = engine.clone();
+    registry.write(&mut file)?;
+    Ok(engine)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_engine(path: String, app: tauri::AppHandle) -> Result<(), Error> {
+    let _lock = REGISTRY_LOCK.lock().await;
+    let registry = Registry::open(&app)?;
+    let mut file = registry.read()?;
+    let count = file.engines.len();
+    file.engines.retain(|engine| engine.path != path);
+    if file.engines.len() == count {
+        return Err(Error::EngineNotRegistered(path));
+    }
+    info!("Removing engine {}", path);
+    registry.write(&mut file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_version("Stockfish 16.1").as_deref(), Some("16.1"));
+        assert_eq!(parse_version("Lc0 v0.31.1").as_deref(), Some("0.31.1"));
+        assert_eq!(parse_version("Stockfish dev-20240310-abc").as_deref(), Some("20240310-abc"));
+        assert_eq!(parse_version("Berserk 12 NNUE x86-64").as_deref(), Some("12"));
+        assert_eq!(parse_version("Komodo Dragon"), None);
+        assert_eq!(parse_version("Lc0"), None);
+    }
+
+    #[test]
+    fn migrates_legacy_engines() {
+        let legacy: Value = serde_json::from_str(
+            r#"[
+                {
+                    "type": "local",
+                    "name": "Stockfish",
+                    "version": "17",
+                    "path": "/does/not/exist/stockfish",
+                    "elo": 3600,
+                    "go": { "t": "Depth", "c": 24 },
+                    "settings": [
+                        { "name": "Hash", "value": 256 },
+                        { "name": "UCI_ShowWDL", "value": true },
+                        { "name": "SyzygyPath", "value": null }
+                    ]
+                },
+                { "type": "lichess", "name": "Lichess Cloud", "url": "https://lichess.org" }
+            ]"#,
+        )
+        .unwrap();
+
+        let engines = migrate_legacy(&legacy);
+        assert_eq!(engines.len(), 1);
+        let engine = &engines[0];
+        assert_eq!(engine.name, "Stockfish");
+        assert_eq!(engine.version.as_deref(), Some("17"));
+        assert_eq!(engine.go, Some(GoMode::Depth(24)));
+        assert_eq!(engine.elo, Some(3600));
+        assert!(engine.enabled);
+        assert!(engine.binary_hash.is_none());
+        assert_eq!(
+            engine.options,
+            vec![
+                EngineOption {
+                    name: "Hash".to_string(),
+                    value: "256".to_string(),
+                },
+                EngineOption {
+                    name: "UCI_ShowWDL".to_string(),
+                    value: "true".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_legacy_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Registry {
+            path: dir.path().join("registry.json"),
+            legacy_path: dir.path().join("engines.json"),
+        };
+        assert!(registry.read().unwrap().engines.is_empty());
+
+        fs::write(
+            &registry.legacy_path,
+            r#"[{ "type": "local", "name": "Fish", "version": "", "path": "/fish" }]"#,
+        )
+        .unwrap();
+        let mut file = registry.read().unwrap();
+        assert_eq!(file.engines.len(), 1);
+
+        // Probed details and engines added here survive edits to the legacy file
+        file.engines[0].engine_id = Some("Fish 1".to_string());
+        file.engines.push(RegisteredEngine {
+            name: "Other".to_string(),
+            path: "/other".to_string(),
+            ..file.engines[0].clone()
+        });
+        registry.write(&mut file).unwrap();
+
+        fs::write(
+            &registry.legacy_path,
+            r#"[{ "type": "local", "name": "Big Fish", "version": "", "path": "/fish" }]"#,
+        )
+        .unwrap();
+        let engines = registry.read().unwrap().engines;
+        assert_eq!(engines.len(), 2);
+        assert_eq!(engines[0].name, "Big Fish");
+        assert_eq!(engines[0].engine_id.as_deref(), Some("Fish 1"));
+
+        fs::write(&registry.legacy_path, "[]").unwrap();
+        let engines = registry.read().unwrap().engines;
+        assert_eq!(engines.len(), 1);
+        assert_eq!(engines[0].path, "/other");
+    }
+}
@@ -0,0 +1,153 @@
+use shakmaty::{Bitboard, Board, Chess, Move, Position, Role, Square};
+
+/// A move is a sacrifice when it gives up more than a pawn.
+const SACRIFICE_THRESHOLD: i32 = 100;
+
+const fn piece_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 300,
+        Role::Bishop => 300,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        // Only captured in the exchange when that is the last capture
+        Role::King => 20_000,
+    }
+}
+
+fn least_valuable(board: &Board, attackers: Bitboard) -> Option<(Square, Role)> {
+    Role::ALL
+        .into_iter()
+        .find_map(|role| (attackers & board.by_role(role)).first().map(|sq| (sq, role)))
+}
+
+/// Static exchange evaluation: the material the mover wins with `mv` and the
+/// recaptures on its target square that follow, each side capturing with
+/// its least valuable piece and stopping when going on would lose. Pins are
+/// ignored.
+fn see(board: &Board, mv: &Move) -> i32 {
+    let (Some(from), false) = (mv.from(), mv.is_castle()) else {
+        return 0;
+    };
+    let Some(mut side) = board.color_at(from) else {
+        return 0;
+    };
+    let to = mv.to();
+
+    let mut occupied = board.occupied();
+    occupied.discard(from);
+    if let Move::EnPassant { .. } = mv {
+        occupied.discard(Square::from_coords(to.file(), from.rank()));
+    }
+
+    // gains[i]: what the side making capture i has won if the exchange stops there
+    let mut gains = vec![mv.capture().map_or(0, piece_value)];
+    let mut on_square = mv.promotion().unwrap_or(mv.role());
+    loop {
+        side = !side;
+        let attackers = board.attacks_to(to, side, occupied) & occupied;
+        let Some((sq, role)) = least_valuable(board, attackers) else {
+            break;
+        };
+        let previous = gains[gains.len() - 1];
+        gains.push(piece_value(on_square) - previous);
+        occupied.discard(sq);
+        on_square = role;
+    }
+
+    while let Some(last) = gains.pop() {
+        match gains.last_mut() {
+            Some(previous) => *previous = -(-*previous).max(last),
+            None => return last,
+        }
+    }
+    0
+}
+
+/// The most material the side to move wins with a single capture.
+fn best_capture(pos: &Chess) -> i32 {
+    pos.legal_moves()
+        .iter()
+        .filter(|mv| mv.is_capture())
+        .map(|mv| see(pos.board(), mv))
+        .max()
+        .unwrap_or(0)
+        .max(0)
+}
+
+/// What `mv` gives up: the material the opponent can win right after it,
+/// less what the move captured. Trades and recaptures give up nothing.
+fn material_given(pos: &Chess, mv: &Move) -> i32 {
+    let mut after = pos.clone();
+    after.play_unchecked(mv);
+    if after.is_game_over() {
+        return 0;
+    }
+    best_capture(&after) - mv.capture().map_or(0, piece_value)
+}
+
+/// Whether `mv` gives up material. Whether the sacrifice was sound is up to
+/// the engine, see `classify_game`.
+pub(super) fn is_sacrifice(pos: &Chess, mv: &Move) -> bool {
+    material_given(pos, mv) > SACRIFICE_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{fen::Fen, uci::UciMove, CastlingMode};
+
+    fn position(fen: &str) -> Chess {
+        fen.parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap()
+    }
+
+    fn uci(pos: &Chess, uci: &str) -> Move {
+        UciMove::from_ascii(uci.as_bytes()).unwrap().to_move(pos).unwrap()
+    }
+
+    #[test]
+    fn exchanges() {
+        // Rook takes a knight defended by a pawn
+        let pos = position("4k3/8/2p5/3n4/8/8/8/3RK3 w - - 0 1");
+        assert_eq!(see(pos.board(), &uci(&pos, "d1d5")), -200);
+
+        // Bishop takes a pawn defended by a pawn, backed up by a rook
+        let pos = position("4k3/8/2p5/3p4/8/5B2/8/3RK3 w - - 0 1");
+        assert_eq!(see(pos.board(), &uci(&pos, "f3d5")), -100);
+
+        // Undefended piece, and an x-ray through the front rook
+        let pos = position("4k3/8/8/3n4/8/8/3R4/3RK3 w - - 0 1");
+        assert_eq!(see(pos.board(), &uci(&pos, "d2d5")), 300);
+        let pos = position("4k3/3r4/8/3n4/8/8/3R4/3RK3 w - - 0 1");
+        assert_eq!(see(pos.board(), &uci(&pos, "d2d5")), 300);
+    }
+
+    #[test]
+    fn detects_sacrifices() {
+        // Greek gift
+        let pos = position("rnbq1rk1/pppp1ppp/4pn2/8/1bPP4/2NB1N2/PP3PPP/R1BQK2R w KQ - 0 6");
+        assert!(is_sacrifice(&pos, &uci(&pos, "d3h7")));
+
+        // Exchange sacrifice
+        let pos = position("4k3/8/2p5/3n4/8/8/8/3RK3 w - - 0 1");
+        assert!(is_sacrifice(&pos, &uci(&pos, "d1d5")));
+    }
+
+    #[test]
+    fn trades_are_not_sacrifices() {
+        // Queen trade
+        let pos = position("8/8/4k3/3q4/8/8/3Q4/4K3 w - - 0 1");
+        assert!(!is_sacrifice(&pos, &uci(&pos, "d2d5")));
+
+        // Recapture
+        let pos = position("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        assert!(!is_sacrifice(&pos, &uci(&pos, "e4d5")));
+
+        // Quiet move to a safe square
+        let pos = position("4k3/8/8/8/8/8/8/3RK3 w - - 0 1");
+        assert!(!is_sacrifice(&pos, &uci(&pos, "d1d4")));
+    }
+}
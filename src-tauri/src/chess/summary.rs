@@ -0,0 +1,214 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{Chess, Color};
+use specta::Type;
+
+use super::{
+    classify::{played_moves, PlayedMove, WINNING},
+    AnalysisOptions, MoveAnalysis, MoveClassification,
+};
+use crate::{
+    db::{load_game_summary, store_game_summary},
+    error::Error,
+    AppState,
+};
+
+/// One player's side of a game analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSummary {
+    /// Average centipawn loss, with scores capped like the win chances.
+    pub acpl: f64,
+    /// From 0 to 100, from the win chance lost on every move.
+    pub accuracy: f64,
+    /// Moves with analysed positions on both sides; the others are left
+    /// out of every figure.
+    pub moves: u32,
+    pub inaccuracies: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+    pub missed_wins: u32,
+    /// Moves after which the player was winning or losing, by the same
+    /// win chance bounds the classification uses.
+    pub winning_moves: u32,
+    pub losing_moves: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSummary {
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+#[derive(Serialize, Debug, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct GameAnalysis {
+    pub moves: Vec<MoveAnalysis>,
+    /// `None` when the played moves were left out of the search, since
+    /// there is nothing to measure them against.
+    #[specta(optional)]
+    pub summary: Option<GameSummary>,
+}
+
+/// Lichess's accuracy of a single move, from the win chance it lost.
+fn move_accuracy(win_loss: f64) -> f64 {
+    (103.1668 * (-0.04354 * win_loss).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// The mean of the arithmetic and harmonic means of the move accuracies, so
+/// a few bad moves weigh more than in a plain average.
+fn game_accuracy(accuracies: &[f64]) -> f64 {
+    if accuracies.is_empty() {
+        return 0.0;
+    }
+    let n = accuracies.len() as f64;
+    let mean = accuracies.iter().sum::<f64>() / n;
+    // Floored, so a single lost-everything move doesn't zero the game
+    let harmonic = n / accuracies.iter().map(|a| 1.0 / a.max(1.0)).sum::<f64>();
+    (mean + harmonic) / 2.0
+}
+
+fn player_summary(played: &[PlayedMove], analysis: &[MoveAnalysis], color: Color) -> PlayerSummary {
+    let mut summary = PlayerSummary::default();
+    let mut cp_loss = 0;
+    let mut accuracies = Vec::new();
+
+    for played in played.iter().filter(|played| played.turn == color) {
+        summary.moves += 1;
+        cp_loss += (played.cp_before - played.cp_after).max(0);
+        accuracies.push(move_accuracy((played.win_before - played.win_after).max(0.0)));
+
+        match analysis[played.index].classification {
+            Some(MoveClassification::Inaccuracy) => summary.inaccuracies += 1,
+            Some(MoveClassification::Mistake) => summary.mistakes += 1,
+            Some(MoveClassification::Blunder) => summary.blunders += 1,
+            Some(MoveClassification::MissedWin) => summary.missed_wins += 1,
+            _ => {}
+        }
+        if played.win_after >= WINNING {
+            summary.winning_moves += 1;
+        } else if played.win_after <= 100.0 - WINNING {
+            summary.losing_moves += 1;
+        }
+    }
+
+    if summary.moves > 0 {
+        summary.acpl = cp_loss as f64 / summary.moves as f64;
+    }
+    summary.accuracy = game_accuracy(&accuracies);
+    summary
+}
+
+/// Per-player figures of a classified game analysis, where entry `i` is the
+/// position before `moves[i]`.
+pub(super) fn summarize_game(
+    analysis: &[MoveAnalysis],
+    start: Chess,
+    moves: &[String],
+) -> Result<GameSummary, Error> {
+    let played = played_moves(analysis, start, moves)?;
+    Ok(GameSummary {
+        white: player_summary(&played, analysis, Color::White),
+        black: player_summary(&played, analysis, Color::Black),
+    })
+}
+
+/// Per-player figures of the analysis of `options`, see [`summarize_game`].
+pub(super) fn summarize_analysis(
+    analysis: &[MoveAnalysis],
+    start: Chess,
+    options: &AnalysisOptions,
+) -> Result<Option<GameSummary>, Error> {
+    if options.exclude_played_moves {
+        return Ok(None);
+    }
+    summarize_game(analysis, start, &options.moves).map(Some)
+}
+
+/// Stores a game's analysis summary in its database, replacing an earlier
+/// one.
+#[tauri::command]
+#[specta::specta]
+pub async fn save_game_summary(
+    file: PathBuf,
+    game_id: i32,
+    summary: GameSummary,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), Error> {
+    store_game_summary(&state, &file, game_id, &serde_json::to_string(&summary)?)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_game_summary(
+    file: PathBuf,
+    game_id: i32,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<GameSummary>, Error> {
+    match load_game_summary(&state, &file, game_id)? {
+        Some(summary) => Ok(Some(serde_json::from_str(&summary)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played(index: usize, turn: Color, cp: (i32, i32), win: (f64, f64)) -> PlayedMove {
+        PlayedMove {
+            index,
+            turn,
+            cp_before: cp.0,
+            cp_after: cp.1,
+            win_before: win.0,
+            win_after: win.1,
+        }
+    }
+
+    #[test]
+    fn summarizes_each_side() {
+        let blunder = MoveAnalysis {
+            classification: Some(MoveClassification::Blunder),
+            ..Default::default()
+        };
+        let analysis = [MoveAnalysis::default(), MoveAnalysis::default(), blunder];
+        let moves = [
+            played(0, Color::White, (30, 30), (55.0, 55.0)),
+            played(1, Color::Black, (-30, -10), (45.0, 46.0)),
+            played(2, Color::White, (50, -250), (60.0, 15.0)),
+        ];
+
+        let white = player_summary(&moves, &analysis, Color::White);
+        assert_eq!(white.moves, 2);
+        assert_eq!(white.acpl, 150.0);
+        assert_eq!(white.blunders, 1);
+        assert_eq!(white.losing_moves, 1);
+        assert!(white.accuracy > 0.0 && white.accuracy < 60.0);
+
+        let black = player_summary(&moves, &analysis, Color::Black);
+        assert_eq!(black.moves, 1);
+        assert_eq!(black.acpl, 0.0);
+        assert!(black.accuracy > 99.0);
+    }
+
+    #[test]
+    fn excluded_moves_are_not_summarized() {
+        let options = AnalysisOptions {
+            moves: vec!["e2e4".to_string()],
+            exclude_played_moves: true,
+            ..Default::default()
+        };
+        let analysis = [MoveAnalysis::default(), MoveAnalysis::default()];
+        assert_eq!(summarize_analysis(&analysis, Chess::default(), &options).unwrap(), None);
+    }
+
+    #[test]
+    fn perfect_moves_are_fully_accurate() {
+        assert!((move_accuracy(0.0) - 99.9999).abs() < 1e-9);
+        assert_eq!(game_accuracy(&[]), 0.0);
+    }
+}
@@ -0,0 +1,422 @@
+use std::sync::atomic::Ordering;
+
+use futures_util::StreamExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use shakmaty::fen::Fen;
+use specta::Type;
+use sysinfo::SystemExt;
+use tauri_specta::Event;
+
+use super::engine_match::{
+    append_pgn, play_game, GameContext, MatchEngine, MatchGame, MatchOpening, MatchSettings,
+    RunningMatch, Termination,
+};
+use crate::{db::Outcome, error::Error, AppState};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum TournamentKind {
+    /// Every engine plays every other engine.
+    RoundRobin,
+    /// The first engine plays every other engine.
+    Gauntlet,
+}
+
+#[derive(Deserialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentSettings {
+    pub kind: TournamentKind,
+    /// Maximum number of games played at the same time. Capped by the
+    /// number of physical cores, since every game runs two engines.
+    #[specta(optional)]
+    pub concurrency: Option<u32>,
+    /// Per-game settings. Every opening is always played with both colors.
+    pub games: MatchSettings,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Wdl {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Wdl {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    fn add(&mut self, other: &Wdl) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+/// Elo difference with a 95% confidence margin and likelihood of superiority.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EloEstimate {
+    pub elo: f64,
+    pub error: f64,
+    pub los: f64,
+}
+
+impl EloEstimate {
+    pub fn from_wdl(wdl: &Wdl) -> Self {
+        let n = wdl.games() as f64;
+        if n == 0.0 {
+            return Self {
+                los: 0.5,
+                ..Default::default()
+            };
+        }
+
+        let score = wdl.points() / n;
+        let variance = (wdl.wins as f64 * (1.0 - score).powi(2)
+            + wdl.draws as f64 * (0.5 - score).powi(2)
+            + wdl.losses as f64 * score.powi(2))
+            / n;
+        let margin = 1.959964 * (variance / n).sqrt();
+
+        let elo = elo_from_score(score);
+        let error = (elo_from_score(score + margin) - elo_from_score(score - margin)) / 2.0;
+
+        let decisive = (wdl.wins + wdl.losses) as f64;
+        let los = if decisive == 0.0 {
+            0.5
+        } else {
+            0.5 * (1.0 + erf((wdl.wins as f64 - wdl.losses as f64) / (2.0 * decisive).sqrt()))
+        };
+
+        Self { elo, error, los }
+    }
+}
+
+/// Converts an expected score to an Elo difference, clamped so that perfect
+/// scores stay finite.
+fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(0.001, 0.999);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Abramowitz & Stegun approximation of the error function (7.1.26).
+fn erf(x: f64) -> f64 {
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = ((((1.061405429 * t - 1.453152027) * t + 1.421413741) * t - 0.284496736) * t
+        + 0.254829592)
+        * t;
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Standing {
+    pub engine: String,
+    pub points: f64,
+    pub score: Wdl,
+    pub elo: EloEstimate,
+}
+
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PairResult {
+    pub first: String,
+    pub second: String,
+    /// Result from the point of view of `first`.
+    pub score: Wdl,
+    pub elo: EloEstimate,
+}
+
+/// A scheduled game that could not be played, for example because its
+/// opening is not a legal position. Engines that fail forfeit instead.
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedGame {
+    pub round: u32,
+    pub white: String,
+    pub black: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentResult {
+    pub standings: Vec<Standing>,
+    pub pairs: Vec<PairResult>,
+    /// `crosstable[i][j]` is the score of engine `i` against engine `j`.
+    pub crosstable: Vec<Vec<Wdl>>,
+    pub games: Vec<MatchGame>,
+    /// Games left out of the scores.
+    pub failed: Vec<FailedGame>,
+}
+
+#[derive(Clone, Serialize, Debug, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentUpdate {
+    pub id: String,
+    pub games_played: u32,
+    /// Games that could not be played. The tournament is complete once
+    /// these and `games_played` add up to `total_games`.
+    pub games_failed: u32,
+    pub total_games: u32,
+    pub standings: Vec<Standing>,
+    pub crosstable: Vec<Vec<Wdl>>,
+}
+
+struct Pairing<'a> {
+    white: usize,
+    black: usize,
+    opening: &'a MatchOpening,
+}
+
+fn pairings<'a>(
+    kind: TournamentKind,
+    engines: usize,
+    rounds: u32,
+    openings: &'a [MatchOpening],
+) -> Vec<Pairing<'a>> {
+    let pairs: Vec<(usize, usize)> = match kind {
+        TournamentKind::RoundRobin => (0..engines)
+            .flat_map(|i| (i + 1..engines).map(move |j| (i, j)))
+            .collect(),
+        TournamentKind::Gauntlet => (1..engines).map(|j| (0, j)).collect(),
+    };
+
+    let mut pairings = Vec::new();
+    for _ in 0..rounds {
+        for &(a, b) in &pairs {
+            for opening in openings {
+                pairings.push(Pairing {
+                    white: a,
+                    black: b,
+                    opening,
+                });
+                pairings.push(Pairing {
+                    white: b,
+                    black: a,
+                    opening,
+                });
+            }
+        }
+    }
+    pairings
+}
+
+fn max_concurrency(requested: Option<u32>) -> usize {
+    let cores = sysinfo::System::new_all()
+        .physical_core_count()
+        .unwrap_or(1);
+    let limit = (cores / 2).max(1);
+    requested.map_or(limit, |r| (r as usize).clamp(1, limit))
+}
+
+fn standings(engines: &[MatchEngine], crosstable: &[Vec<Wdl>]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = engines
+        .iter()
+        .zip(crosstable)
+        .map(|(engine, row)| {
+            let mut score = Wdl::default();
+            row.iter().for_each(|wdl| score.add(wdl));
+            Standing {
+                engine: engine.name.clone(),
+                points: score.points(),
+                score,
+                elo: EloEstimate::from_wdl(&score),
+            }
+        })
+        .collect();
+    standings.sort_by(|a, b| b.points.total_cmp(&a.points));
+    standings
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn start_tournament(
+    id: String,
+    engines: Vec<MatchEngine>,
+    settings: TournamentSettings,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<TournamentResult, Error> {
+    info!("Starting tournament {} with {} engines", id, engines.len());
+
+    let running = RunningMatch::register(&state, &id);
+
+    let openings = if settings.games.openings.is_empty() {
+        vec![MatchOpening {
+            fen: Fen::default().to_string(),
+            moves: vec![],
+        }]
+    } else {
+        settings.games.openings.clone()
+    };
+    let schedule = pairings(settings.kind, engines.len(), settings.games.rounds, &openings);
+    let total_games = schedule.len() as u32;
+    let concurrency = max_concurrency(settings.concurrency);
+    info!(
+        "Tournament {}: {} games, {} at a time",
+        id, total_games, concurrency
+    );
+
+    let mut result = TournamentResult {
+        crosstable: vec![vec![Wdl::default(); engines.len()]; engines.len()],
+        ..Default::default()
+    };
+
+    let (id_ref, engines_ref, cancelled_ref, app_ref) =
+        (&id, &engines, &*running.cancelled, &app);
+    let match_settings = &settings.games;
+    let mut games = futures_util::stream::iter(schedule.into_iter().enumerate().map(
+        move |(i, pairing)| async move {
+            let round = i as u32 + 1;
+            let ctx = GameContext {
+                id: id_ref,
+                round,
+                total_rounds: total_games,
+                settings: match_settings,
+                cancelled: cancelled_ref,
+                app: app_ref,
+            };
+            let game = play_game(
+                &engines_ref[pairing.white],
+                &engines_ref[pairing.black],
+                pairing.opening,
+                &ctx,
+            )
+            .await;
+            (round, pairing, game)
+        },
+    ))
+    .buffer_unordered(concurrency);
+
+    let mut record =
+        |round: u32, pairing: Pairing<'_>, game: Result<MatchGame, Error>| -> Result<(), Error> {
+            let (w, b) = (pairing.white, pairing.black);
+            match game {
+                Ok(game) => {
+                    append_pgn(&settings.games.pgn_path, &game.pgn)?;
+                    match game.result {
+                        Outcome::WhiteWin => {
+                            result.crosstable[w][b].wins += 1;
+                            result.crosstable[b][w].losses += 1;
+                        }
+                        Outcome::BlackWin => {
+                            result.crosstable[w][b].losses += 1;
+                            result.crosstable[b][w].wins += 1;
+                        }
+                        _ => {
+                            result.crosstable[w][b].draws += 1;
+                            result.crosstable[b][w].draws += 1;
+                        }
+                    }
+                    result.games.push(game);
+                }
+                Err(e) => {
+                    warn!("Tournament {} game {} failed: {}", id, round, e);
+                    result.failed.push(FailedGame {
+                        round,
+                        white: engines[w].name.clone(),
+                        black: engines[b].name.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+
+            TournamentUpdate {
+                id: id.clone(),
+                games_played: result.games.len() as u32,
+                games_failed: result.failed.len() as u32,
+                total_games,
+                standings: standings(&engines, &result.crosstable),
+                crosstable: result.crosstable.clone(),
+            }
+            .emit(&app)?;
+            Ok(())
+        };
+
+    while let Some((round, pairing, game)) = games.next().await {
+        if matches!(&game, Ok(game) if game.termination == Termination::Aborted) {
+            continue;
+        }
+        if let Err(e) = record(round, pairing, game) {
+            // Let the games in progress abort and quit their engines first
+            running.cancelled.store(true, Ordering::Relaxed);
+            while games.next().await.is_some() {}
+            return Err(e);
+        }
+    }
+    result.standings = standings(&engines, &result.crosstable);
+    for i in 0..engines.len() {
+        for j in i + 1..engines.len() {
+            let score = result.crosstable[i][j];
+            if score.games() == 0 {
+                continue;
+            }
+            result.pairs.push(PairResult {
+                first: engines[i].name.clone(),
+                second: engines[j].name.clone(),
+                score,
+                elo: EloEstimate::from_wdl(&score),
+            });
+        }
+    }
+
+    info!("Tournament {} finished", id);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wdl(wins: u32, draws: u32, losses: u32) -> Wdl {
+        Wdl {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn even_score_is_zero_elo() {
+        let estimate = EloEstimate::from_wdl(&wdl(10, 20, 10));
+        assert!(estimate.elo.abs() < 1e-9);
+        assert!((estimate.los - 0.5).abs() < 1e-6);
+        assert!(estimate.error > 0.0);
+    }
+
+    #[test]
+    fn winning_score_is_positive_elo() {
+        let estimate = EloEstimate::from_wdl(&wdl(50, 20, 10));
+        // 75% score
+        assert!((estimate.elo - 190.85).abs() < 0.1);
+        assert!(estimate.los > 0.99);
+    }
+
+    #[test]
+    fn perfect_score_stays_finite() {
+        let estimate = EloEstimate::from_wdl(&wdl(10, 0, 0));
+        assert!(estimate.elo.is_finite());
+        assert!(estimate.error.is_finite());
+    }
+
+    #[test]
+    fn gauntlet_pairs_first_engine_only() {
+        let openings = vec![MatchOpening::default()];
+        let games = pairings(TournamentKind::Gauntlet, 4, 2, &openings);
+        assert_eq!(games.len(), 3 * 2 * 2);
+        assert!(games.iter().all(|p| p.white == 0 || p.black == 0));
+
+        let games = pairings(TournamentKind::RoundRobin, 4, 1, &openings);
+        assert_eq!(games.len(), 6 * 2);
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader, Lines},
+    net::TcpStream,
+    process::{Child, ChildStderr, Command},
+    time::timeout,
+};
+
+use super::{logs::EngineLogs, EngineLog};
+use crate::error::Error;
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+const TCP_PREFIX: &str = "tcp://";
+const COMMAND_PREFIX: &str = "cmd://";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// No reply to `uci` in this time means the engine likely speaks CECP.
+const UCI_PROBE_TIMEOUT: Duration = Duration::from_secs(8);
+
+pub(super) type EngineReader = Lines<BufReader<Box<dyn AsyncRead + Send + Unpin>>>;
+pub(super) type EngineWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// How an engine process is started, stored alongside the engine definition.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq, Type)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EngineLaunch {
+    /// Arguments passed after the executable, e.g. `--weights=net.pb.gz`.
+    pub args: Vec<String>,
+    /// Extra environment variables; these override the defaults we set.
+    pub env: HashMap<String, String>,
+    /// Defaults to the directory of the executable.
+    #[specta(optional)]
+    pub working_dir: Option<PathBuf>,
+    /// Start the engine again, resuming the search, if it crashes during
+    /// live analysis.
+    pub auto_restart: bool,
+    /// Milliseconds to wait for a reply to `uci` before trying CECP, for
+    /// engines that are slow to start.
+    #[specta(optional)]
+    pub uci_timeout: Option<u32>,
+}
+
+impl EngineLaunch {
+    pub(super) fn uci_probe_timeout(&self) -> Duration {
+        self.uci_timeout.map_or(UCI_PROBE_TIMEOUT, |ms| Duration::from_millis(ms.into()))
+    }
+}
+
+/// Where an engine runs, parsed from the engine path.
+///
+/// `tcp://host:port` connects to an engine served over TCP, `cmd://program
+/// args...` runs an arbitrary command line (such as `ssh box stockfish`) and
+/// anything else is the path of a local executable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineSource {
+    Process {
+        program: PathBuf,
+        launch: EngineLaunch,
+    },
+    Tcp(String),
+}
+
+impl EngineSource {
+    pub fn parse(engine: &str, launch: &EngineLaunch) -> Result<Self, Error> {
+        if let Some(address) = engine.strip_prefix(TCP_PREFIX) {
+            if address.is_empty() {
+                return Err(Error::InvalidEngine(engine.to_string()));
+            }
+            if !launch.args.is_empty() || !launch.env.is_empty() || launch.working_dir.is_some() {
+                warn!("Ignoring launch settings for remote engine {}", address);
+            }
+            return Ok(Self::Tcp(address.to_string()));
+        }
+        if let Some(command_line) = engine.strip_prefix(COMMAND_PREFIX) {
+            let mut words = split_command_line(command_line).into_iter();
+            let program = words
+                .next()
+                .ok_or_else(|| Error::InvalidEngine(engine.to_string()))?;
+            let mut args: Vec<String> = words.collect();
+            args.extend(launch.args.iter().cloned());
+            return Ok(Self::Process {
+                program: PathBuf::from(program),
+                launch: EngineLaunch {
+                    args,
+                    ..launch.clone()
+                },
+            });
+        }
+        Ok(Self::Process {
+            program: PathBuf::from(engine),
+            launch: launch.clone(),
+        })
+    }
+}
+
+/// Splits a command line on whitespace, keeping quoted words together.
+/// Backslashes are left alone so Windows paths need no escaping.
+fn split_command_line(command_line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in command_line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Byte streams to an engine, plus the child process when it runs locally.
+pub(super) struct EngineConnection {
+    pub writer: EngineWriter,
+    pub reader: EngineReader,
+    pub child: Option<Child>,
+    /// Starts out with the engine's stderr, which keeps being appended.
+    pub logs: EngineLogs,
+}
+
+pub(super) async fn connect(source: &EngineSource) -> Result<EngineConnection, Error> {
+    match source {
+        EngineSource::Process { program, launch } => spawn(program, launch),
+        EngineSource::Tcp(address) => {
+            info!("Connecting to remote engine at {}", address);
+            let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+                .await
+                .map_err(|_| Error::EngineTimeout)??;
+            stream.set_nodelay(true)?;
+            let (read, write) = stream.into_split();
+            Ok(EngineConnection {
+                writer: Box::new(write),
+                reader: lines(Box::new(read)),
+                child: None,
+                logs: EngineLogs::default(),
+            })
+        }
+    }
+}
+
+/// The only place engine processes are started, for analysis, matches and
+/// reading engine configs alike.
+fn spawn(program: &Path, launch: &EngineLaunch) -> Result<EngineConnection, Error> {
+    let mut command = Command::new(program);
+    command.args(&launch.args);
+
+    // Engines often load networks and books relative to their own directory
+    let dir = launch
+        .working_dir
+        .as_deref()
+        .or(program.parent())
+        .filter(|dir| !dir.as_os_str().is_empty());
+    match dir {
+        Some(dir) => {
+            command.current_dir(dir);
+        }
+        None => {
+            // Fallback for engines found on the PATH, such as Homebrew installs
+            if let Some(home_dir) = std::env::var_os("HOME") {
+                command.current_dir(home_dir);
+            }
+        }
+    }
+
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("TERM", "dumb") // Prevent terminal feature usage
+        .envs(&launch.env);
+
+    #[cfg(target_os = "windows")]
+    command.creation_flags(CREATE_NO_WINDOW);
+
+    debug!("Spawning engine process: {:?}", command);
+    let mut child = command.spawn().map_err(|e| {
+        error!("Failed to spawn engine process {:?}: {}", command, e);
+        Error::Io(e)
+    })?;
+
+    let stdin = child.stdin.take().ok_or_else(|| {
+        error!("Failed to get stdin handle from engine process");
+        Error::NoStdin
+    })?;
+    let stdout = child.stdout.take().ok_or_else(|| {
+        error!("Failed to get stdout handle from engine process");
+        Error::NoStdout
+    })?;
+    let logs = EngineLogs::default();
+    spawn_stderr_handler(child.stderr.take(), logs.clone());
+
+    debug!("Engine process spawned successfully");
+    Ok(EngineConnection {
+        writer: Box::new(stdin),
+        reader: lines(Box::new(stdout)),
+        child: Some(child),
+        logs,
+    })
+}
+
+fn lines(reader: Box<dyn AsyncRead + Send + Unpin>) -> EngineReader {
+    // Use a smaller buffer for more responsive reading
+    BufReader::with_capacity(1024, reader).lines()
+}
+
+fn spawn_stderr_handler(stderr: Option<ChildStderr>, logs: EngineLogs) {
+    if let Some(stderr) = stderr {
+        tokio::spawn(async move {
+            let mut stderr_lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = stderr_lines.next_line().await {
+                warn!("Engine stderr: {}", line);
+                logs.push(EngineLog::Stderr(line));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::EngineProcess;
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    #[test]
+    fn parses_engine_sources() {
+        let launch = EngineLaunch {
+            args: vec!["--threads".to_string(), "8".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            EngineSource::parse("/usr/bin/stockfish", &launch).unwrap(),
+            EngineSource::Process {
+                program: PathBuf::from("/usr/bin/stockfish"),
+                launch: launch.clone(),
+            }
+        );
+        assert_eq!(
+            EngineSource::parse("tcp://engines.lan:9000", &launch).unwrap(),
+            EngineSource::Tcp("engines.lan:9000".to_string())
+        );
+        assert_eq!(
+            EngineSource::parse(r#"cmd://ssh box "/opt/my engines/sf""#, &launch).unwrap(),
+            EngineSource::Process {
+                program: PathBuf::from("ssh"),
+                launch: EngineLaunch {
+                    args: vec![
+                        "box".to_string(),
+                        "/opt/my engines/sf".to_string(),
+                        "--threads".to_string(),
+                        "8".to_string(),
+                    ],
+                    ..Default::default()
+                },
+            }
+        );
+        assert!(EngineSource::parse("cmd://  ", &launch).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn logs_stderr() {
+        let launch = EngineLaunch {
+            args: vec![
+                "-c".to_string(),
+                "for i in $(seq 1 30); do echo line $i >&2; done; exit 3".to_string(),
+            ],
+            ..Default::default()
+        };
+        let mut connection = spawn(Path::new("sh"), &launch).unwrap();
+        let status = connection.child.as_mut().unwrap().wait().await.unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        // The stderr reader task may still be catching up
+        for _ in 0..100 {
+            if connection.logs.entries().len() == 30 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let tail = connection.logs.last_stderr(20);
+        assert_eq!(tail.len(), 20);
+        assert_eq!(tail.first().map(String::as_str), Some("line 11"));
+        assert_eq!(tail.last().map(String::as_str), Some("line 30"));
+    }
+
+    /// Stands in for a UCI engine shared over TCP.
+    async fn serve_fake_engine(listener: TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let reply = match line.as_str() {
+                "uci" => "id name Remote Fish\noption name Hash type spin default 16 min 1 max 1024\nuciok\n",
+                "isready" => "readyok\n",
+                "quit" => break,
+                _ => continue,
+            };
+            write.write_all(reply.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn initializes_engine_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve_fake_engine(listener));
+
+        let (mut process, _reader) = EngineProcess::new(
+            PathBuf::from(format!("tcp://{address}")),
+            &EngineLaunch::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(process.config.name, "Remote Fish");
+        assert_eq!(process.config.options.len(), 1);
+        process.kill().await.unwrap();
+    }
+}
@@ -0,0 +1,533 @@
+use std::time::{Duration, Instant};
+
+use derivative::Derivative;
+use log::{debug, trace};
+use shakmaty::{
+    san::{San, SanPlus},
+    uci::UciMove,
+    CastlingMode, Chess, Color, Move, Position,
+};
+use tokio::time::timeout;
+use vampirc_uci::{
+    uci::{Score, ScoreValue},
+    UciOptionConfig,
+};
+
+use super::{
+    engine_match::position_key,
+    invert_score, position_after,
+    logs::EngineLogs,
+    options::option_name,
+    transport::{EngineReader, EngineWriter},
+    BestMoves, EngineConfig, EngineEvent, EngineLog, EngineOptions, EngineProcess, GoMode,
+    PlayersTime,
+};
+use crate::error::Error;
+
+/// Engines that predate protocol version 2 never send `feature done=1`.
+const FEATURE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Upper bound for engines that asked for more time with `feature done=0`.
+const ENGINE_FEATURE_WAIT: Duration = Duration::from_secs(60);
+/// CECP engines report mate as `MATE_SCORE` minus the distance in plies.
+const MATE_SCORE: i32 = 100000;
+/// Assumed search speed for node limits on engines without `nps`.
+const NODES_PER_SECOND: u32 = 1_000_000;
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+
+/// Features announced by the engine in reply to `protover 2`.
+#[derive(Debug, Derivative)]
+#[derivative(Default)]
+struct XboardFeatures {
+    myname: Option<String>,
+    setboard: bool,
+    usermove: bool,
+    san: bool,
+    #[derivative(Default(value = "true"))]
+    analyze: bool,
+    #[derivative(Default(value = "true"))]
+    nps: bool,
+    memory: bool,
+    smp: bool,
+    exclude: bool,
+    done: Option<bool>,
+    options: Vec<UciOptionConfig>,
+}
+
+impl XboardFeatures {
+    fn apply(&mut self, key: &str, value: &str) {
+        let flag = value == "1";
+        match key {
+            "myname" => self.myname = Some(value.to_string()),
+            "setboard" => self.setboard = flag,
+            "usermove" => self.usermove = flag,
+            "san" => self.san = flag,
+            "analyze" => self.analyze = flag,
+            "nps" => self.nps = flag,
+            "memory" => self.memory = flag,
+            "smp" => self.smp = flag,
+            "exclude" => self.exclude = flag,
+            "done" => self.done = Some(flag),
+            "option" => self.options.extend(parse_option(value)),
+            _ => trace!("Ignoring CECP feature {}={}", key, value),
+        }
+    }
+}
+
+/// State of an engine speaking CECP (the XBoard/WinBoard protocol).
+#[derive(Debug)]
+pub(super) struct Xboard {
+    features: XboardFeatures,
+    analyzing: bool,
+    excluding: bool,
+    node_limited: bool,
+}
+
+impl Xboard {
+    /// Negotiates features with `protover 2` and leaves the engine in force
+    /// mode with thinking output enabled.
+    pub(super) async fn initialize(
+        stdin: &mut EngineWriter,
+        lines: &mut EngineReader,
+        logs: &EngineLogs,
+        config: &mut EngineConfig,
+    ) -> Result<Self, Error> {
+        debug!("Starting CECP initialization");
+        EngineProcess::send_command_with_log(stdin, "xboard\nprotover 2\n", logs).await?;
+
+        let mut features = XboardFeatures::default();
+        let start = Instant::now();
+        loop {
+            // `done=0` asks for as much time as the engine needs
+            let remaining = match features.done {
+                Some(true) => break,
+                Some(false) => ENGINE_FEATURE_WAIT,
+                None => FEATURE_TIMEOUT.saturating_sub(start.elapsed()),
+            };
+            let line = match timeout(remaining, lines.next_line()).await {
+                Ok(line) => line?.ok_or(Error::EngineTimeout)?,
+                Err(_) => break,
+            };
+            trace!("Engine response: {}", line);
+            logs.push(EngineLog::Engine(line.clone()));
+
+            if let Some(rest) = line.strip_prefix("feature ") {
+                let mut reply = String::new();
+                for (key, value) in parse_features(rest) {
+                    features.apply(&key, &value);
+                    reply.push_str(&format!("accepted {key}\n"));
+                }
+                EngineProcess::send_command_with_log(stdin, &reply, logs).await?;
+            }
+        }
+
+        if let Some(name) = &features.myname {
+            config.name = name.clone();
+        }
+        config.options = std::mem::take(&mut features.options);
+
+        EngineProcess::send_command_with_log(stdin, "new\nforce\npost\neasy\n", logs).await?;
+        debug!("CECP engine ready: {:?}", features);
+
+        Ok(Self {
+            features,
+            analyzing: false,
+            excluding: false,
+            node_limited: false,
+        })
+    }
+
+    /// Translates a UCI option into the matching CECP command, or `None` if
+    /// the engine did not announce it.
+    pub(super) fn option_command(
+        &self,
+        name: &str,
+        value: &str,
+        config: &EngineConfig,
+    ) -> Option<String> {
+        match name {
+            "Hash" if self.features.memory => Some(format!("memory {value}\n")),
+            "Threads" if self.features.smp => Some(format!("cores {value}\n")),
+            _ => config
+                .options
+                .iter()
+                .any(|opt| option_name(opt) == name)
+                .then(|| format!("option {name}={value}\n")),
+        }
+    }
+
+    pub(super) fn position_commands(&self, fen: &str, moves: &[String]) -> Result<String, Error> {
+        let mut pos = position_after(fen, &[])?;
+        let mut commands = if self.features.setboard {
+            format!("force\nsetboard {fen}\n")
+        } else if position_key(&pos) == position_key(&Chess::default()) {
+            "new\nforce\n".to_string()
+        } else {
+            return Err(Error::EngineUnsupported("setboard".to_string()));
+        };
+
+        for m in moves {
+            let mv = UciMove::from_ascii(m.as_bytes())?.to_move(&pos)?;
+            let text = self.move_text(&pos, &mv);
+            if self.features.usermove {
+                commands.push_str(&format!("usermove {text}\n"));
+            } else {
+                commands.push_str(&format!("{text}\n"));
+            }
+            pos.play_unchecked(&mv);
+        }
+        Ok(commands)
+    }
+
+    fn move_text(&self, pos: &Chess, mv: &Move) -> String {
+        if self.features.san {
+            San::from_move(pos, mv).to_string()
+        } else {
+            mv.to_uci(CastlingMode::Standard).to_string()
+        }
+    }
+
+    /// Restricts the search to `root_moves` by excluding every other legal
+    /// move, which needs the `exclude` feature.
+    pub(super) fn exclude_commands(
+        &mut self,
+        pos: &Chess,
+        root_moves: Option<&[String]>,
+    ) -> Result<String, Error> {
+        let Some(root_moves) = root_moves else {
+            return Ok(if std::mem::take(&mut self.excluding) {
+                "include all\n".to_string()
+            } else {
+                String::new()
+            });
+        };
+        if !self.features.exclude {
+            return Err(Error::EngineUnsupported("exclude".to_string()));
+        }
+
+        let mut commands = "include all\n".to_string();
+        for mv in pos.legal_moves() {
+            let uci = mv.to_uci(CastlingMode::Standard).to_string();
+            if !root_moves.contains(&uci) {
+                commands.push_str(&format!("exclude {}\n", self.move_text(pos, &mv)));
+            }
+        }
+        self.excluding = true;
+        Ok(commands)
+    }
+
+    /// Infinite searches run in analyze mode, which like `go infinite` only
+    /// ends when stopped. CECP has no node limit, so node searches set a
+    /// node rate with `nps` and search for one second of it.
+    pub(super) fn go_command(&mut self, mode: &GoMode, turn: Color) -> String {
+        let mut commands = String::new();
+        if std::mem::take(&mut self.node_limited) {
+            commands.push_str("nps 0\n");
+        }
+        self.analyzing = self.features.analyze && matches!(mode, GoMode::Infinite);
+        if self.analyzing {
+            commands.push_str("analyze\n");
+            return commands;
+        }
+        commands.push_str(&match mode {
+            GoMode::Infinite => "st 86400\ngo\n".to_string(),
+            GoMode::Nodes(nodes) if self.features.nps => {
+                self.node_limited = true;
+                format!("nps {}\nst 1\ngo\n", (*nodes).max(1))
+            }
+            // without `nps`, guess the time the nodes take
+            GoMode::Nodes(nodes) => {
+                format!("st {}\ngo\n", nodes.div_ceil(NODES_PER_SECOND).max(1))
+            }
+            GoMode::Depth(depth) => format!("sd {depth}\nst 86400\ngo\n"),
+            GoMode::Time(time) => format!("st {}\ngo\n", time.div_ceil(1000).max(1)),
+            GoMode::PlayersTime(PlayersTime {
+                white,
+                black,
+                winc,
+                binc,
+            }) => {
+                let (own, opp, inc) = match turn {
+                    Color::White => (white, black, winc),
+                    Color::Black => (black, white, binc),
+                };
+                let base = own / 1000;
+                format!(
+                    "level 0 {}:{:02} {}\ntime {}\notim {}\ngo\n",
+                    base / 60,
+                    base % 60,
+                    inc / 1000,
+                    own / 10,
+                    opp / 10
+                )
+            }
+        });
+        commands
+    }
+
+    /// Analyze mode is left with `exit`; a normal search is cut short with
+    /// `?`, which still makes the engine send its move.
+    pub(super) fn stop_command(&mut self) -> &'static str {
+        if std::mem::take(&mut self.analyzing) {
+            "exit\n"
+        } else {
+            "?\n"
+        }
+    }
+
+    pub(super) fn new_game(&mut self) -> &'static str {
+        self.analyzing = false;
+        self.excluding = false;
+        self.node_limited = false;
+        "new\nforce\n"
+    }
+
+    pub(super) fn parse_line(&self, line: &str, options: &EngineOptions) -> Result<EngineEvent, Error> {
+        let pos = position_after(&options.fen, &options.moves)?;
+        if let Some(best_moves) = parse_thinking(line, &pos) {
+            return Ok(EngineEvent::Info(best_moves));
+        }
+        if let Some(text) = line.strip_prefix("move ") {
+            let mv = parse_move(text.trim(), &pos)?;
+            return Ok(EngineEvent::BestMove {
+                best_move: mv.to_uci(CastlingMode::Standard).to_string(),
+                ponder: None,
+            });
+        }
+        Ok(EngineEvent::Other)
+    }
+}
+
+/// Whether a reply to `uci` shows the engine does not speak UCI.
+pub(super) fn rejects_uci(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    line.starts_with("error")
+        || line.starts_with("illegal move")
+        || line.starts_with("feature ")
+        || line.contains("unknown command")
+}
+
+/// Splits the arguments of a `feature` command into key/value pairs.
+fn parse_features(args: &str) -> Vec<(String, String)> {
+    let mut features = Vec::new();
+    let mut rest = args.trim_start();
+    while let Some((key, after)) = rest.split_once('=') {
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        features.push((key.trim().to_string(), value.to_string()));
+        rest = after.trim_start();
+    }
+    features
+}
+
+/// Parses the value of `feature option="..."`, e.g. `Hash -spin 64 1 1024`.
+fn parse_option(desc: &str) -> Option<UciOptionConfig> {
+    const KINDS: &[&str] = &[
+        "-button", "-save", "-reset", "-check", "-string", "-file", "-path", "-spin", "-slider",
+        "-combo",
+    ];
+    let (name, kind, args) = KINDS.iter().find_map(|kind| {
+        let (name, args) = desc.split_once(&format!(" {kind}"))?;
+        Some((name.trim().to_string(), *kind, args.trim()))
+    })?;
+
+    Some(match kind {
+        "-check" => UciOptionConfig::Check {
+            name,
+            default: Some(args == "1"),
+        },
+        "-string" | "-file" | "-path" => UciOptionConfig::String {
+            name,
+            default: Some(args.to_string()),
+        },
+        "-spin" | "-slider" => {
+            let mut values = args.split_whitespace().map(|v| v.parse::<i64>().ok());
+            UciOptionConfig::Spin {
+                name,
+                default: values.next().flatten(),
+                min: values.next().flatten(),
+                max: values.next().flatten(),
+            }
+        }
+        "-combo" => {
+            let choices: Vec<&str> = args.split("///").map(str::trim).collect();
+            UciOptionConfig::Combo {
+                name,
+                default: choices
+                    .iter()
+                    .find_map(|c| c.strip_prefix('*'))
+                    .or(choices.first().copied())
+                    .map(str::to_string),
+                var: choices
+                    .iter()
+                    .map(|c| c.trim_start_matches('*').to_string())
+                    .collect(),
+            }
+        }
+        _ => UciOptionConfig::Button { name },
+    })
+}
+
+/// Engines may send moves either in coordinate notation or in SAN.
+fn parse_move(text: &str, pos: &Chess) -> Result<Move, Error> {
+    match UciMove::from_ascii(text.as_bytes()) {
+        Ok(uci) => Ok(uci.to_move(pos)?),
+        Err(_) => Ok(San::from_ascii(text.as_bytes())?.to_move(pos)?),
+    }
+}
+
+/// Parses `post` output: `ply score time nodes pv`, optionally followed by
+/// `seldepth nps tbhits` and a tab before the PV. Scores are in centipawns
+/// for the side to move; time is in centiseconds.
+fn parse_thinking(line: &str, pos: &Chess) -> Option<BestMoves> {
+    let (head, pv) = match line.split_once('\t') {
+        Some((head, pv)) => (head, Some(pv)),
+        None => (line, None),
+    };
+    let mut fields = head.split_whitespace();
+    let depth: u32 = fields
+        .next()?
+        .trim_end_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()?;
+    let score: i32 = fields.next()?.parse().ok()?;
+    let centis: u64 = fields.next()?.parse().ok()?;
+    let nodes: u64 = fields.next()?.parse().ok()?;
+
+    let mut best_moves = BestMoves {
+        depth,
+        nodes,
+        nps: (nodes * 100).checked_div(centis).unwrap_or(0),
+        time: centis * 10,
+        score: Score {
+            value: if score.abs() >= MATE_THRESHOLD {
+                let plies = MATE_SCORE - score.abs();
+                ScoreValue::Mate(score.signum() * (plies + 1) / 2)
+            } else {
+                ScoreValue::Cp(score)
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let pv_tokens: Vec<&str> = match pv {
+        Some(pv) => {
+            // extended format: seldepth nps tbhits
+            let mut extra = fields.map(|v| v.parse::<u64>().ok());
+            if let Some(seldepth) = extra.next().flatten() {
+                best_moves.seldepth = seldepth as u32;
+            }
+            if let Some(nps) = extra.next().flatten() {
+                best_moves.nps = nps;
+            }
+            if let Some(tbhits) = extra.next().flatten() {
+                best_moves.tbhits = tbhits;
+            }
+            pv.split_whitespace().collect()
+        }
+        None => fields.collect(),
+    };
+
+    let mut temp_pos = pos.clone();
+    for token in pv_tokens {
+        // move numbers such as `12.` or `12...`
+        if token.ends_with('.') {
+            continue;
+        }
+        let Ok(mv) = parse_move(token, &temp_pos) else {
+            break;
+        };
+        best_moves
+            .uci_moves
+            .push(mv.to_uci(CastlingMode::Standard).to_string());
+        let san = SanPlus::from_move_and_play_unchecked(&mut temp_pos, &mv);
+        best_moves.san_moves.push(san.to_string());
+    }
+    if best_moves.uci_moves.is_empty() {
+        return None;
+    }
+
+    if pos.turn() == Color::Black {
+        best_moves.score = invert_score(best_moves.score);
+    }
+    Some(best_moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_features() {
+        let features =
+            parse_features(r#"myname="Crafty 25.2" setboard=1 option="Hash -spin 64 1 1024" done=1"#);
+        assert_eq!(
+            features,
+            vec![
+                ("myname".to_string(), "Crafty 25.2".to_string()),
+                ("setboard".to_string(), "1".to_string()),
+                ("option".to_string(), "Hash -spin 64 1 1024".to_string()),
+                ("done".to_string(), "1".to_string()),
+            ]
+        );
+
+        match parse_option("Playing Style -combo Solid /// *Normal /// Risky") {
+            Some(UciOptionConfig::Combo { name, default, var }) => {
+                assert_eq!(name, "Playing Style");
+                assert_eq!(default.as_deref(), Some("Normal"));
+                assert_eq!(var, vec!["Solid", "Normal", "Risky"]);
+            }
+            other => panic!("unexpected option {:?}", other),
+        }
+    }
+
+    #[test]
+    fn thinking_output_is_white_relative() {
+        let pos = position_after(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &["e2e4".to_string()],
+        )
+        .unwrap();
+        let best = parse_thinking("9 35 120 45000 1... e7e5 Nf3 Nc6 <HT>", &pos).unwrap();
+        assert_eq!(best.depth, 9);
+        assert!(matches!(best.score.value, ScoreValue::Cp(-35)));
+        assert_eq!(best.uci_moves, vec!["e7e5", "g1f3", "b8c6"]);
+        assert_eq!(best.san_moves, vec!["e5", "Nf3", "Nc6"]);
+        assert_eq!(best.nps, 37500);
+        assert_eq!(best.time, 1200);
+
+        assert!(parse_thinking("move e7e5", &pos).is_none());
+    }
+
+    #[test]
+    fn node_limits_use_nps() {
+        let mut xboard = Xboard {
+            features: XboardFeatures::default(),
+            analyzing: false,
+            excluding: false,
+            node_limited: false,
+        };
+        assert_eq!(
+            xboard.go_command(&GoMode::Nodes(500000), Color::White),
+            "nps 500000\nst 1\ngo\n"
+        );
+        assert_eq!(xboard.go_command(&GoMode::Time(2500), Color::White), "nps 0\nst 3\ngo\n");
+        assert_eq!(xboard.go_command(&GoMode::Infinite, Color::White), "analyze\n");
+        assert_eq!(xboard.stop_command(), "exit\n");
+
+        xboard.features.nps = false;
+        assert_eq!(
+            xboard.go_command(&GoMode::Nodes(2500000), Color::White),
+            "st 3\ngo\n"
+        );
+    }
+
+    #[test]
+    fn converts_mate_scores() {
+        let pos = Chess::default();
+        let best = parse_thinking("12 99997 50 1000 e2e4", &pos).unwrap();
+        assert!(matches!(best.score.value, ScoreValue::Mate(2)));
+    }
+}
@@ -45,6 +45,30 @@ CREATE TABLE Games (
     FOREIGN KEY(BlackID) REFERENCES Players
 );
 
+CREATE TABLE Evaluations (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    GameID INTEGER NOT NULL,
+    Ply INTEGER NOT NULL,
+    Engine TEXT NOT NULL,
+    Depth INTEGER NOT NULL,
+    Cp INTEGER,
+    Mate INTEGER,
+    WdlWin INTEGER,
+    WdlDraw INTEGER,
+    WdlLoss INTEGER,
+    BestMove TEXT,
+    UNIQUE(GameID, Ply, Engine),
+    FOREIGN KEY(GameID) REFERENCES Games ON DELETE CASCADE
+);
+
+CREATE INDEX evaluations_game_id ON Evaluations(GameID);
+
+CREATE TABLE GameSummaries (
+    GameID INTEGER PRIMARY KEY,
+    Summary TEXT NOT NULL,
+    FOREIGN KEY(GameID) REFERENCES Games ON DELETE CASCADE
+);
+
 INSERT INTO Players (ID, Name, Elo) VALUES (0, 'Unknown', NULL);
 INSERT INTO Events (ID, Name) VALUES (0, 'Unknown');
 INSERT INTO Sites (ID, Name) VALUES (0, 'Unknown');
\ No newline at end of file
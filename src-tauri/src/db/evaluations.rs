@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use diesel::{connection::SimpleConnection, prelude::*};
+
+use super::{
+    models::{Evaluation, NewEvaluation},
+    schema::evaluations,
+};
+use crate::error::{Error, Result};
+
+/// The table as in `create.sql`, for databases created before evaluations
+/// were stored.
+const CREATE_EVALUATIONS_SQL: &str = "CREATE TABLE IF NOT EXISTS Evaluations (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    GameID INTEGER NOT NULL,
+    Ply INTEGER NOT NULL,
+    Engine TEXT NOT NULL,
+    Depth INTEGER NOT NULL,
+    Cp INTEGER,
+    Mate INTEGER,
+    WdlWin INTEGER,
+    WdlDraw INTEGER,
+    WdlLoss INTEGER,
+    BestMove TEXT,
+    UNIQUE(GameID, Ply, Engine),
+    FOREIGN KEY(GameID) REFERENCES Games ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS evaluations_game_id ON Evaluations(GameID);";
+
+pub fn create_table(conn: &mut SqliteConnection) -> Result<()> {
+    conn.batch_execute(CREATE_EVALUATIONS_SQL)?;
+    Ok(())
+}
+
+/// Replaces the evaluations of every game and engine in `evals`.
+pub fn replace_evaluations(conn: &mut SqliteConnection, evals: &[NewEvaluation]) -> Result<()> {
+    if evals.is_empty() {
+        return Ok(());
+    }
+    conn.transaction::<_, Error, _>(|conn| {
+        let mut replaced: Vec<(i32, &str)> =
+            evals.iter().map(|e| (e.game_id, e.engine.as_str())).collect();
+        replaced.sort_unstable();
+        replaced.dedup();
+
+        for (game_id, engine) in replaced {
+            diesel::delete(
+                evaluations::table
+                    .filter(evaluations::game_id.eq(game_id))
+                    .filter(evaluations::engine.eq(engine)),
+            )
+            .execute(conn)?;
+        }
+        diesel::insert_into(evaluations::table)
+            .values(evals)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// A game's evaluations by ply, optionally from a single engine.
+pub fn game_evaluations(
+    conn: &mut SqliteConnection,
+    game_id: i32,
+    engine: Option<&str>,
+) -> Result<Vec<Evaluation>> {
+    let mut query = evaluations::table
+        .filter(evaluations::game_id.eq(game_id))
+        .into_boxed();
+    if let Some(engine) = engine {
+        query = query.filter(evaluations::engine.eq(engine.to_string()));
+    }
+    Ok(query
+        .order((evaluations::ply.asc(), evaluations::engine.asc()))
+        .select(Evaluation::as_select())
+        .load(conn)?)
+}
+
+/// The deepest evaluation of every ply, whatever the engine.
+pub fn deepest_by_ply(evals: Vec<Evaluation>) -> BTreeMap<i32, Evaluation> {
+    let mut deepest: BTreeMap<i32, Evaluation> = BTreeMap::new();
+    for eval in evals {
+        match deepest.get(&eval.ply) {
+            Some(current) if current.depth >= eval.depth => {}
+            _ => {
+                deepest.insert(eval.ply, eval);
+            }
+        }
+    }
+    deepest
+}
@@ -1,8 +1,10 @@
 mod encoding;
+mod evaluations;
 mod models;
 mod ops;
 mod schema;
 mod search;
+mod summaries;
 mod core;
 mod pgn;
 
@@ -47,11 +49,14 @@ use tauri::{Emitter, State};
 use log::info;
 use tauri_specta::Event as _;
 
-pub use self::models::NormalizedGame;
+pub use self::models::{NewEvaluation, NormalizedGame};
+pub use self::pgn::{format_eval, read_game_tree, MoveAnnotation, TreeMove};
+pub use self::models::Outcome;
 pub use self::models::Puzzle;
 pub use self::schema::puzzles;
 pub use self::search::{
-    is_position_in_db, search_position, PositionQuery, PositionQueryJs, PositionStats,
+    is_position_in_db, position_reference, search_position, PositionQuery, PositionQueryJs,
+    PositionReference, PositionStats,
 };
 
 const INDEXES_SQL: &str = include_str!("indexes.sql");
@@ -134,6 +139,7 @@ fn get_db_or_create(
                 .max_size(16)
                 .connection_customizer(Box::new(options))
                 .build(ConnectionManager::<SqliteConnection>::new(db_path))?;
+            upgrade_tables(&mut pool.get()?)?;
             state
                 .connection_pool
                 .insert(db_path.to_string(), pool.clone());
@@ -151,6 +157,24 @@ pub struct TempPlayer {
     rating: Option<i32>,
 }
 
+/// `[%eval]` comments for the moves of a stored game, from the deepest
+/// evaluation of each position.
+fn eval_annotations(evals: Vec<Evaluation>) -> Vec<MoveAnnotation> {
+    let deepest = evaluations::deepest_by_ply(evals);
+    let plies = deepest.keys().next_back().copied().unwrap_or(0).max(0) as usize;
+    let mut annotations = vec![MoveAnnotation::default(); plies];
+    for (ply, eval) in deepest {
+        // Ply 0 is the start position, which has no move to comment
+        if ply > 0 {
+            annotations[ply as usize - 1].eval = format_eval(eval.cp, eval.mate);
+        }
+    }
+    annotations
+}
+
+/// The engine recorded for evaluations imported from `[%eval]` comments.
+const PGN_EVAL_ENGINE: &str = "PGN";
+
 pub fn insert_to_db(db: &mut SqliteConnection, game: &TempGame) -> Result<()> {
     let pawn_home = get_pawn_home(game.position.board());
 
@@ -205,11 +229,39 @@ pub fn insert_to_db(db: &mut SqliteConnection, game: &TempGame) -> Result<()> {
         pawn_home: pawn_home as i32,
     };
 
-    core::add_game(db, new_game)?;
+    let inserted = core::add_game(db, new_game)?;
+
+    let evals: Vec<NewEvaluation> = game
+        .evals
+        .iter()
+        .map(|eval| NewEvaluation {
+            game_id: inserted.id,
+            ply: eval.ply,
+            engine: PGN_EVAL_ENGINE.to_string(),
+            depth: eval.depth.unwrap_or(0),
+            cp: eval.cp,
+            mate: eval.mate,
+            ..Default::default()
+        })
+        .collect();
+    evaluations::replace_evaluations(db, &evals)?;
 
     Ok(())
 }
 
+/// Imports a single PGN game into an existing database.
+pub(crate) fn import_pgn_game(state: &State<AppState>, file: &PathBuf, pgn: &str) -> Result<()> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+
+    let mut importer = Importer::new(None);
+    let game = BufferedReader::new_cursor(pgn.as_bytes())
+        .read_game(&mut importer)?
+        .flatten()
+        .ok_or(Error::NoMovesFound)?;
+
+    insert_to_db(db, &game)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn convert_pgn(
@@ -313,6 +365,25 @@ pub struct DatabaseInfo {
     indexed: bool,
 }
 
+#[derive(QueryableByName)]
+struct TableInfo {
+    #[diesel(sql_type = Text, column_name = "name")]
+    _name: String,
+}
+
+/// Adds the tables of newer versions to a database created by an older one,
+/// once per opened database. New databases get them from `create.sql`.
+fn upgrade_tables(conn: &mut SqliteConnection) -> Result<()> {
+    let games: Vec<TableInfo> =
+        sql_query("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'Games';")
+            .load(conn)?;
+    if games.is_empty() {
+        return Ok(());
+    }
+    evaluations::create_table(conn)?;
+    summaries::create_table(conn)
+}
+
 #[derive(QueryableByName, Debug, Serialize)]
 struct IndexInfo {
     #[diesel(sql_type = Text, column_name = "name")]
@@ -1167,25 +1238,25 @@ pub async fn delete_empty_games(
     Ok(())
 }
 
-struct PgnGame {
-    event: Option<String>,
-    site: Option<String>,
-    date: Option<String>,
-    round: Option<String>,
-    white: Option<String>,
-    black: Option<String>,
-    result: Option<String>,
-    time_control: Option<String>,
-    eco: Option<String>,
-    white_elo: Option<String>,
-    black_elo: Option<String>,
-    ply_count: Option<String>,
-    fen: Option<String>,
-    moves: String,
+pub(crate) struct PgnGame {
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub date: Option<String>,
+    pub round: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub time_control: Option<String>,
+    pub eco: Option<String>,
+    pub white_elo: Option<String>,
+    pub black_elo: Option<String>,
+    pub ply_count: Option<String>,
+    pub fen: Option<String>,
+    pub moves: String,
 }
 
 impl PgnGame {
-    fn write(&self, writer: &mut impl Write) -> Result<()> {
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
         writeln!(
             writer,
             "[Event \"{}\"]",
@@ -1250,9 +1321,19 @@ impl PgnGame {
 pub async fn export_to_pgn(
     file: PathBuf,
     dest_file: PathBuf,
+    include_evals: Option<bool>,
     state: tauri::State<'_, AppState>,
 ) -> Result<()> {
     let db = &mut get_db_or_create(&state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    // The games are streamed from `db`, so evaluations need a connection of their own
+    let mut evals_db = match include_evals {
+        Some(true) => Some(get_db_or_create(
+            &state,
+            file.to_str().unwrap(),
+            ConnectionOptions::default(),
+        )?),
+        _ => None,
+    };
 
     let file = OpenOptions::new()
         .create(true)
@@ -1271,6 +1352,16 @@ pub async fn export_to_pgn(
         .load_iter::<(Game, Player, Player, Event, Site), DefaultLoadingMode>(db)?
         .flatten()
         .map(|(game, white, black, event, site)| {
+            let position = game.fen
+                .as_ref()
+                .and_then(|fen| Fen::from_ascii(fen.as_bytes()).ok())
+                .and_then(|fen| Chess::from_setup(fen.into(), CastlingMode::Chess960).ok());
+            let mut tree = GameTree::from_bytes(&game.moves, position)?;
+            if let Some(evals_db) = evals_db.as_mut() {
+                let evals = evaluations::game_evaluations(evals_db, game.id, None)?;
+                tree.annotate(&eval_annotations(evals));
+            }
+
             let pgn = PgnGame {
                 event: event.name,
                 site: site.name,
@@ -1285,14 +1376,7 @@ pub async fn export_to_pgn(
                 black_elo: game.black_elo.map(|e| e.to_string()),
                 ply_count: game.ply_count.map(|e| e.to_string()),
                 fen: game.fen.clone(),
-                 moves: GameTree::from_bytes(
-                    &game.moves,
-                    game.fen
-                        .map(|fen| Fen::from_ascii(fen.as_bytes()).ok())
-                        .flatten()
-                        .map(|fen| Chess::from_setup(fen.into(), CastlingMode::Chess960).ok())
-                        .flatten()
-                )?.to_string(),
+                moves: tree.to_string(),
             };
 
             pgn.write(&mut writer)?;
@@ -1344,6 +1428,113 @@ pub async fn update_game(
     Ok(())
 }
 
+/// Writes engine annotations into the main line of a stored game, see
+/// `GameTree::annotate`.
+pub fn annotate_game(
+    state: &State<AppState>,
+    file: &PathBuf,
+    game_id: i32,
+    annotations: &[MoveAnnotation],
+) -> Result<()> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+
+    let (moves, fen): (Vec<u8>, Option<String>) = games::table
+        .filter(games::id.eq(game_id))
+        .select((games::moves, games::fen))
+        .first(db)?;
+    let position = fen
+        .and_then(|fen| Fen::from_ascii(fen.as_bytes()).ok())
+        .and_then(|fen| Chess::from_setup(fen.into(), CastlingMode::Chess960).ok());
+
+    let mut tree = GameTree::from_bytes(&moves, position.clone())?;
+    tree.annotate(annotations);
+    let mut bytes = Vec::new();
+    tree.encode(&mut bytes, position);
+
+    diesel::update(games::table.filter(games::id.eq(game_id)))
+        .set(games::moves.eq(bytes))
+        .execute(db)?;
+
+    Ok(())
+}
+
+/// A stored game's start position as a FEN and its main line moves in UCI
+/// notation.
+pub fn game_main_line(
+    state: &State<AppState>,
+    file: &PathBuf,
+    game_id: i32,
+) -> Result<(String, Vec<String>)> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+
+    let (moves, fen): (Vec<u8>, Option<String>) = games::table
+        .filter(games::id.eq(game_id))
+        .select((games::moves, games::fen))
+        .first(db)?;
+    let position: Chess = match fen {
+        Some(fen) => Fen::from_ascii(fen.as_bytes())?.into_position(CastlingMode::Chess960)?,
+        None => Chess::default(),
+    };
+
+    let tree = GameTree::from_bytes(&moves, Some(position.clone()))?;
+    let fen = Fen::from_position(position.clone(), EnPassantMode::Legal).to_string();
+    Ok((fen, tree.main_line_uci(position)?))
+}
+
+/// Replaces the stored evaluations of every game and engine in `evals`.
+pub fn store_evaluations(
+    state: &State<AppState>,
+    file: &PathBuf,
+    evals: &[NewEvaluation],
+) -> Result<()> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    evaluations::replace_evaluations(db, evals)
+}
+
+/// Stores engine evaluations, replacing earlier ones of the same games by
+/// the same engines.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_evaluations(
+    file: PathBuf,
+    evaluations: Vec<NewEvaluation>,
+    state: tauri::State<'_, AppState>,
+) -> Result<()> {
+    store_evaluations(&state, &file, &evaluations)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_evaluations(
+    file: PathBuf,
+    game_id: i32,
+    engine: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<Evaluation>> {
+    let db = &mut get_db_or_create(&state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    evaluations::game_evaluations(db, game_id, engine.as_deref())
+}
+
+/// Stores a game's analysis summary, serialized by the caller.
+pub fn store_game_summary(
+    state: &State<AppState>,
+    file: &PathBuf,
+    game_id: i32,
+    summary: &str,
+) -> Result<()> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    summaries::replace_summary(db, game_id, summary)
+}
+
+pub fn load_game_summary(
+    state: &State<AppState>,
+    file: &PathBuf,
+    game_id: i32,
+) -> Result<Option<String>> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    summaries::game_summary(db, game_id)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn merge_players(
@@ -1393,7 +1584,7 @@ pub async fn merge_players(
 #[specta::specta]
 pub fn clear_games(state: tauri::State<'_, AppState>) {
     let mut state = state.db_cache.lock().unwrap();
-    state.clear();
+    state.games.clear();
 }
 
 #[cfg(test)]
@@ -118,13 +118,57 @@ pub struct NewEvent<'a> {
     pub name: &'a str,
 }
 
+/// An engine's evaluation of the position after `ply` main line moves
+/// (0 is the start position), white-relative.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Type)]
+#[diesel(table_name = evaluations)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    pub id: i32,
+    pub game_id: i32,
+    pub ply: i32,
+    pub engine: String,
+    pub depth: i32,
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+    pub wdl_win: Option<i32>,
+    pub wdl_draw: Option<i32>,
+    pub wdl_loss: Option<i32>,
+    /// In UCI notation.
+    pub best_move: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone, Default, Deserialize, Type)]
+#[diesel(table_name = evaluations)]
+#[serde(rename_all = "camelCase")]
+pub struct NewEvaluation {
+    pub game_id: i32,
+    pub ply: i32,
+    pub engine: String,
+    pub depth: i32,
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+    pub wdl_win: Option<i32>,
+    pub wdl_draw: Option<i32>,
+    pub wdl_loss: Option<i32>,
+    pub best_move: Option<String>,
+}
+
+/// A game's analysis summary, serialized as JSON.
+#[derive(Insertable, Debug)]
+#[diesel(table_name = game_summaries)]
+pub struct NewGameSummary<'a> {
+    pub game_id: i32,
+    pub summary: &'a str,
+}
+
 #[derive(Queryable, Serialize, Deserialize)]
 pub struct Info {
     pub name: String,
     pub value: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Default, Type, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Type, Eq, PartialEq, Hash)]
 pub enum Outcome {
     #[serde(rename = "1-0")]
     WhiteWin,
@@ -163,7 +207,7 @@ impl std::fmt::Display for Outcome {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Type)]
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
 pub struct NormalizedGame {
     pub id: i32,
     pub fen: String,
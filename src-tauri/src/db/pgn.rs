@@ -1,10 +1,57 @@
 use shakmaty::{
-    fen::Fen, ByColor, Chess, FromSetup, Position, PositionError, Board
+    fen::Fen, ByColor, Chess, FromSetup, Position, PositionError, Board, CastlingMode
 };
-use pgn_reader::{Nag, RawComment, RawHeader, SanPlus, Skip, Visitor};
+use pgn_reader::{BufferedReader, Nag, RawComment, RawHeader, SanPlus, Skip, Visitor};
 use chrono::{NaiveDate, NaiveTime};
+use lazy_static::lazy_static;
+use regex::Regex;
 use crate::error::{Error, Result};
 
+lazy_static! {
+    static ref EVAL_COMMENT: Regex = Regex::new(r"\[%eval [^\]]*\]").unwrap();
+    /// `[%eval 0.17]`, `[%eval #-3]`, optionally followed by the depth
+    /// (`[%eval 0.17,23]`).
+    static ref EVAL_VALUE: Regex =
+        Regex::new(r"\[%eval\s+(#)?([+-]?\d+(?:\.\d+)?)(?:,(\d+))?\s*\]").unwrap();
+}
+
+/// A `[%eval]` read from a main line comment, white-relative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnEval {
+    /// Main line moves before the comment.
+    pub ply: i32,
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+    pub depth: Option<i32>,
+}
+
+/// Parses the `[%eval]` of a comment, leaving the ply to the caller.
+fn parse_eval(comment: &str) -> Option<PgnEval> {
+    let caps = EVAL_VALUE.captures(comment)?;
+    let value = caps.get(2)?.as_str();
+    let (cp, mate) = if caps.get(1).is_some() {
+        (None, Some(value.parse().ok()?))
+    } else {
+        let pawns: f64 = value.parse().ok()?;
+        (Some((pawns * 100.0).round() as i32), None)
+    };
+    Some(PgnEval {
+        ply: 0,
+        cp,
+        mate,
+        depth: caps.get(3).and_then(|depth| depth.as_str().parse().ok()),
+    })
+}
+
+/// The `[%eval]` value of a white-relative score: pawns, or `#n` for mates.
+pub fn format_eval(cp: Option<i32>, mate: Option<i32>) -> Option<String> {
+    match (mate, cp) {
+        (Some(mate), _) => Some(format!("#{}", mate)),
+        (None, Some(cp)) => Some(format!("{:.2}", cp as f64 / 100.0)),
+        (None, None) => None,
+    }
+}
+
 pub type MaterialCount = ByColor<u8>;
 
 pub fn get_material_count(board: &Board) -> MaterialCount {
@@ -28,11 +75,39 @@ pub enum GameTreeNode {
 #[derive(Debug, PartialEq, Eq, Default)]
 pub struct GameTree(Vec<GameTreeNode>);
 
+/// Engine annotations for one main line move, see `GameTree::annotate`.
+#[derive(Debug, Clone, Default)]
+pub struct MoveAnnotation {
+    /// Replaces the move's quality NAG.
+    pub nag: Option<u8>,
+    /// Written as `[%eval ...]` in front of the move's comment, replacing
+    /// an earlier evaluation.
+    pub eval: Option<String>,
+    /// Appended to the move's comment, unless it already has it.
+    pub note: Option<String>,
+    /// Added after the move's variations, unless one starts the same way.
+    pub variation: Vec<SanPlus>,
+}
+
+/// A move anywhere in a game tree, addressed like the frontend addresses
+/// tree nodes: by the child index at every level, where 0 continues the
+/// line and higher indices are variations.
+#[derive(Debug, Clone)]
+pub struct TreeMove {
+    pub path: Vec<usize>,
+    /// Moves from the start position up to and including this one.
+    pub uci_moves: Vec<String>,
+    /// The position after the move.
+    pub position: Chess,
+}
+
 impl GameTree {
     const START_VARIATION: u8 = 254;
     const END_VARIATION: u8 = 253;
     const COMMENT: u8 = 252;
     const NAG: u8 = 251; 
+    /// NAGs judging the move itself, from `!` to `□`.
+    const MOVE_NAGS: std::ops::RangeInclusive<u8> = 1..=7;
 
 
     pub fn new() -> Self {
@@ -52,6 +127,19 @@ impl GameTree {
             .sum()
     }
  
+    /// The main line moves in UCI notation.
+    pub fn main_line_uci(&self, mut position: Chess) -> Result<Vec<String>> {
+        let mut moves = Vec::with_capacity(self.count_main_line_moves());
+        for node in &self.0 {
+            if let GameTreeNode::Move(san) = node {
+                let mv = san.san.to_move(&position)?;
+                moves.push(mv.to_uci(CastlingMode::Standard).to_string());
+                position.play_unchecked(&mv);
+            }
+        }
+        Ok(moves)
+    }
+
     pub fn encode(&self, bytes: &mut Vec<u8>, position: Option<Chess>) {
         let mut cur_position = position.unwrap_or_default();
         let mut prev_position = cur_position.clone();
@@ -134,6 +222,147 @@ impl GameTree {
         Ok(Self(Self::from_bytes_impl(bytes, position.unwrap_or_default())?.0))
     }
 
+    /// Writes `annotations[i]` to the `i`-th main line move.
+    pub fn annotate(&mut self, annotations: &[MoveAnnotation]) {
+        let mut nodes = Vec::with_capacity(self.0.len());
+        let mut items = std::mem::take(&mut self.0).into_iter().peekable();
+        let mut ply = 0;
+
+        while let Some(item) = items.next() {
+            let is_move = matches!(item, GameTreeNode::Move(_));
+            nodes.push(item);
+            if !is_move {
+                continue;
+            }
+            let Some(annotation) = annotations.get(ply) else {
+                ply += 1;
+                continue;
+            };
+            ply += 1;
+
+            let mut nags = Vec::new();
+            let mut comment: Option<String> = None;
+            while let Some(item) = items.next_if(|item| {
+                matches!(item, GameTreeNode::Nag(_) | GameTreeNode::Comment(_))
+            }) {
+                match item {
+                    GameTreeNode::Nag(nag) => nags.push(nag),
+                    GameTreeNode::Comment(text) => {
+                        comment = Some(match comment {
+                            Some(previous) => format!("{previous} {text}"),
+                            None => text,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(nag) = annotation.nag {
+                nags.retain(|existing| !Self::MOVE_NAGS.contains(&existing.0));
+                nags.insert(0, Nag(nag));
+            }
+            nodes.extend(nags.into_iter().map(GameTreeNode::Nag));
+
+            if let Some(eval) = &annotation.eval {
+                let rest = comment
+                    .as_deref()
+                    .map(|text| EVAL_COMMENT.replace_all(text, "").trim().to_string())
+                    .unwrap_or_default();
+                comment = Some(if rest.is_empty() {
+                    format!("[%eval {eval}]")
+                } else {
+                    format!("[%eval {eval}] {rest}")
+                });
+            }
+            if let Some(note) = &annotation.note {
+                comment = match comment {
+                    Some(text) if text.contains(note.as_str()) => Some(text),
+                    Some(text) => Some(format!("{text} {note}")),
+                    None => Some(note.clone()),
+                };
+            }
+            nodes.extend(comment.map(GameTreeNode::Comment));
+
+            let mut has_variation = false;
+            while let Some(item) =
+                items.next_if(|item| matches!(item, GameTreeNode::Variation(_)))
+            {
+                if let GameTreeNode::Variation(branch) = &item {
+                    has_variation |= matches!(
+                        (branch.0.first(), annotation.variation.first()),
+                        (Some(GameTreeNode::Move(existing)), Some(first)) if existing == first
+                    );
+                }
+                nodes.push(item);
+            }
+            if !annotation.variation.is_empty() && !has_variation {
+                let branch = annotation.variation.iter().cloned().map(GameTreeNode::Move).collect();
+                nodes.push(GameTreeNode::Variation(GameTree(branch)));
+            }
+        }
+        self.0 = nodes;
+    }
+
+    /// Every move of the tree, variations included, in PGN order.
+    pub fn moves(&self, position: Chess) -> Result<Vec<TreeMove>> {
+        let mut moves = Vec::new();
+        self.collect_moves(position, Vec::new(), Vec::new(), 0, &mut moves)?;
+        Ok(moves)
+    }
+
+    /// Collects the moves of a line starting after `path`, whose first move
+    /// is child `first_index`. Returns how many alternatives to the first
+    /// move the line contains, as they take the next child indices.
+    fn collect_moves(
+        &self,
+        position: Chess,
+        path: Vec<usize>,
+        uci_moves: Vec<String>,
+        first_index: usize,
+        moves: &mut Vec<TreeMove>,
+    ) -> Result<usize> {
+        let mut parent = (position.clone(), path.clone(), uci_moves.clone());
+        let mut current = (position, path, uci_moves);
+        let mut played = 0;
+        let mut alternatives = 0;
+        let mut first_alternatives = 0;
+
+        for item in &self.0 {
+            match item {
+                GameTreeNode::Move(m) => {
+                    let mv = m.san.to_move(&current.0)?;
+                    parent = current.clone();
+                    current.0.play_unchecked(&mv);
+                    current.1.push(if played == 0 { first_index } else { 0 });
+                    current.2.push(mv.to_uci(CastlingMode::Standard).to_string());
+                    moves.push(TreeMove {
+                        path: current.1.clone(),
+                        uci_moves: current.2.clone(),
+                        position: current.0.clone(),
+                    });
+                    played += 1;
+                    alternatives = 0;
+                }
+                GameTreeNode::Variation(branch) if played > 0 => {
+                    let index = current.1.last().copied().unwrap_or(0) + 1 + alternatives;
+                    let nested = branch.collect_moves(
+                        parent.0.clone(),
+                        parent.1.clone(),
+                        parent.2.clone(),
+                        index,
+                        moves,
+                    )?;
+                    alternatives += 1 + nested;
+                    if played == 1 {
+                        first_alternatives = alternatives;
+                    }
+                }
+                GameTreeNode::Variation(_) | GameTreeNode::Nag(_) | GameTreeNode::Comment(_) => {}
+            }
+        }
+        Ok(first_alternatives)
+    }
+
     pub fn pretty_print(&self, writer: &mut std::fmt::Formatter<'_>, position: Option<Chess>) -> Result<()> {
         let mut cur_position = position.unwrap_or_default();
         let mut prev_position = cur_position.clone();
@@ -215,6 +444,8 @@ pub struct TempGame {
     pub position: Chess,
     pub material_count: ByColor<u8>,
     pub tree: GameTree,
+    /// Engine evaluations found in main line comments.
+    pub evals: Vec<PgnEval>,
 }
 
 pub struct Importer {
@@ -222,6 +453,8 @@ pub struct Importer {
     variants: Vec<GameTree>,
     timestamp: Option<i64>,
     skip: bool,
+    /// Main line moves read so far.
+    ply: i32,
 }
 
 
@@ -232,6 +465,7 @@ impl Importer {
             variants: Vec::new(),
             timestamp,
             skip: false,
+            ply: 0,
         }
     }
 
@@ -247,6 +481,7 @@ impl Visitor for Importer {
 
     fn begin_game(&mut self) {
         self.skip = false;
+        self.ply = 0;
     }
 
     fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
@@ -320,11 +555,22 @@ impl Visitor for Importer {
     }
 
     fn san(&mut self, san: SanPlus) {
+        if self.variants.is_empty() {
+            self.ply += 1;
+        }
         self.active_branch().push(GameTreeNode::Move(san));
     }
 
     fn comment(&mut self, comment: RawComment<'_>) {
         if let Ok(comment) = String::from_utf8(comment.as_bytes().to_owned()) {
+            if self.variants.is_empty() {
+                if let Some(eval) = parse_eval(&comment) {
+                    self.game.evals.push(PgnEval {
+                        ply: self.ply,
+                        ..eval
+                    });
+                }
+            }
             self.active_branch().push(GameTreeNode::Comment(comment));
         }
     }
@@ -372,6 +618,15 @@ impl Visitor for Importer {
     }
 }
 
+/// Reads the first game of a PGN.
+pub fn read_game_tree(pgn: &str) -> Result<TempGame> {
+    let mut reader = BufferedReader::new_cursor(pgn.as_bytes());
+    let mut importer = Importer::new(None);
+    reader
+        .read_game(&mut importer)?
+        .flatten()
+        .ok_or(Error::InvalidPgn)
+}
 
 #[cfg(test)]
 mod test {
@@ -554,4 +809,107 @@ mod test {
         );
         assert_eq!(trim(&game.tree.to_string()), trim(pgn));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tree_move_paths() {
+        let paths = |pgn: &str| -> Vec<(String, Vec<usize>)> {
+            let game = read_game_tree(pgn).unwrap();
+            game.tree
+                .moves(game.position)
+                .unwrap()
+                .into_iter()
+                .map(|m| (m.uci_moves.last().unwrap().clone(), m.path))
+                .collect()
+        };
+
+        assert_eq!(
+            paths("1.e4 e5 2.Nf3 ( 2.Bc4 c6 ) ( 2.d4 ) 2...Nc6 *"),
+            vec![
+                ("e2e4".to_string(), vec![0]),
+                ("e7e5".to_string(), vec![0, 0]),
+                ("g1f3".to_string(), vec![0, 0, 0]),
+                ("f1c4".to_string(), vec![0, 0, 1]),
+                ("c7c6".to_string(), vec![0, 0, 1, 0]),
+                ("d2d4".to_string(), vec![0, 0, 2]),
+                ("b8c6".to_string(), vec![0, 0, 0, 0]),
+            ]
+        );
+
+        // An alternative nested in a variation is another child of the same node
+        assert_eq!(
+            paths("1.e4 ( 1.d4 ( 1.c4 ) 1...d5 ) ( 1.Nf3 ) 1...e5 *"),
+            vec![
+                ("e2e4".to_string(), vec![0]),
+                ("d2d4".to_string(), vec![1]),
+                ("c2c4".to_string(), vec![2]),
+                ("d7d5".to_string(), vec![1, 0]),
+                ("g1f3".to_string(), vec![3]),
+                ("e7e5".to_string(), vec![0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_annotate_main_line() {
+        let mut game = read_game_tree("1.e4 e5 2.Qh5 $1 {[%eval 0.1] Bold} ( 2.Nf3 ) 2...Nc6 *").unwrap();
+        let san = |s: &str| SanPlus::from_ascii(s.as_bytes()).unwrap();
+        let annotations = [
+            MoveAnnotation {
+                eval: Some("0.3".to_string()),
+                ..Default::default()
+            },
+            MoveAnnotation {
+                note: Some("Main line".to_string()),
+                ..Default::default()
+            },
+            MoveAnnotation {
+                nag: Some(6),
+                eval: Some("0.0".to_string()),
+                variation: vec![san("Nf3"), san("Nc6")],
+                ..Default::default()
+            },
+            MoveAnnotation {
+                nag: Some(4),
+                eval: Some("#3".to_string()),
+                variation: vec![san("g6")],
+                ..Default::default()
+            },
+        ];
+        game.tree.annotate(&annotations);
+        assert_eq!(
+            trim(&game.tree.to_string()).trim(),
+            trim("1.e4 {[%eval 0.3]} e5 {Main line} 2.Qh5 $6 {[%eval 0.0] Bold} ( 2.Nf3 ) 2...Nc6 $4 {[%eval #3]} ( 2...g6 )")
+        );
+
+        // Annotating again changes nothing
+        let annotated = game.tree.to_string();
+        game.tree.annotate(&annotations);
+        assert_eq!(game.tree.to_string(), annotated);
+    }
+
+    #[test]
+    fn test_parse_evals() {
+        assert_eq!(
+            parse_eval("[%eval 0.17] [%clk 0:03:00]"),
+            Some(PgnEval { ply: 0, cp: Some(17), mate: None, depth: None })
+        );
+        assert_eq!(
+            parse_eval("[%eval #-3]"),
+            Some(PgnEval { ply: 0, cp: None, mate: Some(-3), depth: None })
+        );
+        assert_eq!(
+            parse_eval("[%eval -1.5,23]"),
+            Some(PgnEval { ply: 0, cp: Some(-150), mate: None, depth: Some(23) })
+        );
+        assert_eq!(parse_eval("Good move"), None);
+        assert_eq!(format_eval(Some(-150), None).as_deref(), Some("-1.50"));
+        assert_eq!(format_eval(None, Some(-3)).as_deref(), Some("#-3"));
+
+        let game = read_game_tree(
+            "1.e4 { [%eval 0.3] } e5 ( 1...c5 { [%eval 0.4] } ) 2.Qh5 { [%eval #2] } *",
+        )
+        .unwrap();
+        let plies: Vec<_> = game.evals.iter().map(|eval| (eval.ply, eval.cp, eval.mate)).collect();
+        assert_eq!(plies, vec![(1, Some(30), None), (3, None, Some(2))]);
+    }
+}
@@ -110,7 +110,56 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    #[sql_name = "Evaluations"]
+    evaluations (id) {
+        #[sql_name = "ID"]
+        id -> Integer,
+        #[sql_name = "GameID"]
+        game_id -> Integer,
+        #[sql_name = "Ply"]
+        ply -> Integer,
+        #[sql_name = "Engine"]
+        engine -> Text,
+        #[sql_name = "Depth"]
+        depth -> Integer,
+        #[sql_name = "Cp"]
+        cp -> Nullable<Integer>,
+        #[sql_name = "Mate"]
+        mate -> Nullable<Integer>,
+        #[sql_name = "WdlWin"]
+        wdl_win -> Nullable<Integer>,
+        #[sql_name = "WdlDraw"]
+        wdl_draw -> Nullable<Integer>,
+        #[sql_name = "WdlLoss"]
+        wdl_loss -> Nullable<Integer>,
+        #[sql_name = "BestMove"]
+        best_move -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    #[sql_name = "GameSummaries"]
+    game_summaries (game_id) {
+        #[sql_name = "GameID"]
+        game_id -> Integer,
+        #[sql_name = "Summary"]
+        summary -> Text,
+    }
+}
+
+diesel::joinable!(evaluations -> games (game_id));
+diesel::joinable!(game_summaries -> games (game_id));
 diesel::joinable!(games -> events (event_id));
 diesel::joinable!(games -> sites (site_id));
 
-diesel::allow_tables_to_appear_in_same_query!(comments, events, games, info, players, sites,);
+diesel::allow_tables_to_appear_in_same_query!(
+    comments,
+    evaluations,
+    events,
+    game_summaries,
+    games,
+    info,
+    players,
+    sites,
+);
@@ -6,10 +6,10 @@ use serde::{Deserialize, Serialize};
 use shakmaty::{fen::Fen, san::SanPlus, Bitboard, ByColor, Chess, FromSetup, Position, Setup};
 use specta::Type;
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Mutex,
+        Mutex, MutexGuard,
     },
     time::Instant,
 };
@@ -17,12 +17,12 @@ use tauri::Emitter;
 
 use crate::{
     db::{
-        encoding::decode_move, get_db_or_create, get_pawn_home, models::*,
+        core::get_game, encoding::decode_move, get_db_or_create, get_pawn_home, models::*,
         pgn::{get_material_count, MaterialCount},
         normalize_games, schema::*, ConnectionOptions,
     },
     error::Error,
-    AppState,
+    AppState, GameCache, GameData,
 };
 
 use super::GameQueryJs;
@@ -192,6 +192,52 @@ fn get_move_after_match(
     Ok(None)
 }
 
+/// The games of `db`, stored at `file`, loaded into the cache unless it
+/// already holds them. The cache only keeps the database searched last.
+fn load_games<'a>(
+    state: &'a AppState,
+    file: &Path,
+    db: &mut SqliteConnection,
+) -> Result<MutexGuard<'a, GameCache>, Error> {
+    let start = Instant::now();
+    let mut cache = state.db_cache.lock().unwrap();
+
+    if cache.file != file || cache.games.is_empty() {
+        cache.file = file.to_path_buf();
+        cache.games = games::table
+            .select((
+                games::id,
+                games::white_id,
+                games::black_id,
+                games::date,
+                games::result,
+                games::moves,
+                games::fen,
+                games::pawn_home,
+                games::white_material,
+                games::black_material,
+            ))
+            .load(db)?;
+
+        info!("got {} games: {:?}", cache.games.len(), start.elapsed());
+    }
+    Ok(cache)
+}
+
+/// The move played after `query` in a cached game, `"*"` if the game ends
+/// there, or `None` if the game never reaches it.
+fn move_after_query(game: &GameData, query: &PositionQuery) -> Option<String> {
+    let (_, _, _, _, _, moves, fen, end_pawn_home, white_material, black_material) = game;
+    let end_material: MaterialCount = ByColor {
+        white: *white_material as u8,
+        black: *black_material as u8,
+    };
+    if !query.can_reach(&end_material, *end_pawn_home as u16) {
+        return None;
+    }
+    get_move_after_match(moves, fen, query).unwrap_or(None)
+}
+
 #[derive(Clone, serde::Serialize)]
 pub struct ProgressPayload {
     pub progress: f64,
@@ -219,26 +265,8 @@ pub async fn search_position(
     info!("start loading games");
 
     let permit = state.new_request.acquire().await.unwrap();
-    let mut games = state.db_cache.lock().unwrap();
-
-    if games.is_empty() {
-        *games = games::table
-            .select((
-                games::id,
-                games::white_id,
-                games::black_id,
-                games::date,
-                games::result,
-                games::moves,
-                games::fen,
-                games::pawn_home,
-                games::white_material,
-                games::black_material,
-            ))
-            .load(db)?;
-
-        info!("got {} games: {:?}", games.len(), start.elapsed());
-    }
+    let cache = load_games(&state, &file, db)?;
+    let games = &cache.games;
 
     let openings: DashMap<String, PositionStats> = DashMap::new();
     let sample_games: Mutex<Vec<i32>> = Mutex::new(Vec::new());
@@ -247,138 +275,120 @@ pub async fn search_position(
 
     println!("start search on {tab_id}");
 
-    games.par_iter().for_each(
-        |(
-            id,
-            white_id,
-            black_id,
-            date,
-            result,
-            game,
-            fen,
-            end_pawn_home,
-            white_material,
-            black_material,
-        )| {
-            if state.new_request.available_permits() == 0 {
-                return;
-            }
-            let end_material: MaterialCount = ByColor {
-                white: *white_material as u8,
-                black: *black_material as u8,
-            };
-            processed.fetch_add(1, Ordering::Relaxed);
-            let index = processed.load(Ordering::Relaxed);
-            if (index + 1) % 10000 == 0 {
-                info!("{} games processed: {:?}", index + 1, start.elapsed());
-                app.emit(
-                    "search_progress",
-                    ProgressPayload {
-                        progress: (index as f64 / games.len() as f64) * 100.0,
-                        id: tab_id.clone(),
-                        finished: false,
-                    },
-                )
-                .unwrap();
-            }
+    games.par_iter().for_each(|row| {
+        let (id, white_id, black_id, date, result, ..) = row;
+        if state.new_request.available_permits() == 0 {
+            return;
+        }
+        processed.fetch_add(1, Ordering::Relaxed);
+        let index = processed.load(Ordering::Relaxed);
+        if (index + 1) % 10000 == 0 {
+            info!("{} games processed: {:?}", index + 1, start.elapsed());
+            app.emit(
+                "search_progress",
+                ProgressPayload {
+                    progress: (index as f64 / games.len() as f64) * 100.0,
+                    id: tab_id.clone(),
+                    finished: false,
+                },
+            )
+            .unwrap();
+        }
 
-            if let Some(start_date) = &query.start_date {
-                if let Some(date) = date {
-                    if date < start_date {
-                        return;
-                    }
+        if let Some(start_date) = &query.start_date {
+            if let Some(date) = date {
+                if date < start_date {
+                    return;
                 }
             }
+        }
 
-            if let Some(end_date) = &query.end_date {
-                if let Some(date) = date {
-                    if date > end_date {
-                        return;
-                    }
+        if let Some(end_date) = &query.end_date {
+            if let Some(date) = date {
+                if date > end_date {
+                    return;
                 }
             }
+        }
 
-            if let Some(white) = query.player1 {
-                if white != *white_id {
-                    return;
-                }
+        if let Some(white) = query.player1 {
+            if white != *white_id {
+                return;
             }
+        }
 
-            if let Some(black) = query.player2 {
-                if black != *black_id {
-                    return;
-                }
+        if let Some(black) = query.player2 {
+            if black != *black_id {
+                return;
             }
+        }
 
-            if let Some(result) = result {
-                if let Some(wanted_result) = &query.wanted_result {
-                    match wanted_result.as_str() {
-                        "whitewon" => {
-                            if result != "1-0" {
-                                return
-                            } 
-                        }
-                        "blackwon" => {
-                            if result != "0-1" {
-                                return
-                            } 
-                        }
-                        "draw" => {
-                            if result != "1/2-1/2" {
-                                return
-                            } 
-                        }
-                        &_ => {}
+        if let Some(result) = result {
+            if let Some(wanted_result) = &query.wanted_result {
+                match wanted_result.as_str() {
+                    "whitewon" => {
+                        if result != "1-0" {
+                            return
+                        } 
                     }
+                    "blackwon" => {
+                        if result != "0-1" {
+                            return
+                        } 
+                    }
+                    "draw" => {
+                        if result != "1/2-1/2" {
+                            return
+                        } 
+                    }
+                    &_ => {}
                 }
             }
+        }
 
-            if let Some(position_query) = &query.position {
-                    let position_query = match convert_position_query(position_query.clone()) {
-                        Ok(val) => val,
-                        Err(_) => return,
-                    };
-                if position_query.can_reach(&end_material, *end_pawn_home as u16) {
-                    if let Ok(Some(m)) = get_move_after_match(game, fen, &position_query) {
-                        let mut guard = match sample_games.lock() {
-                            Ok(guard) => guard,
-                            Err(_) => return,
-                        };
-                        if guard.len() < 10 {
-                            guard.push(*id);
+        if let Some(position_query) = &query.position {
+                let position_query = match convert_position_query(position_query.clone()) {
+                    Ok(val) => val,
+                    Err(_) => return,
+                };
+            if let Some(m) = move_after_query(row, &position_query) {
+                let mut guard = match sample_games.lock() {
+                    Ok(guard) => guard,
+                    Err(_) => return,
+                };
+                if guard.len() < 10 {
+                    guard.push(*id);
+                }
+                let entry = openings.entry(m);
+                match entry {
+                    Entry::Occupied(mut e) => {
+                        let opening = e.get_mut();
+                        match result.as_deref() {
+                            Some("1-0") => opening.white += 1,
+                            Some("0-1") => opening.black += 1,
+                            Some("1/2-1/2") => opening.draw += 1,
+                            _ => (),
                         }
-                        let entry = openings.entry(m);
-                        match entry {
-                            Entry::Occupied(mut e) => {
-                                let opening = e.get_mut();
-                                match result.as_deref() {
-                                    Some("1-0") => opening.white += 1,
-                                    Some("0-1") => opening.black += 1,
-                                    Some("1/2-1/2") => opening.draw += 1,
-                                    _ => (),
-                                }
-                            }
-                            Entry::Vacant(e) => {
-                                let mut opening = PositionStats {
-                                    black: 0,
-                                    white: 0,
-                                    draw: 0,
-                                    move_: e.key().to_string(),
-                                };
-                                match result.as_deref() {
-                                    Some("1-0") => opening.white = 1,
-                                    Some("0-1") => opening.black = 1,
-                                    Some("1/2-1/2") => opening.draw = 1,
-                                    _ => (),
-                                }
-                                e.insert(opening);
-                            }
+                    }
+                    Entry::Vacant(e) => {
+                        let mut opening = PositionStats {
+                            black: 0,
+                            white: 0,
+                            draw: 0,
+                            move_: e.key().to_string(),
+                        };
+                        match result.as_deref() {
+                            Some("1-0") => opening.white = 1,
+                            Some("0-1") => opening.black = 1,
+                            Some("1/2-1/2") => opening.draw = 1,
+                            _ => (),
                         }
+                        e.insert(opening);
                     }
                 }
             }
-        },
-    );
+        }
+    });
 
     let openings: Vec<PositionStats> = openings.into_iter().map(|(_, v)| v).collect();
     let ids: Vec<i32> = sample_games.lock()
@@ -425,59 +435,21 @@ pub async fn is_position_in_db(
     info!("start loading games");
 
     let permit = state.new_request.acquire().await.unwrap();
-    let mut games = state.db_cache.lock().unwrap();
+    let cache = load_games(&state, &file, db)?;
+    let games = &cache.games;
 
-    if games.is_empty() {
-        *games = games::table
-            .select((
-                games::id,
-                games::white_id,
-                games::black_id,
-                games::date,
-                games::result,
-                games::moves,
-                games::fen,
-                games::pawn_home,
-                games::white_material,
-                games::black_material,
-            ))
-            .load(db)?;
-
-        info!("got {} games: {:?}", games.len(), start.elapsed());
-    }
-
-    let exists = games.par_iter().any(
-        |(
-            _id,
-            _white_id,
-            _black_id,
-            _date,
-            _result,
-            game,
-            fen,
-            end_pawn_home,
-            white_material,
-            black_material,
-        )| {
-            if state.new_request.available_permits() == 0 {
-                return false;
-            }
-            let end_material: MaterialCount = ByColor {
-                white: *white_material as u8,
-                black: *black_material as u8,
-            };
-            if let Some(position_query) = &query.position {
-                let position_query =
-                    convert_position_query(position_query.clone()).expect("Invalid position query");
-                position_query.can_reach(&end_material, *end_pawn_home as u16)
-                    && get_move_after_match(game, fen, &position_query)
-                        .unwrap_or(None)
-                        .is_some()
-            } else {
-                false
-            }
-        },
-    );
+    let exists = games.par_iter().any(|row| {
+        if state.new_request.available_permits() == 0 {
+            return false;
+        }
+        if let Some(position_query) = &query.position {
+            let position_query =
+                convert_position_query(position_query.clone()).expect("Invalid position query");
+            move_after_query(row, &position_query).is_some()
+        } else {
+            false
+        }
+    });
     info!("finished search in {:?}", start.elapsed());
     if state.new_request.available_permits() == 0 {
         drop(permit);
@@ -492,6 +464,103 @@ pub async fn is_position_in_db(
     Ok(exists)
 }
 
+/// The games in a database that reached a position, for telling what was
+/// played before a novelty.
+#[derive(Debug, Serialize, Deserialize, Clone, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionReference {
+    pub games: i32,
+    /// Moves played from the position, most played first.
+    pub continuations: Vec<PositionStats>,
+    /// The game between the highest rated players, counting a missing
+    /// rating as 0.
+    #[specta(optional)]
+    pub top_game: Option<NormalizedGame>,
+}
+
+/// Ids are looked up in chunks, below SQLite's limit on bound parameters.
+const RATING_CHUNK: usize = 10_000;
+
+pub async fn position_reference(
+    file: PathBuf,
+    fen: &str,
+    state: &tauri::State<'_, AppState>,
+) -> Result<PositionReference, Error> {
+    let db = &mut get_db_or_create(state, file.to_str().unwrap(), ConnectionOptions::default())?;
+    let position_query = PositionQuery::exact_from_fen(fen)?;
+
+    let start = Instant::now();
+    let permit = state.new_request.acquire().await.unwrap();
+    let matches: Vec<(i32, String, Option<String>)> = {
+        let cache = load_games(state, &file, db)?;
+        cache
+            .games
+            .par_iter()
+            .filter_map(|row| {
+                if state.new_request.available_permits() == 0 {
+                    return None;
+                }
+                move_after_query(row, &position_query).map(|m| (row.0, m, row.4.clone()))
+            })
+            .collect()
+    };
+    if state.new_request.available_permits() == 0 {
+        drop(permit);
+        return Err(Error::SearchStopped);
+    }
+    drop(permit);
+    info!("found {} reference games in {:?}", matches.len(), start.elapsed());
+
+    let mut continuations: Vec<PositionStats> = Vec::new();
+    for (_, m, result) in matches.iter().filter(|(_, m, _)| m != "*") {
+        let index = match continuations.iter().position(|stats| &stats.move_ == m) {
+            Some(index) => index,
+            None => {
+                continuations.push(PositionStats {
+                    move_: m.clone(),
+                    white: 0,
+                    draw: 0,
+                    black: 0,
+                });
+                continuations.len() - 1
+            }
+        };
+        let stats = &mut continuations[index];
+        match result.as_deref() {
+            Some("1-0") => stats.white += 1,
+            Some("0-1") => stats.black += 1,
+            Some("1/2-1/2") => stats.draw += 1,
+            _ => (),
+        }
+    }
+    continuations.sort_by_key(|stats| std::cmp::Reverse(stats.white + stats.draw + stats.black));
+
+    let ids: Vec<i32> = matches.iter().map(|(id, _, _)| *id).collect();
+    let mut top: Option<(i32, i32)> = None;
+    for chunk in ids.chunks(RATING_CHUNK) {
+        let ratings: Vec<(i32, Option<i32>, Option<i32>)> = games::table
+            .select((games::id, games::white_elo, games::black_elo))
+            .filter(games::id.eq_any(chunk))
+            .load(db)?;
+        for (id, white_elo, black_elo) in ratings {
+            let rating = white_elo.unwrap_or(0) + black_elo.unwrap_or(0);
+            if top.map_or(true, |(best, _)| rating > best) {
+                top = Some((rating, id));
+            }
+        }
+    }
+    let top_game = match top {
+        Some((_, id)) => Some(get_game(db, id)?),
+        None => None,
+    };
+
+    Ok(PositionReference {
+        games: matches.len() as i32,
+        continuations,
+        top_game,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,33 @@
+use diesel::{connection::SimpleConnection, prelude::*};
+
+use super::{models::NewGameSummary, schema::game_summaries};
+use crate::error::Result;
+
+/// The table as in `create.sql`, for databases created before summaries
+/// were stored.
+const CREATE_SUMMARIES_SQL: &str = "CREATE TABLE IF NOT EXISTS GameSummaries (
+    GameID INTEGER PRIMARY KEY,
+    Summary TEXT NOT NULL,
+    FOREIGN KEY(GameID) REFERENCES Games ON DELETE CASCADE
+);";
+
+pub fn create_table(conn: &mut SqliteConnection) -> Result<()> {
+    conn.batch_execute(CREATE_SUMMARIES_SQL)?;
+    Ok(())
+}
+
+/// Stores a game's summary, replacing an earlier one.
+pub fn replace_summary(conn: &mut SqliteConnection, game_id: i32, summary: &str) -> Result<()> {
+    diesel::replace_into(game_summaries::table)
+        .values(NewGameSummary { game_id, summary })
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn game_summary(conn: &mut SqliteConnection, game_id: i32) -> Result<Option<String>> {
+    Ok(game_summaries::table
+        .find(game_id)
+        .select(game_summaries::summary)
+        .first(conn)
+        .optional()?)
+}
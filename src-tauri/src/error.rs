@@ -18,6 +18,9 @@ pub enum Error {
     #[error(transparent)]
     XmlDeserialize(#[from] quick_xml::de::DeError),
 
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+
     #[error(transparent)]
     ParseInt(#[from] std::num::ParseIntError),
 
@@ -107,6 +110,45 @@ pub enum Error {
 
     #[error("Engine timeout")]
     EngineTimeout,
+
+    #[error("Engine exited unexpectedly")]
+    EngineExited,
+
+    #[error("Invalid engine: {0}")]
+    InvalidEngine(String),
+
+    #[error("Not supported by the engine: {0}")]
+    EngineUnsupported(String),
+
+    #[error("Invalid engine options: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InvalidEngineOptions(Vec<crate::chess::InvalidOption>),
+
+    #[error("Play session not found: {0}")]
+    PlaySessionNotFound(String),
+
+    #[error("Engine preset not found: {0}")]
+    PresetNotFound(String),
+
+    #[error("Engine already registered: {0}")]
+    EngineAlreadyRegistered(String),
+
+    #[error("Engine not registered: {0}")]
+    EngineNotRegistered(String),
+
+    #[error("No engines given")]
+    NoEngines,
+
+    #[error("Invalid PGN")]
+    InvalidPgn,
+
+    #[error("Batch analysis not found: {0}")]
+    BatchJobNotFound(String),
+
+    #[error("Batch analysis already running: {0}")]
+    BatchJobRunning(String),
+
+    #[error("Invalid batch analysis id: {0}")]
+    InvalidBatchJobId(String),
 }
 
 impl serde::Serialize for Error {
@@ -17,12 +17,16 @@ mod puzzle;
 mod telemetry;
 
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
 use std::{fs::create_dir_all};
 
 use fs_extra::dir::{copy, CopyOptions};
 
-use chess::{BestMovesPayload, EngineProcess, ReportProgress};
+use chess::{
+    AnalysisCache, BatchControl, BestMovesPayload, ConsensusPayload, ConsensusSession,
+    EngineProcess, EngineStatusPayload, MatchGameFinished, MatchProgress, PlaySession,
+    ReportProgress, TournamentUpdate,
+};
 use dashmap::DashMap;
 use db::{DatabaseProgress, GameQueryJs, NormalizedGame, PositionStats};
 use derivative::Derivative;
@@ -35,12 +39,21 @@ use tauri::path::BaseDirectory;
 use tauri::{AppHandle, Manager, Window};
 
 use crate::chess::{
-    analyze_game, get_engine_config, get_engine_logs, kill_engine, kill_engines, stop_engine,
+    add_engine, analyze_game, analyze_game_tree, annotate_db_game, annotate_game_pgn,
+    benchmark_engine, cancel_batch_analysis, check_engine_options, clear_analysis_cache,
+    close_play_session, delete_engine_preset, export_engine_logs, get_critical_moments,
+    get_engine_config, get_engine_logs, get_game_summary, kill_engine, kill_engines,
+    kill_multi_engine_analysis, list_batch_analyses, list_engine_presets, list_engines,
+    load_engine_preset, pause_batch_analysis, play_session_move, remove_engine,
+    resign_play_session, resume_batch_analysis, save_engine_preset, save_game_summary,
+    save_play_session, start_batch_analysis, start_engine_match, start_multi_engine_analysis,
+    start_play_session, start_tournament, stop_engine, stop_engine_match,
+    stop_multi_engine_analysis, takeback_play_session, update_engine,
 };
 use crate::db::{
-    clear_games, convert_pgn, create_indexes, delete_database, delete_db_game, delete_empty_games,
-    delete_indexes, export_to_pgn, get_player, get_players_game_info, get_tournaments,
-    search_position,
+    add_evaluations, clear_games, convert_pgn, create_indexes, delete_database, delete_db_game,
+    delete_empty_games, delete_indexes, export_to_pgn, get_evaluations, get_player,
+    get_players_game_info, get_tournaments, search_position,
 };
 use crate::fide::{download_fide_db, find_fide_player};
 use crate::fs::{set_file_as_executable, DownloadProgress};
@@ -75,6 +88,13 @@ pub type GameData = (
     i32,
 );
 
+/// The games of the database searched last, kept between position searches.
+#[derive(Default)]
+struct GameCache {
+    file: PathBuf,
+    games: Vec<GameData>,
+}
+
 #[derive(Derivative)]
 #[derivative(Default)]
 pub struct AppState {
@@ -83,12 +103,17 @@ pub struct AppState {
         diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::SqliteConnection>>,
     >,
     line_cache: DashMap<(GameQueryJs, PathBuf), (Vec<PositionStats>, Vec<NormalizedGame>)>,
-    db_cache: Mutex<Vec<GameData>>,
+    db_cache: Mutex<GameCache>,
     #[derivative(Default(value = "Arc::new(Semaphore::new(2))"))]
     new_request: Arc<Semaphore>,
     pgn_offsets: DashMap<String, Vec<u64>>,
     fide_players: RwLock<Vec<FidePlayer>>,
     engine_processes: DashMap<(String, String), Arc<tokio::sync::Mutex<EngineProcess>>>,
+    engine_matches: DashMap<String, Arc<AtomicBool>>,
+    play_sessions: DashMap<String, Arc<tokio::sync::Mutex<PlaySession>>>,
+    consensus_sessions: DashMap<String, Arc<ConsensusSession>>,
+    batch_jobs: DashMap<String, Arc<BatchControl>>,
+    analysis_cache: once_cell::sync::OnceCell<Arc<AnalysisCache>>,
     auth: AuthState,
 }
 
@@ -321,10 +346,45 @@ async fn main() {
             find_fide_player,
             get_best_moves,
             analyze_game,
+            clear_analysis_cache,
             stop_engine,
             kill_engine,
             kill_engines,
             get_engine_logs,
+            export_engine_logs,
+            save_engine_preset,
+            list_engine_presets,
+            load_engine_preset,
+            delete_engine_preset,
+            check_engine_options,
+            list_engines,
+            add_engine,
+            update_engine,
+            remove_engine,
+            benchmark_engine,
+            start_multi_engine_analysis,
+            stop_multi_engine_analysis,
+            kill_multi_engine_analysis,
+            analyze_game_tree,
+            annotate_game_pgn,
+            annotate_db_game,
+            save_game_summary,
+            get_game_summary,
+            get_critical_moments,
+            start_batch_analysis,
+            resume_batch_analysis,
+            pause_batch_analysis,
+            cancel_batch_analysis,
+            list_batch_analyses,
+            start_engine_match,
+            stop_engine_match,
+            start_tournament,
+            start_play_session,
+            play_session_move,
+            takeback_play_session,
+            resign_play_session,
+            save_play_session,
+            close_play_session,
             memory_size,
             get_puzzle,
             search_opening_name,
@@ -352,6 +412,8 @@ async fn main() {
             delete_db_game,
             delete_database,
             export_to_pgn,
+            add_evaluations,
+            get_evaluations,
             authenticate,
             write_game,
             download_fide_db,
@@ -380,9 +442,14 @@ async fn main() {
         ))
         .events(tauri_specta::collect_events!(
             BestMovesPayload,
+            EngineStatusPayload,
+            ConsensusPayload,
             DatabaseProgress,
             DownloadProgress,
-            ReportProgress
+            MatchGameFinished,
+            MatchProgress,
+            ReportProgress,
+            TournamentUpdate
         ));
 
     #[cfg(debug_assertions)]
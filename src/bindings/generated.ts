@@ -21,17 +21,25 @@ async findFidePlayer(player: string) : Promise<Result<FidePlayer | null, string>
     else return { status: "error", error: e  as any };
 }
 },
-async getBestMoves(id: string, engine: string, tab: string, goMode: GoMode, options: EngineOptions) : Promise<Result<[number, BestMoves[]] | null, string>> {
+async getBestMoves(id: string, engine: string, tab: string, goMode: GoMode, options: EngineOptions, launch: EngineLaunch | null) : Promise<Result<[number, BestMoves[]] | null, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("get_best_moves", { id, engine, tab, goMode, options }) };
+    return { status: "ok", data: await TAURI_INVOKE("get_best_moves", { id, engine, tab, goMode, options, launch }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
 },
-async analyzeGame(id: string, engine: string, goMode: GoMode, options: AnalysisOptions, uciOptions: EngineOption[]) : Promise<Result<MoveAnalysis[], string>> {
+async analyzeGame(id: string, engine: string, goMode: GoMode, options: AnalysisOptions, uciOptions: EngineOption[], launch: EngineLaunch | null) : Promise<Result<GameAnalysis, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("analyze_game", { id, engine, goMode, options, uciOptions }) };
+    return { status: "ok", data: await TAURI_INVOKE("analyze_game", { id, engine, goMode, options, uciOptions, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async clearAnalysisCache(engine: string | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("clear_analysis_cache", { engine }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -69,6 +77,350 @@ async getEngineLogs(engine: string, tab: string) : Promise<Result<EngineLog[], s
     else return { status: "error", error: e  as any };
 }
 },
+/**
+ * Writes an engine's log to a text file, one line per message.
+ */
+async exportEngineLogs(engine: string, tab: string, file: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_engine_logs", { engine, tab, file }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async saveEnginePreset(preset: EnginePreset, launch: EngineLaunch | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_engine_preset", { preset, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listEnginePresets(engine: string) : Promise<Result<EnginePreset[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_engine_presets", { engine }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async loadEnginePreset(engine: string, name: string) : Promise<Result<EnginePreset, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("load_engine_preset", { engine, name }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async deleteEnginePreset(engine: string, name: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("delete_engine_preset", { engine, name }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Starts the engine and lists the options it would reject, so they can be
+ * shown next to the settings instead of as one error message.
+ */
+async checkEngineOptions(path: string, options: EngineOption[], launch: EngineLaunch | null) : Promise<Result<InvalidOption[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("check_engine_options", { path, options, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listEngines() : Promise<Result<RegisteredEngine[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_engines") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Registers an engine after checking that it starts and speaks UCI or CECP.
+ */
+async addEngine(path: string, name: string | null, launch: EngineLaunch | null) : Promise<Result<RegisteredEngine, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("add_engine", { path, name, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Saves user changes to an engine. The engine is probed again when its
+ * binary or launch settings changed, or it has never been probed.
+ */
+async updateEngine(engine: RegisteredEngine) : Promise<Result<RegisteredEngine, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("update_engine", { engine }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async removeEngine(path: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("remove_engine", { path }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Benchmarks an engine with its built-in `bench` when it has one, or by
+ * searching a fixed set of positions to `depth` otherwise. The result is
+ * stored with the engine's registry entry when it is registered.
+ */
+async benchmarkEngine(id: string, engine: string, depth: number | null, options: EngineOption[], launch: EngineLaunch | null) : Promise<Result<EngineBenchmark, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("benchmark_engine", { id, engine, depth, options, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Analyses one position with several engines at once and emits their lines
+ * together with how much they agree. Calling it again with the same engines
+ * moves them to the new position; other engines replace the session.
+ */
+async startMultiEngineAnalysis(id: string, engines: ConsensusEngine[], goMode: GoMode, options: EngineOptions) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_multi_engine_analysis", { id, engines, goMode, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Stops every engine's search; the engines stay up for the next position.
+ */
+async stopMultiEngineAnalysis(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("stop_multi_engine_analysis", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async killMultiEngineAnalysis(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("kill_multi_engine_analysis", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Analyses every position of a PGN game, sidelines and nested variations
+ * included. Finished positions (mate, stalemate) are skipped.
+ */
+async analyzeGameTree(id: string, engine: string, goMode: GoMode, options: TreeAnalysisOptions, uciOptions: EngineOption[], launch: EngineLaunch | null) : Promise<Result<NodeAnalysis[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("analyze_game_tree", { id, engine, goMode, options, uciOptions, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Writes a game analysis into the first game of a PGN, returning the
+ * annotated PGN. Headers are kept as they are.
+ */
+async annotateGamePgn(pgn: string, analysis: MoveAnalysis[], options: AnnotateOptions) : Promise<Result<string, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("annotate_game_pgn", { pgn, analysis, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Writes a game analysis into a database game's moves.
+ */
+async annotateDbGame(file: string, gameId: number, analysis: MoveAnalysis[], options: AnnotateOptions) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("annotate_db_game", { file, gameId, analysis, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Stores a game's analysis summary in its database, replacing an earlier
+ * one.
+ */
+async saveGameSummary(file: string, gameId: number, summary: GameSummary) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_game_summary", { file, gameId, summary }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getGameSummary(file: string, gameId: number) : Promise<Result<GameSummary | null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_game_summary", { file, gameId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Ranks the critical moments of an analysed game, from `analyze_game`.
+ * Moves are classified again, so analyses stored before classes were
+ * added work too.
+ */
+async getCriticalMoments(fen: string, moves: string[], analysis: MoveAnalysis[]) : Promise<Result<CriticalMoment[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_critical_moments", { fen, moves, analysis }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Analyses every game of a database matching `query`, storing one
+ * evaluation per main line position. Resolves when the job finishes or is
+ * paused or cancelled.
+ */
+async startBatchAnalysis(id: string, file: string, query: GameQueryJs, engine: string, goMode: GoMode, uciOptions: EngineOption[], launch: EngineLaunch | null) : Promise<Result<BatchJob, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_batch_analysis", { id, file, query, engine, goMode, uciOptions, launch }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Continues a paused job, or one interrupted by the app closing.
+ */
+async resumeBatchAnalysis(id: string) : Promise<Result<BatchJob, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_batch_analysis", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async pauseBatchAnalysis(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pause_batch_analysis", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async cancelBatchAnalysis(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_batch_analysis", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Saved jobs, including ones a crash left marked as running.
+ */
+async listBatchAnalyses() : Promise<Result<BatchJob[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_batch_analyses") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async startEngineMatch(id: string, first: MatchEngine, second: MatchEngine, settings: MatchSettings) : Promise<Result<MatchResult, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_engine_match", { id, first, second, settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Stops a running match or tournament after the games in progress are aborted.
+ */
+async stopEngineMatch(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("stop_engine_match", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async startTournament(id: string, engines: MatchEngine[], settings: TournamentSettings) : Promise<Result<TournamentResult, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_tournament", { id, engines, settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async startPlaySession(id: string, engine: MatchEngine, settings: PlaySettings) : Promise<Result<PlayState, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_play_session", { id, engine, settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Plays the user's move and, unless the game ended, waits for the engine's reply.
+ */
+async playSessionMove(id: string, uciMove: string) : Promise<Result<PlayState, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("play_session_move", { id, uciMove }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Takes back the user's last move together with the engine's reply. When
+ * the engine moved first and there is no user move to go back to, the
+ * engine plays its first move again.
+ */
+async takebackPlaySession(id: string) : Promise<Result<PlayState, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("takeback_play_session", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async resignPlaySession(id: string) : Promise<Result<PlayState, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resign_play_session", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Saves the session's game into a database and returns its PGN.
+ */
+async savePlaySession(id: string, file: string, white: string, black: string) : Promise<Result<string, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_play_session", { id, file, white, black }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async closePlaySession(id: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("close_play_session", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async memorySize() : Promise<bigint> {
     return await TAURI_INVOKE("memory_size");
 },
@@ -130,9 +482,9 @@ async getPlayersGameInfo(file: string, id: number) : Promise<Result<PlayerGameIn
     else return { status: "error", error: e  as any };
 }
 },
-async getEngineConfig(path: string) : Promise<Result<EngineConfig, string>> {
+async getEngineConfig(path: string, launch: EngineLaunch | null) : Promise<Result<EngineConfig, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("get_engine_config", { path }) };
+    return { status: "ok", data: await TAURI_INVOKE("get_engine_config", { path, launch }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -232,9 +584,9 @@ async deleteEmptyGames(file: string) : Promise<Result<null, string>> {
 async clearGames() : Promise<void> {
     await TAURI_INVOKE("clear_games");
 },
-async setFileAsExecutable(path: string) : Promise<Result<null, string>> {
+async setFileAsExecutable(Path: string) : Promise<Result<null, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("set_file_as_executable", { path }) };
+    return { status: "ok", data: await TAURI_INVOKE("set_file_as_executable", { Path }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -280,9 +632,29 @@ async deleteDatabase(file: string) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async exportToPgn(file: string, destFile: string) : Promise<Result<null, string>> {
+async exportToPgn(file: string, destFile: string, includeEvals: boolean | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_to_pgn", { file, destFile, includeEvals }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Stores engine evaluations, replacing earlier ones of the same games by
+ * the same engines.
+ */
+async addEvaluations(file: string, evaluations: NewEvaluation[]) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("add_evaluations", { file, evaluations }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getEvaluations(file: string, gameId: number, engine: string | null) : Promise<Result<Evaluation[], string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("export_to_pgn", { file, destFile }) };
+    return { status: "ok", data: await TAURI_INVOKE("get_evaluations", { file, gameId, engine }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -524,14 +896,24 @@ async openExternalLink(url: string) : Promise<Result<null, string>> {
 
 export const events = __makeEvents__<{
 bestMovesPayload: BestMovesPayload,
+consensusPayload: ConsensusPayload,
 databaseProgress: DatabaseProgress,
 downloadProgress: DownloadProgress,
-reportProgress: ReportProgress
+engineStatusPayload: EngineStatusPayload,
+matchGameFinished: MatchGameFinished,
+matchProgress: MatchProgress,
+reportProgress: ReportProgress,
+tournamentUpdate: TournamentUpdate
 }>({
 bestMovesPayload: "best-moves-payload",
+consensusPayload: "consensus-payload",
 databaseProgress: "database-progress",
 downloadProgress: "download-progress",
-reportProgress: "report-progress"
+engineStatusPayload: "engine-status-payload",
+matchGameFinished: "match-game-finished",
+matchProgress: "match-progress",
+reportProgress: "report-progress",
+tournamentUpdate: "tournament-update"
 })
 
 /** user-defined constants **/
@@ -540,35 +922,498 @@ reportProgress: "report-progress"
 
 /** user-defined types **/
 
-export type AnalysisOptions = { fen: string; moves: string[]; annotateNovelties: boolean; referenceDb: string | null; reversed: boolean }
-export type BestMoves = { nodes: number; depth: number; score: Score; uciMoves: string[]; sanMoves: string[]; multipv: number; nps: number }
-export type BestMovesPayload = { bestLines: BestMoves[]; engine: string; tab: string; fen: string; moves: string[]; progress: number }
+/**
+ * Rules for ending a game early based on the engines' own evaluations.
+ */
+export type Adjudication = { 
+/**
+ * Resign when the mover's score stays below `-resign_score` centipawns...
+ */
+resignScore?: number | null; 
+/**
+ * ...for this many consecutive moves of that engine.
+ */
+resignMoveCount: number; 
+/**
+ * Declare a draw when both engines report `|score| <= draw_score`...
+ */
+drawScore?: number | null; 
+/**
+ * ...for this many consecutive plies...
+ */
+drawMoveCount: number; 
+/**
+ * ...starting at this full move number.
+ */
+drawMoveNumber: number; 
+/**
+ * Declare a draw once the game reaches this many full moves.
+ */
+maxMoves?: number | null }
+/**
+ * How much the engines agree on the position, judged by their first lines.
+ */
+export type Agreement = { 
+/**
+ * The best move most engines chose; ties go to the engine listed first.
+ */
+bestMove?: string | null; 
+/**
+ * Engines whose best move is `best_move`.
+ */
+agreeing: number; 
+/**
+ * Engines that have reported a line.
+ */
+reporting: number; 
+/**
+ * Every reporting engine chose `best_move`.
+ */
+unanimous: boolean; 
+/**
+ * Difference between the highest and lowest evaluation in centipawns,
+ * when at least two engines report and none sees a mate.
+ */
+evalSpread?: number | null }
+export type AnalysisOptions = { fen: string; moves: string[]; annotateNovelties: boolean; referenceDb: string | null; reversed: boolean; 
+/**
+ * Analyze the best alternative to each played move instead.
+ */
+excludePlayedMoves: boolean; 
+/**
+ * A saved preset for this engine, applied under `uci_options`.
+ */
+preset?: string | null }
+export type AnnotateOptions = { 
+/**
+ * Move-quality NAGs from the classifications.
+ */
+nags: boolean; 
+/**
+ * `[%eval]` comments with the score after every move.
+ */
+evals: boolean; 
+/**
+ * The engine's line after inaccuracies and worse moves.
+ */
+variations: boolean; 
+/**
+ * A comment on the novelty saying what was played before it.
+ */
+novelties: boolean }
+/**
+ * A database analysis job, saved after every game.
+ */
+export type BatchJob = { id: string; file: string; engine: string; goMode: GoMode; uciOptions: EngineOption[]; launch?: EngineLaunch | null; 
+/**
+ * The games to analyse, in order, fixed when the job starts.
+ */
+gameIds: number[]; 
+/**
+ * Games done; the job resumes with `game_ids[completed]`.
+ */
+completed: number; 
+/**
+ * Games that could not be read, skipped.
+ */
+failed: number[]; status: BatchStatus }
+export type BatchStatus = "running" | 
+/**
+ * Stopped by the user or by the app exiting; can be resumed.
+ */
+"paused" | "cancelled" | "finished"
+export type BenchmarkMethod = 
+/**
+ * The engine's own `bench` command.
+ */
+"bench" | 
+/**
+ * `BENCH_POSITIONS` searched to a fixed depth.
+ */
+"positions"
+export type BestMoves = { nodes: number; depth: number; seldepth: number; score: Score; 
+/**
+ * The score is only a bound (white-relative), not an exact value.
+ */
+lowerBound: boolean; upperBound: boolean; uciMoves: string[]; sanMoves: string[]; multipv: number; nps: number; 
+/**
+ * Hash table usage in permille.
+ */
+hashfull: number; tbhits: number; 
+/**
+ * Search time in milliseconds.
+ */
+time: number }
+export type BestMovesPayload = { bestLines: BestMoves[]; engine: string; tab: string; fen: string; moves: string[]; progress: number; currentMove: CurrentMove | null }
+/**
+ * One engine taking part in a multi-engine analysis.
+ */
+export type ConsensusEngine = { 
+/**
+ * The engine path, as passed to the analysis commands.
+ */
+engine: string; launch?: EngineLaunch | null; 
+/**
+ * Options for this engine only, overriding the shared ones.
+ */
+options: EngineOption[] }
+export type ConsensusPayload = { id: string; fen: string; moves: string[]; 
+/**
+ * In the order the engines were given.
+ */
+engines: EngineLines[]; agreement: Agreement; 
+/**
+ * The progress of the slowest engine.
+ */
+progress: number }
+export type CriticalKind = 
+/**
+ * A mistake or blunder that swung the game.
+ */
+"turningPoint" | 
+/**
+ * A won position let slip.
+ */
+"missedWin" | 
+/**
+ * Only one move held, and it was found.
+ */
+"onlyMove"
+/**
+ * A move worth going back to, with what is needed to explain it or to
+ * make a puzzle of the position before it.
+ */
+export type CriticalMoment = { kind: CriticalKind; 
+/**
+ * Index of the move, and of the analysis of the position before it.
+ */
+index: number; moveNumber: number; color: PlayerColor; 
+/**
+ * The position before the move.
+ */
+fen: string; played: string; playedUci: string; best?: string | null; bestUci?: string | null; 
+/**
+ * The engine's line from the position, in SAN.
+ */
+bestLine: string[]; 
+/**
+ * The mover's win chance in percent, before and after the move.
+ */
+winBefore: number; winAfter: number; 
+/**
+ * How much worse, in win chance, the second best move was.
+ */
+secondBestLoss?: number | null; classification?: MoveClassification | null; isSacrifice: boolean; 
+/**
+ * The win chance at stake, which moments are ranked by: what a turning
+ * point or missed win lost, or what any other move than an only move
+ * would have.
+ */
+weight: number }
+/**
+ * Root move the engine is searching, from `currmove`/`currmovenumber`.
+ */
+export type CurrentMove = { uciMove: string; number: number }
 export type DatabaseInfo = { title: string; description: string; player_count: number; event_count: number; game_count: number; storage_size: number; filename: string; indexed: boolean }
 export type DatabaseProgress = { id: string; progress: number }
 export type DownloadProgress = { progress: number; id: string; finished: boolean }
+/**
+ * Elo difference with a 95% confidence margin and likelihood of superiority.
+ */
+export type EloEstimate = { elo: number; error: number; los: number }
+/**
+ * One benchmark run. Runs of the same method and depth can be compared
+ * across builds and machines: equal signatures mean the engines searched
+ * the same trees, so only the speed differs.
+ */
+export type EngineBenchmark = { engine: string; method: BenchmarkMethod; 
+/**
+ * The fixed depth, for `Positions` runs.
+ */
+depth?: number | null; totalNodes: bigint; totalTimeMs: bigint; nps: bigint; 
+/**
+ * The node count, like Stockfish's bench signature. Only reproducible
+ * with a single search thread.
+ */
+signature: string; 
+/**
+ * Empty for `Bench` runs, which only report totals.
+ */
+positions: PositionBenchmark[]; binaryHash?: string | null; 
+/**
+ * RFC 3339 time the run finished.
+ */
+date: string }
 export type EngineConfig = { name: string; options: UciOptionConfig[] }
-export type EngineLog = { type: "gui"; value: string } | { type: "engine"; value: string }
+/**
+ * How an engine process is started, stored alongside the engine definition.
+ */
+export type EngineLaunch = { 
+/**
+ * Arguments passed after the executable, e.g. `--weights=net.pb.gz`.
+ */
+args: string[]; 
+/**
+ * Extra environment variables; these override the defaults we set.
+ */
+env: Partial<{ [key in string]: string }>; 
+/**
+ * Defaults to the directory of the executable.
+ */
+workingDir?: string | null; 
+/**
+ * Start the engine again, resuming the search, if it crashes during
+ * live analysis.
+ */
+autoRestart: boolean; 
+/**
+ * Milliseconds to wait for a reply to `uci` before trying CECP, for
+ * engines that are slow to start.
+ */
+uciTimeout?: number | null }
+export type EngineLines = { engine: string; lines: BestMoves[]; progress: number; 
+/**
+ * The engine sent `bestmove` or stopped responding.
+ */
+finished: boolean }
+export type EngineLog = { type: "gui"; value: string } | { type: "engine"; value: string } | { type: "stderr"; value: string }
 export type EngineOption = { name: string; value: string }
-export type EngineOptions = { fen: string; moves: string[]; extraOptions: EngineOption[] }
+export type EngineOptions = { fen: string; moves: string[]; extraOptions: EngineOption[]; 
+/**
+ * Only search these root moves.
+ */
+searchMoves: string[]; 
+/**
+ * Search every legal root move except these.
+ */
+excludeMoves: string[]; 
+/**
+ * A saved preset for this engine, applied under `extra_options`.
+ */
+preset?: string | null }
+/**
+ * A named set of options for one engine, such as "Long analysis" with a
+ * bigger hash and more threads.
+ */
+export type EnginePreset = { 
+/**
+ * The engine path, as passed to the analysis commands.
+ */
+engine: string; name: string; options: EngineOption[] }
+export type EngineStatus = { type: "started" } | { type: "crashed"; 
+/**
+ * `None` for remote engines and processes killed by a signal.
+ */
+exitCode: number | null; 
+/**
+ * The last lines the engine wrote to stderr.
+ */
+stderr: string[] } | { type: "restarted"; attempt: number }
+/**
+ * Tells the UI when an engine starts, dies or is brought back, since a
+ * crashed engine otherwise looks like one that is still thinking.
+ */
+export type EngineStatusPayload = { engine: string; 
+/**
+ * The analysis id for game analysis.
+ */
+tab: string; status: EngineStatus }
+/**
+ * An engine's evaluation of the position after `ply` main line moves
+ * (0 is the start position), white-relative.
+ */
+export type Evaluation = { id: number; gameId: number; ply: number; engine: string; depth: number; cp: number | null; mate: number | null; wdlWin: number | null; wdlDraw: number | null; wdlLoss: number | null; 
+/**
+ * In UCI notation.
+ */
+bestMove: string | null }
 export type Event = { id: number; name: string | null }
+/**
+ * A scheduled game that could not be played, for example because its
+ * opening is not a legal position. Engines that fail forfeit instead.
+ */
+export type FailedGame = { round: number; white: string; black: string; error: string }
 export type FidePlayer = { fideid: number; name: string; country: string; sex: string; title: string | null; w_title: string | null; o_title: string | null; foa_title: string | null; rating: number | null; games: number | null; k: number | null; rapid_rating: number | null; rapid_games: number | null; rapid_k: number | null; blitz_rating: number | null; blitz_games: number | null; blitz_k: number | null; birthday: number | null; flag: string | null }
 export type FileMetadata = { last_modified: number }
+export type GameAnalysis = { moves: MoveAnalysis[]; 
+/**
+ * `None` when the played moves were left out of the search, since
+ * there is nothing to measure them against.
+ */
+summary?: GameSummary | null }
 export type GameOutcome = "Won" | "Drawn" | "Lost"
 export type GameQueryJs = { options?: QueryOptions<GameSort> | null; player1?: number | null; player2?: number | null; tournament_id?: number | null; start_date?: string | null; end_date?: string | null; range1?: [number, number] | null; range2?: [number, number] | null; sides?: Sides | null; outcome?: string | null; position?: PositionQueryJs | null; wanted_result?: string | null }
 export type GameSort = "id" | "date" | "whiteElo" | "blackElo" | "ply_count"
+export type GameSummary = { white: PlayerSummary; black: PlayerSummary }
 export type GoMode = { t: "PlayersTime"; c: PlayersTime } | { t: "Depth"; c: number } | { t: "Time"; c: number } | { t: "Nodes"; c: number } | { t: "Infinite" }
-export type MoveAnalysis = { best: BestMoves[]; novelty: boolean; is_sacrifice: boolean }
+/**
+ * An option value rejected before being sent to the engine.
+ */
+export type InvalidOption = { name: string; value: string; reason: InvalidReason }
+export type InvalidReason = 
+/**
+ * The engine does not advertise the option.
+ */
+{ t: "Unknown" } | { t: "NotBoolean" } | { t: "NotInteger" } | { t: "OutOfRange"; c: { min: bigint | null; max: bigint | null } } | { t: "NotInCombo"; c: string[] }
+export type MatchEngine = { name: string; path: string; options: EngineOption[]; launch?: EngineLaunch | null }
+export type MatchGame = { round: number; white: string; black: string; result: Outcome; termination: Termination; moves: string[]; pgn: string }
+export type MatchGameFinished = { id: string; game: MatchGame }
+export type MatchOpening = { fen: string; moves: string[] }
+export type MatchProgress = { id: string; round: number; totalRounds: number; fen: string; moves: string[]; whiteClock: number; blackClock: number; score: Score | null }
+export type MatchResult = { games: MatchGame[]; 
+/**
+ * Score of the first engine: wins, draws, losses.
+ */
+wins: number; draws: number; losses: number }
+export type MatchSettings = { event: string; timeControl: MatchTimeControl; openings: MatchOpening[]; 
+/**
+ * Play every opening twice with colors reversed.
+ */
+swapColors: boolean; rounds: number; adjudication: Adjudication; pgnPath: string; 
+/**
+ * Let engines think on their opponent's time.
+ */
+ponder: boolean }
+/**
+ * Clock settings, in milliseconds.
+ */
+export type MatchTimeControl = { initial: number; increment: number }
+export type MoveAnalysis = { best: BestMoves[]; novelty: boolean; is_sacrifice: boolean; 
+/**
+ * White's chance to win the position, in percent.
+ */
+win_chance?: number | null; 
+/**
+ * How good the move played from this position was.
+ */
+classification?: MoveClassification | null; 
+/**
+ * What the reference database had, on the position reached by the
+ * novelty.
+ */
+novelty_info?: Novelty | null }
+export type MoveClassification = "best" | "excellent" | "good" | "inaccuracy" | "mistake" | "blunder" | 
+/**
+ * A won position let slip.
+ */
+"missedWin" | 
+/**
+ * The best move, when every other move is much worse.
+ */
+"onlyMove" | 
+/**
+ * An only move that sacrifices material.
+ */
+"brilliant"
+export type NewEvaluation = { gameId: number; ply: number; engine: string; depth: number; cp: number | null; mate: number | null; wdlWin: number | null; wdlDraw: number | null; wdlLoss: number | null; bestMove: string | null }
+/**
+ * The analysis of one node of a game tree.
+ */
+export type NodeAnalysis = { 
+/**
+ * Child indices from the root, as the frontend addresses tree nodes;
+ * empty for the start position.
+ */
+path: number[]; fen: string; 
+/**
+ * Empty when the engine failed on this node.
+ */
+best: BestMoves[]; 
+/**
+ * Why the engine failed on this node.
+ */
+error?: string | null }
 export type NormalizedGame = { id: number; fen: string; event: string; event_id: number; site: string; site_id: number; date?: string | null; time?: string | null; round?: string | null; white: string; white_id: number; white_elo?: number | null; black: string; black_id: number; black_elo?: number | null; result: Outcome; time_control?: string | null; eco?: string | null; ply_count?: number | null; moves: string }
+/**
+ * The move that left the reference database, with what had been played in
+ * the last position it knows.
+ */
+export type Novelty = { moveNumber: number; color: PlayerColor; san: string; reference: PositionReference }
 export type OutOpening = { name: string; fen: string }
 export type Outcome = "1-0" | "0-1" | "1/2-1/2" | "*"
 export type PackageManagerResult = { success: boolean; stdout: string; stderr: string }
+export type PairResult = { first: string; second: string; 
+/**
+ * Result from the point of view of `first`.
+ */
+score: Wdl; elo: EloEstimate }
+export type PlaySettings = { fen: string; 
+/**
+ * Color played by the engine.
+ */
+engineColor: PlayerColor; timeControl: MatchTimeControl; 
+/**
+ * Target strength, applied through `UCI_LimitStrength`/`UCI_Elo`, or
+ * approximated with `Skill Level` when that is all the engine offers.
+ */
+elo?: number | null; 
+/**
+ * Explicit `Skill Level`, used when no Elo is requested.
+ */
+skillLevel?: bigint | null; 
+/**
+ * Let the engine think on the user's time.
+ */
+ponder: boolean }
+export type PlayState = { id: string; fen: string; moves: string[]; 
+/**
+ * Remaining time in milliseconds.
+ */
+whiteClock: number; blackClock: number; result: Outcome; termination: Termination | null; 
+/**
+ * The engine's reply to the last user move, if it made one.
+ */
+engineMove: string | null }
 export type Player = { id: number; name: string | null; elo: number | null }
+export type PlayerColor = "white" | "black"
 export type PlayerGameInfo = { site_stats_data: SiteStatsData[] }
 export type PlayerQuery = { options: QueryOptions<PlayerSort>; name?: string | null; range?: [number, number] | null }
 export type PlayerSort = "id" | "name" | "elo"
+/**
+ * One player's side of a game analysis.
+ */
+export type PlayerSummary = { 
+/**
+ * Average centipawn loss, with scores capped like the win chances.
+ */
+acpl: number; 
+/**
+ * From 0 to 100, from the win chance lost on every move.
+ */
+accuracy: number; 
+/**
+ * Moves with analysed positions on both sides; the others are left
+ * out of every figure.
+ */
+moves: number; inaccuracies: number; mistakes: number; blunders: number; missedWins: number; 
+/**
+ * Moves after which the player was winning or losing, by the same
+ * win chance bounds the classification uses.
+ */
+winningMoves: number; losingMoves: number }
 export type PlayersTime = { white: number; black: number; winc: number; binc: number }
+export type PositionBenchmark = { fen: string; depth: number; nodes: bigint; 
+/**
+ * Time until the engine reached the target depth.
+ */
+timeMs: bigint; nps: bigint }
 export type PositionQueryJs = { fen: string; type_: string }
+/**
+ * The games in a database that reached a position, for telling what was
+ * played before a novelty.
+ */
+export type PositionReference = { games: number; 
+/**
+ * Moves played from the position, most played first.
+ */
+continuations: PositionStats[]; 
+/**
+ * The game between the highest rated players, counting a missing
+ * rating as 0.
+ */
+topGame?: NormalizedGame | null }
 export type PositionStats = { move: string; white: number; draw: number; black: number }
+export type ProtocolKind = "uci" | "xboard"
 export type Puzzle = { id: number; fen: string; moves: string; rating: number; rating_deviation: number; popularity: number; nb_plays: number }
 /**
  * Information about a puzzle database
@@ -597,6 +1442,28 @@ storageSize: number;
 path: string }
 export type QueryOptions<SortT> = { skipCount: boolean; page?: number | null; pageSize?: number | null; sort: SortT; direction: SortDirection }
 export type QueryResponse<T> = { data: T; count: number | null }
+/**
+ * A local or remote engine known to the app. The path identifies the
+ * engine, as it does in every analysis command.
+ */
+export type RegisteredEngine = { name: string; path: string; launch: EngineLaunch; 
+/**
+ * Detected when the engine is added; `None` for migrated engines until
+ * they are updated.
+ */
+protocol?: ProtocolKind | null; 
+/**
+ * The name the engine reports with `id name`.
+ */
+engineId?: string | null; version?: string | null; 
+/**
+ * Hash of the executable, used to notice when the engine was replaced.
+ */
+binaryHash?: string | null; options: EngineOption[]; go?: GoMode | null; elo?: number | null; image?: string | null; enabled: boolean; 
+/**
+ * Past benchmark runs, oldest first.
+ */
+benchmarks: EngineBenchmark[] }
 export type ReportProgress = { progress: number; id: string; finished: boolean }
 export type Score = { value: ScoreValue; 
 /**
@@ -615,11 +1482,56 @@ export type ScoreValue =
 export type Sides = "BlackWhite" | "WhiteBlack" | "Any"
 export type SiteStatsData = { site: string; player: string; data: StatsData[] }
 export type SortDirection = "asc" | "desc"
+export type Standing = { engine: string; points: number; score: Wdl; elo: EloEstimate }
 export type StatsData = { date: string; is_player_white: boolean; player_elo: number; result: GameOutcome; time_control: string; opening: string }
 export type TelemetryConfig = { enabled: boolean; initial_run_completed: boolean }
+export type Termination = "checkmate" | "stalemate" | "insufficientMaterial" | "fiftyMoves" | "repetition" | "resignation" | "drawAdjudication" | "maxMoves" | "timeForfeit" | "illegalMove" | "engineFailure" | "aborted"
 export type Token = { type: "ParenOpen" } | { type: "ParenClose" } | { type: "Comment"; value: string } | { type: "San"; value: string } | { type: "Header"; value: { tag: string; value: string } } | { type: "Nag"; value: string } | { type: "Outcome"; value: string }
+export type TournamentKind = 
+/**
+ * Every engine plays every other engine.
+ */
+"roundRobin" | 
+/**
+ * The first engine plays every other engine.
+ */
+"gauntlet"
 export type TournamentQuery = { options: QueryOptions<TournamentSort>; name: string | null }
+export type TournamentResult = { standings: Standing[]; pairs: PairResult[]; 
+/**
+ * `crosstable[i][j]` is the score of engine `i` against engine `j`.
+ */
+crosstable: Wdl[][]; games: MatchGame[]; 
+/**
+ * Games left out of the scores.
+ */
+failed: FailedGame[] }
+export type TournamentSettings = { kind: TournamentKind; 
+/**
+ * Maximum number of games played at the same time. Capped by the
+ * number of physical cores, since every game runs two engines.
+ */
+concurrency?: number | null; 
+/**
+ * Per-game settings. Every opening is always played with both colors.
+ */
+games: MatchSettings }
 export type TournamentSort = "id" | "name"
+export type TournamentUpdate = { id: string; gamesPlayed: number; 
+/**
+ * Games that could not be played. The tournament is complete once
+ * these and `games_played` add up to `total_games`.
+ */
+gamesFailed: number; totalGames: number; standings: Standing[]; crosstable: Wdl[][] }
+export type TreeAnalysisOptions = { 
+/**
+ * The game to analyse; only the first game of the PGN is used.
+ */
+pgn: string; 
+/**
+ * A saved preset for this engine, applied under `uci_options`.
+ */
+preset?: string | null }
 /**
  * Represents a UCI option definition.
  */
@@ -693,6 +1605,7 @@ name: string;
  */
 default: string | null } }
 export type UpdateGame = { fen: string; event: string; site: string; date?: string | null; time?: string | null; round?: string | null; white: string; white_elo?: number | null; black: string; black_elo?: number | null; result: Outcome; time_control?: string | null; eco?: string | null; ply_count?: number | null; moves: string }
+export type Wdl = { wins: number; draws: number; losses: number }
 
 /** tauri-specta globals **/
 
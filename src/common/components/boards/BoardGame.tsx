@@ -380,7 +380,10 @@ function BoardGame() {
                 ...s,
                 value: s.value?.toString() ?? "",
               })),
+            searchMoves: [],
+            excludeMoves: [],
           },
+          null,
         );
       }
     }
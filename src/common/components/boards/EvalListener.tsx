@@ -194,6 +194,8 @@ function EngineListener({
             moves: searchingMoves,
             fen: searchingFen,
             extraOptions: options,
+            searchMoves: [],
+            excludeMoves: [],
           }).then((moves) => {
             if (moves) {
               const [progress, bestMoves] = moves;
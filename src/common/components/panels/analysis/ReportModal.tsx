@@ -93,12 +93,14 @@ function ReportModal({
           referenceDb,
           reversed: form.values.reversed,
           moves,
+          excludePlayedMoves: false,
         },
         engineSettings,
+        null,
       )
       .then((analysis) => {
         const analysisData = unwrap(analysis);
-        addAnalysis(analysisData);
+        addAnalysis(analysisData.moves);
       })
       .finally(() => setInProgress(false));
   }
@@ -391,7 +391,7 @@ export default function DatabasesPage() {
                           });
                           if (!destFile) return;
                           setExportLoading(true);
-                          await commands.exportToPgn(selectedDatabase.file, destFile);
+                          await commands.exportToPgn(selectedDatabase.file, destFile, null);
                           setExportLoading(false);
                         }}
                       >
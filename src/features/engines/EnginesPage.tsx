@@ -263,7 +263,7 @@ function EngineSettings({ selected, setSelected }: { selected: number; setSelect
           return;
         }
 
-        const result = await commands.getEngineConfig(engine.path);
+        const result = await commands.getEngineConfig(engine.path, null);
         if (cancelled) return;
 
         if (result.status === "ok") {
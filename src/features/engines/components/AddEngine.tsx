@@ -224,7 +224,7 @@ function EngineCard({ engine, engineId }: { engine: LocalEngine; engineId: numbe
           throw new Error(`Unsupported installation method: ${engine.installMethod}`);
         }
 
-        const configResult = await commands.getEngineConfig(enginePath);
+        const configResult = await commands.getEngineConfig(enginePath, null);
         const config = configResult.status === "ok" ? configResult.data : { name: engine.name, options: [] };
 
         setEngines(async (prev) => [
@@ -47,7 +47,7 @@ export default function EngineForm({
             filters,
           });
           if (!selected) return;
-          const configResult = await commands.getEngineConfig(selected as string);
+          const configResult = await commands.getEngineConfig(selected as string, null);
           config.current = configResult.status === "ok" ? configResult.data : { name: "", options: [] };
           form.setFieldValue("path", selected as string);
           form.setFieldValue("name", config.current.name || "Unknown Engine");
@@ -103,9 +103,11 @@ function PracticeBoard({
               value: s.value?.toString() ?? "",
             })),
         ],
+        searchMoves: [],
+        excludeMoves: [],
       };
 
-      commands.getBestMoves(engine.name, engine.path, engineTabRef.current, goMode, options);
+      commands.getBestMoves(engine.name, engine.path, engineTabRef.current, goMode, options, null);
     } catch (error) {
       console.error("Engine move failed:", error);
       engineThinkingRef.current = false;
@@ -53,8 +53,14 @@ export async function getBestMoves(
         score: { value: chessDBevalToScore(m.score), wdl: null },
         nodes: 0,
         depth: m.depth ?? 0,
+        seldepth: 0,
+        lowerBound: false,
+        upperBound: false,
         multipv: i + 1,
         nps: 0,
+        hashfull: 0,
+        tbhits: 0,
+        time: 0,
         sanMoves: m.san,
         uciMoves: m.uci,
       })),
@@ -413,7 +413,7 @@ export function getBestMoves(
   goMode: GoMode,
   options: EngineOptions,
 ): Promise<[number, BestMoves[]] | null> {
-  return commands.getBestMoves(engine.name, engine.path, tab, goMode, options).then((r) => unwrap(r));
+  return commands.getBestMoves(engine.name, engine.path, tab, goMode, options, null).then((r) => unwrap(r));
 }
 
 export function useDefaultEngines(os: Platform | undefined, opened: boolean) {
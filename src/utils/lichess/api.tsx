@@ -309,8 +309,14 @@ export async function getBestMoves(
         },
         nodes: data.knodes * 1000,
         depth: data.depth,
+        seldepth: 0,
+        lowerBound: false,
+        upperBound: false,
         multipv: i + 1,
         nps: 0,
+        hashfull: 0,
+        tbhits: 0,
+        time: 0,
         sanMoves,
         uciMoves: normalizedUciMoves,
       };